@@ -1,5 +1,18 @@
 use eframe::egui::{self, Sense};
-use std::process::Command;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::mpsc::{Receiver, channel};
+use std::thread;
+
+use crate::config::glob_match;
+use crate::diff_viewer::format_size;
+use crate::file_colors::FileColors;
+use crate::i18n::{tr, trf};
+use crate::jj::{self, DiffSource, FileStat};
+use crate::review::{FileReview, Review};
+use crate::theme;
+
+/// Height of one file row; fixed so the list can be virtualized
+const ROW_HEIGHT: f32 = 28.0;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum FileStatus {
@@ -10,7 +23,7 @@ pub enum FileStatus {
 }
 
 impl FileStatus {
-    fn symbol(&self) -> &'static str {
+    pub fn symbol(&self) -> &'static str {
         match self {
             FileStatus::Added => "+",
             FileStatus::Modified => "~",
@@ -19,6 +32,15 @@ impl FileStatus {
         }
     }
 
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileStatus::Added => tr("Added"),
+            FileStatus::Modified => tr("Modified"),
+            FileStatus::Deleted => tr("Deleted"),
+            FileStatus::Renamed => tr("Renamed"),
+        }
+    }
+
     pub fn color(&self) -> egui::Color32 {
         match self {
             FileStatus::Added => egui::Color32::from_rgb(80, 200, 120), // Green
@@ -32,29 +54,179 @@ impl FileStatus {
 #[derive(Clone, Debug)]
 pub struct ChangedFile {
     pub path: String,
+    /// Where a renamed file used to live
+    pub old_path: Option<String>,
     pub status: FileStatus,
 }
 
+/// Something to do with files picked in the sidebar
+pub enum FileAction {
+    Revert(Vec<String>),
+    Squash(Vec<String>),
+    ExportPatch(Vec<String>),
+    /// Open the restore dialog for one file
+    RestoreFrom(String),
+    /// Show the file on the web page of the repo's git remote
+    OpenOnForge(String),
+    /// Show the file's diff in a window of its own
+    PopOut(String),
+    /// `jj file track` paths jj left out of its snapshots
+    Track(Vec<String>),
+    /// `jj file untrack` paths that are ignored but still recorded
+    Untrack(Vec<String>),
+}
+
+/// Sidebar chip narrowing the list to one kind of change
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum StatusFilter {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Conflicted,
+}
+
+impl StatusFilter {
+    const ALL: [StatusFilter; 5] = [
+        StatusFilter::Added,
+        StatusFilter::Modified,
+        StatusFilter::Deleted,
+        StatusFilter::Renamed,
+        StatusFilter::Conflicted,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            StatusFilter::Added => tr("Added"),
+            StatusFilter::Modified => tr("Modified"),
+            StatusFilter::Deleted => tr("Deleted"),
+            StatusFilter::Renamed => tr("Renamed"),
+            StatusFilter::Conflicted => tr("Conflicted"),
+        }
+    }
+
+    fn matches(self, file: &ChangedFile, conflicts: &HashSet<String>) -> bool {
+        match self {
+            StatusFilter::Added => file.status == FileStatus::Added,
+            StatusFilter::Modified => file.status == FileStatus::Modified,
+            StatusFilter::Deleted => file.status == FileStatus::Deleted,
+            StatusFilter::Renamed => file.status == FileStatus::Renamed,
+            StatusFilter::Conflicted => conflicts.contains(&file.path),
+        }
+    }
+}
+
 /// Cache for changed files list - only fetches on demand
 pub struct ChangedFilesCache {
     files: Vec<ChangedFile>,
     loaded: bool,
+    source: DiffSource,
+    /// Globs for files kept out of the list
+    exclude: Vec<String>,
+    /// How many files the exclude patterns dropped on the last refresh
+    hidden: usize,
+    /// Paths with unresolved conflicts in the revision being shown
+    conflicts: HashSet<String>,
+    /// New files in the working copy that auto-tracking left out, per
+    /// `jj st`; directories end in `/`
+    untracked: Vec<String>,
+    /// Active status chips; none shows every file
+    filters: Vec<StatusFilter>,
+    /// Off hides files whose path has a component starting with `.`
+    show_dotfiles: bool,
+    /// Only files below this directory are listed, picked from the title
+    /// bar's breadcrumbs
+    directory: Option<String>,
+    /// Ctrl/Shift-clicked rows as indices into `files`; two or more turn
+    /// the viewer into a combined diff
+    marked: BTreeSet<usize>,
+    /// Row that Shift-click ranges start from
+    anchor: Option<usize>,
+    /// Indices into `files` in the order the sidebar last listed them,
+    /// after dotfiles, the directory and the status filters
+    visible: Vec<usize>,
+    colors: FileColors,
+    /// Line counts for the row tooltips, fetched on the first hover
+    stats: LineStats,
+}
+
+#[derive(Default)]
+enum LineStats {
+    #[default]
+    NotLoaded,
+    Loading(Receiver<Vec<FileStat>>),
+    /// (added, removed) by path
+    Loaded(HashMap<String, (usize, usize)>),
 }
 
 impl Default for ChangedFilesCache {
     fn default() -> Self {
-        Self::new()
+        Self::new(Vec::new(), true, FileColors::default())
     }
 }
 
 impl ChangedFilesCache {
-    pub fn new() -> Self {
+    pub fn new(exclude: Vec<String>, show_dotfiles: bool, colors: FileColors) -> Self {
         Self {
             files: Vec::new(),
             loaded: false,
+            source: DiffSource::WorkingCopy,
+            exclude,
+            hidden: 0,
+            conflicts: HashSet::new(),
+            untracked: Vec::new(),
+            filters: Vec::new(),
+            show_dotfiles,
+            directory: None,
+            marked: BTreeSet::new(),
+            anchor: None,
+            visible: Vec::new(),
+            colors,
+            stats: LineStats::NotLoaded,
         }
     }
 
+    pub fn exclude_patterns(&self) -> &[String] {
+        &self.exclude
+    }
+
+    pub fn show_dotfiles(&self) -> bool {
+        self.show_dotfiles
+    }
+
+    /// Swap in another repo's sidebar settings; the next refresh applies them
+    pub fn set_preferences(&mut self, exclude: Vec<String>, show_dotfiles: bool) {
+        self.exclude = exclude;
+        self.show_dotfiles = show_dotfiles;
+        self.directory = None;
+    }
+
+    pub fn visible(&self) -> &[usize] {
+        &self.visible
+    }
+
+    pub fn directory(&self) -> Option<&str> {
+        self.directory.as_deref()
+    }
+
+    /// List only files below `directory`, or all of them for None
+    pub fn set_directory(&mut self, directory: Option<String>) {
+        self.directory = directory;
+        self.marked.clear();
+        self.anchor = None;
+    }
+
+    pub fn source(&self) -> &DiffSource {
+        &self.source
+    }
+
+    /// Switch what the list compares and refetch
+    pub fn set_source(&mut self, source: DiffSource) {
+        jj::pause_snapshots(source == DiffSource::Unsnapshotted);
+        self.source = source;
+        self.refresh();
+    }
+
     /// Get files, fetching only on first call
     pub fn get_files(&mut self) -> &[ChangedFile] {
         if !self.loaded {
@@ -65,73 +237,660 @@ impl ChangedFilesCache {
 
     /// Manually refresh the file list
     pub fn refresh(&mut self) {
-        self.files = fetch_changed_files();
+        self.untracked.clear();
+        let mut files = match &self.source {
+            DiffSource::WorkingCopy => {
+                let status = jj_status().unwrap_or_default();
+                self.untracked = parse_untracked(&status);
+                parse_jj_status(&status)
+            }
+            DiffSource::Range { from, to } => fetch_range_files(from, to),
+            DiffSource::Unsnapshotted => fetch_unsnapshotted_files(),
+        };
+        let total = files.len();
+        files.retain(|f| !self.exclude.iter().any(|p| glob_match(p, &f.path)));
+        self.hidden = total - files.len();
+        // Marks follow their paths; files that left the list drop theirs
+        let index_in = |files: &[ChangedFile], i: usize| {
+            let path = &self.files.get(i)?.path;
+            files.iter().position(|f| &f.path == path)
+        };
+        self.marked = self
+            .marked
+            .iter()
+            .filter_map(|&i| index_in(&files, i))
+            .collect();
+        self.anchor = self.anchor.and_then(|i| index_in(&files, i));
+        self.files = files;
+        let revision = self.source.new_revision().unwrap_or("@");
+        self.conflicts = jj::conflicted_files(revision).into_iter().collect();
         self.loaded = true;
+        self.stats = LineStats::NotLoaded;
+    }
+
+    /// Count every file's lines in the background, unless already done
+    fn load_stats(&mut self, ctx: &egui::Context) {
+        if !matches!(self.stats, LineStats::NotLoaded) {
+            return;
+        }
+        let (sender, receiver) = channel();
+        let (ctx, source) = (ctx.clone(), self.source.clone());
+        thread::spawn(move || {
+            let _ = sender.send(jj::diff_stats(&source));
+            ctx.request_repaint();
+        });
+        self.stats = LineStats::Loading(receiver);
+    }
+
+    fn poll_stats(&mut self) {
+        if let LineStats::Loading(receiver) = &self.stats
+            && let Ok(stats) = receiver.try_recv()
+        {
+            let counts = stats
+                .into_iter()
+                .map(|s| (s.path, (s.added, s.removed)))
+                .collect();
+            self.stats = LineStats::Loaded(counts);
+        }
+    }
+
+    /// Files picked with Ctrl/Shift-click; empty unless there are at least two
+    pub fn multi_selection(&self) -> Vec<ChangedFile> {
+        if self.marked.len() < 2 {
+            return Vec::new();
+        }
+        self.marked
+            .iter()
+            .filter_map(|&i| self.files.get(i))
+            .cloned()
+            .collect()
+    }
+
+    /// Plain click selects one row, Ctrl toggles it in the multi-selection
+    /// and Shift marks every visible row from the anchor to it
+    fn click(
+        &mut self,
+        selected: &mut Option<usize>,
+        visible: &[usize],
+        i: usize,
+        modifiers: egui::Modifiers,
+    ) {
+        let position = |row| visible.iter().position(|&v| v == row);
+        let range = self
+            .anchor
+            .or(*selected)
+            .and_then(position)
+            .zip(position(i))
+            .filter(|_| modifiers.shift);
+
+        if let Some((from, to)) = range {
+            self.marked = visible[from.min(to)..=from.max(to)]
+                .iter()
+                .copied()
+                .collect();
+        } else if modifiers.command {
+            if self.marked.is_empty() {
+                self.marked.extend(*selected);
+            }
+            if !self.marked.remove(&i) {
+                self.marked.insert(i);
+            }
+            self.anchor = Some(i);
+        } else {
+            self.marked.clear();
+            self.anchor = Some(i);
+        }
+        *selected = Some(i);
+    }
+
+    /// Hide files matching `pattern` now and in future sessions
+    pub fn exclude(&mut self, pattern: String) {
+        if !self.exclude.contains(&pattern) {
+            self.exclude.push(pattern);
+        }
+        self.refresh();
     }
 }
 
 /// Renders the changed files sidebar and returns the selected file
-/// Returns (selected_file, refresh_requested)
+/// Returns (selected_file, refresh_requested, file_action); `None` selects
+/// the overview. `read_only` hides actions that change the repo, and
+/// `focused` takes the arrow keys and Space.
 pub fn show(
     ui: &mut egui::Ui,
     cache: &mut ChangedFilesCache,
-    selected: &mut usize,
-) -> (Option<ChangedFile>, bool) {
+    selected: &mut Option<usize>,
+    review: &mut Review,
+    read_only: bool,
+    focused: bool,
+) -> (Option<ChangedFile>, bool, Option<FileAction>) {
     let mut refresh_requested = false;
+    let mut file_action = None;
+    let mut back_to_working_copy = false;
+    let mut hide_pattern = None;
 
     ui.vertical(|ui| {
         ui.horizontal(|ui| {
-            ui.label("Changed Files");
+            ui.label(tr("Changed Files"));
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                if ui.button("↻").on_hover_text("Refresh file list").clicked() {
+                if ui
+                    .button("↻")
+                    .on_hover_text(tr("Refresh file list"))
+                    .clicked()
+                {
                     refresh_requested = true;
                 }
+                ui.toggle_value(&mut cache.show_dotfiles, ".*")
+                    .on_hover_text(tr("Show dotfiles and files in dot-directories"));
             });
         });
+
+        let compared = match cache.source() {
+            DiffSource::WorkingCopy => None,
+            DiffSource::Range { from, to } => Some(format!("{} → {}", from, to)),
+            DiffSource::Unsnapshotted => Some(tr("last snapshot → disk").to_string()),
+        };
+        let mut clear_directory = false;
+        if let Some(directory) = &cache.directory {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(format!("{}/", directory)).monospace());
+                if ui
+                    .small_button("✕")
+                    .on_hover_text(tr("List files in every directory"))
+                    .clicked()
+                {
+                    clear_directory = true;
+                }
+            });
+        }
+        if clear_directory {
+            cache.set_directory(None);
+        }
+        if let Some(compared) = compared {
+            ui.horizontal(|ui| {
+                ui.label(
+                    egui::RichText::new(compared)
+                        .monospace()
+                        .color(egui::Color32::from_rgb(220, 180, 80)),
+                );
+                if ui
+                    .small_button("✕")
+                    .on_hover_text(tr("Back to working copy"))
+                    .clicked()
+                {
+                    back_to_working_copy = true;
+                }
+            });
+        }
         ui.separator();
 
-        let files = cache.get_files();
+        if ui
+            .selectable_label(selected.is_none(), tr("Overview"))
+            .clicked()
+        {
+            *selected = None;
+            cache.marked.clear();
+        }
+
+        if cache.hidden > 0 {
+            ui.label(
+                egui::RichText::new(trf("{} hidden by exclude patterns", &[&cache.hidden]))
+                    .small()
+                    .color(theme::dim_text(ui.visuals())),
+            )
+            .on_hover_text(cache.exclude.join("\n"));
+        }
 
-        for (i, file) in files.iter().enumerate() {
-            let response = changed_file_item(ui, file, *selected == i);
+        cache.get_files();
+        status_chips(ui, cache);
 
-            if response.clicked() {
-                *selected = i;
-            }
+        cache.poll_stats();
+        // Indices stay into the full list so selections survive filtering
+        let files = &cache.files;
+        let shown: Vec<usize> = (0..files.len())
+            .filter(|&i| cache.show_dotfiles || !is_dotfile(&files[i].path))
+            .collect();
+        let dotfiles = files.len() - shown.len();
+        let visible: Vec<usize> = shown
+            .into_iter()
+            .filter(|&i| {
+                cache
+                    .directory
+                    .as_deref()
+                    .is_none_or(|dir| in_directory(&files[i].path, dir))
+            })
+            .filter(|&i| {
+                cache.filters.is_empty()
+                    || cache
+                        .filters
+                        .iter()
+                        .any(|f| f.matches(&files[i], &cache.conflicts))
+            })
+            .collect();
+        if dotfiles > 0 {
+            ui.label(
+                egui::RichText::new(trf("{} dotfiles hidden", &[&dotfiles]))
+                    .small()
+                    .color(theme::dim_text(ui.visuals())),
+            );
+        }
+        review_progress(ui, files, &visible, review);
+        cache.visible.clone_from(&visible);
+
+        let marked = &cache.marked;
+        let marked_paths: Vec<String> = marked
+            .iter()
+            .filter_map(|&i| files.get(i))
+            .map(|f| f.path.clone())
+            .collect();
+        let writable = cache.source == DiffSource::WorkingCopy && !read_only;
+        // Sizes are only known for files read from disk
+        let on_disk = cache.source.new_revision().is_none();
+        let stats = match &cache.stats {
+            LineStats::Loaded(counts) => Some(counts),
+            _ => None,
+        };
+        if let Some(action) = untracked_section(ui, &cache.untracked, writable) {
+            file_action = Some(action);
+        }
+        let mut hovered = false;
+        let mut clicked = None;
+        let stepped = if focused && !ui.ctx().wants_keyboard_input() {
+            keyboard_step(ui, files, &visible, *selected, review)
+        } else {
+            None
+        };
+
+        // Only rows in view are laid out, so huge changes stay cheap
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show_rows(ui, ROW_HEIGHT, visible.len(), |ui, rows| {
+                // Rows stepped to with the keyboard may not be laid out yet
+                if let Some(row) = stepped.and_then(|i| visible.iter().position(|&v| v == i)) {
+                    let height = ROW_HEIGHT + ui.spacing().item_spacing.y;
+                    let top = ui.max_rect().top() + (row as f32 - rows.start as f32) * height;
+                    let rect =
+                        egui::Rect::from_x_y_ranges(ui.max_rect().x_range(), top..=top + height);
+                    ui.scroll_to_rect(rect, None);
+                }
+                for i in rows.map(|row| visible[row]) {
+                    let file = &files[i];
+                    let state = review.state(&file.path);
+                    let highlighted = *selected == Some(i) || marked.contains(&i);
+                    let color = cache.colors.color(&file.path);
+                    let response = changed_file_item(ui, file, color, highlighted, state);
+                    hovered |= response.hovered();
+                    let response = response.on_hover_ui(|ui| {
+                        file_tooltip(ui, file, on_disk, stats.map(|s| s.get(&file.path)));
+                    });
+
+                    if response.clicked() {
+                        clicked = Some((i, ui.input(|input| input.modifiers)));
+                    }
+                    if marked.len() > 1 && marked.contains(&i) {
+                        response.context_menu(|ui| {
+                            file_action = bulk_menu(ui, &marked_paths, review, writable);
+                        });
+                        continue;
+                    }
+                    response.context_menu(|ui| {
+                        if let Some(pattern) = extension_pattern(&file.path)
+                            && ui.button(trf("Hide all {} files", &[&pattern])).clicked()
+                        {
+                            hide_pattern = Some(pattern);
+                        }
+                        if ui.button(tr("Hide this file")).clicked() {
+                            hide_pattern = Some(file.path.clone());
+                        }
+                        ui.separator();
+                        for option in [FileReview::Viewed, FileReview::NeedsWork] {
+                            let mut checked = state == Some(option);
+                            if ui.checkbox(&mut checked, option.label()).clicked() {
+                                review.set_state(&file.path, checked.then_some(option));
+                            }
+                        }
+                        if ui.button(tr("Comment on file...")).clicked() {
+                            review.start_comment(&file.path, None);
+                        }
+                        if ui
+                            .button(tr("Open in new window"))
+                            .on_hover_text(tr("Pop the diff out, e.g. onto another monitor"))
+                            .clicked()
+                        {
+                            file_action = Some(FileAction::PopOut(file.path.clone()));
+                        }
+                        if writable && ui.button(tr("Restore from revision...")).clicked() {
+                            file_action = Some(FileAction::RestoreFrom(file.path.clone()));
+                        }
+                        if writable
+                            && file.status != FileStatus::Deleted
+                            && untrack_button(ui, tr("Untrack"))
+                        {
+                            file_action = Some(FileAction::Untrack(vec![file.path.clone()]));
+                        }
+                        if ui
+                            .button(tr("Open on forge"))
+                            .on_hover_text(tr(
+                                "Open the file in the browser at the newest commit the git remote has",
+                            ))
+                            .clicked()
+                        {
+                            file_action = Some(FileAction::OpenOnForge(file.path.clone()));
+                        }
+                    });
+                }
+            });
+
+        if let Some((i, modifiers)) = clicked.or(stepped.map(|i| (i, egui::Modifiers::NONE))) {
+            cache.click(selected, &visible, i, modifiers);
+        }
+        if hovered {
+            cache.load_stats(ui.ctx());
         }
     });
 
     // Handle refresh after UI
-    if refresh_requested {
+    if let Some(pattern) = hide_pattern {
+        cache.exclude(pattern);
+        refresh_requested = true;
+    } else if back_to_working_copy {
+        cache.set_source(DiffSource::WorkingCopy);
+        *selected = None;
+        refresh_requested = true;
+    } else if refresh_requested {
         cache.refresh();
     }
 
     let files = cache.get_files();
 
     // Clamp selection to valid range
-    if !files.is_empty() && *selected >= files.len() {
-        *selected = files.len() - 1;
+    if let Some(i) = *selected
+        && i >= files.len()
+    {
+        *selected = files.len().checked_sub(1);
     }
 
-    (files.get(*selected).cloned(), refresh_requested)
+    (
+        selected.and_then(|i| files.get(i)).cloned(),
+        refresh_requested,
+        file_action,
+    )
 }
 
-fn changed_file_item(ui: &mut egui::Ui, file: &ChangedFile, selected: bool) -> egui::Response {
-    let height = 28.0;
+/// Up and Down step through the listed files and Space toggles the
+/// selected one as viewed. Returns the file stepped to.
+fn keyboard_step(
+    ui: &mut egui::Ui,
+    files: &[ChangedFile],
+    visible: &[usize],
+    selected: Option<usize>,
+    review: &mut Review,
+) -> Option<usize> {
+    if let Some(file) = selected.and_then(|i| files.get(i))
+        && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Space))
+    {
+        let viewed = review.state(&file.path) == Some(FileReview::Viewed);
+        review.set_state(&file.path, (!viewed).then_some(FileReview::Viewed));
+    }
 
+    let down = ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown));
+    let up = ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp));
+    let last = visible.len().checked_sub(1)?;
+    let row = match selected.and_then(|s| visible.iter().position(|&v| v == s)) {
+        Some(row) if down => (row + 1).min(last),
+        Some(row) if up => row.saturating_sub(1),
+        None if down => 0,
+        None if up => last,
+        _ => return None,
+    };
+    Some(visible[row])
+}
+
+/// Whether `path` lies somewhere below `directory`
+pub fn in_directory(path: &str, directory: &str) -> bool {
+    path.strip_prefix(directory)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Whether the file or any directory above it is hidden by the dot convention
+fn is_dotfile(path: &str) -> bool {
+    path.split('/').any(|part| part.starts_with('.'))
+}
+
+/// "3/7 viewed" for the listed files with buttons to mark or clear them
+/// all; hovering the count breaks it down per directory
+fn review_progress(
+    ui: &mut egui::Ui,
+    files: &[ChangedFile],
+    visible: &[usize],
+    review: &mut Review,
+) {
+    if visible.is_empty() {
+        return;
+    }
+    let viewed = |path: &str| review.state(path) == Some(FileReview::Viewed);
+
+    // Every ancestor directory counts the files below it
+    let mut directories: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for &i in visible {
+        let path = files[i].path.as_str();
+        let done = viewed(path) as usize;
+        for (end, _) in path.match_indices('/') {
+            let entry = directories.entry(&path[..end]).or_default();
+            entry.0 += done;
+            entry.1 += 1;
+        }
+    }
+    let total = visible.len();
+    let done = visible.iter().filter(|&&i| viewed(&files[i].path)).count();
+    let reviewed = visible
+        .iter()
+        .any(|&i| review.state(&files[i].path).is_some());
+
+    let mut action = None;
+    ui.horizontal(|ui| {
+        let color = if done == total {
+            FileReview::Viewed.color()
+        } else {
+            theme::dim_text(ui.visuals())
+        };
+        let label = ui.label(
+            egui::RichText::new(trf("{}/{} viewed", &[&done, &total]))
+                .small()
+                .color(color),
+        );
+        if !directories.is_empty() {
+            label.on_hover_ui(|ui| {
+                for (directory, (done, total)) in &directories {
+                    let depth = directory.matches('/').count();
+                    let name = directory.rsplit('/').next().unwrap_or(directory);
+                    let mark = if done == total { "✔" } else { " " };
+                    ui.monospace(format!(
+                        "{}{} {}/  {}/{}",
+                        "  ".repeat(depth),
+                        mark,
+                        name,
+                        done,
+                        total
+                    ));
+                }
+            });
+        }
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if ui
+                .add_enabled(reviewed, egui::Button::new(tr("Clear all")).small())
+                .on_hover_text(tr("Clear the review state of the listed files"))
+                .clicked()
+            {
+                action = Some(None);
+            }
+            if ui
+                .add_enabled(
+                    done < total,
+                    egui::Button::new(tr("Mark all viewed")).small(),
+                )
+                .clicked()
+            {
+                action = Some(Some(FileReview::Viewed));
+            }
+        });
+    });
+
+    if let Some(state) = action {
+        for &i in visible {
+            review.set_state(&files[i].path, state);
+        }
+    }
+}
+
+/// Context menu for a row that is part of a multi-selection
+fn bulk_menu(
+    ui: &mut egui::Ui,
+    paths: &[String],
+    review: &mut Review,
+    writable: bool,
+) -> Option<FileAction> {
+    let count = paths.len();
+    if ui.button(trf("Mark {} files viewed", &[&count])).clicked() {
+        for path in paths {
+            review.set_state(path, Some(FileReview::Viewed));
+        }
+    }
+    if ui
+        .button(trf("Export patch for {} files...", &[&count]))
+        .clicked()
+    {
+        return Some(FileAction::ExportPatch(paths.to_vec()));
+    }
+    if !writable {
+        return None;
+    }
+    ui.separator();
+    if ui
+        .button(trf("Squash {} files into parent", &[&count]))
+        .clicked()
+    {
+        return Some(FileAction::Squash(paths.to_vec()));
+    }
+    if untrack_button(ui, &trf("Untrack {} files", &[&count])) {
+        return Some(FileAction::Untrack(paths.to_vec()));
+    }
+    if ui
+        .button(
+            egui::RichText::new(trf("Revert {} files", &[&count]))
+                .color(FileStatus::Deleted.color()),
+        )
+        .clicked()
+    {
+        return Some(FileAction::Revert(paths.to_vec()));
+    }
+    None
+}
+
+fn untrack_button(ui: &mut egui::Ui, label: &str) -> bool {
+    ui.button(label)
+        .on_hover_text(tr(
+            "Stop recording in jj. Only works for files .gitignore matches; others are tracked again on the next snapshot.",
+        ))
+        .clicked()
+}
+
+/// Collapsed list of the working copy's untracked paths, each with a
+/// context menu to track it
+fn untracked_section(
+    ui: &mut egui::Ui,
+    untracked: &[String],
+    writable: bool,
+) -> Option<FileAction> {
+    if untracked.is_empty() {
+        return None;
+    }
+    let mut action = None;
+    let header = egui::CollapsingHeader::new(trf("{} untracked", &[&untracked.len()]))
+        .id_salt("untracked_paths")
+        .show(ui, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(120.0)
+                .show(ui, |ui| {
+                    for path in untracked {
+                        let row = ui.add(
+                            egui::Label::new(
+                                egui::RichText::new(format!("? {}", path))
+                                    .monospace()
+                                    .color(theme::dim_text(ui.visuals())),
+                            )
+                            .sense(egui::Sense::click()),
+                        );
+                        if writable {
+                            row.context_menu(|ui| {
+                                if ui.button(tr("Track")).clicked() {
+                                    action = Some(FileAction::Track(vec![path.clone()]));
+                                }
+                            });
+                        }
+                    }
+                });
+        });
+    if writable {
+        header.header_response.context_menu(|ui| {
+            if ui
+                .button(trf("Track {} paths", &[&untracked.len()]))
+                .clicked()
+            {
+                action = Some(FileAction::Track(untracked.to_vec()));
+            }
+        });
+    }
+    action
+}
+
+/// Toggle chips with per-status counts; kinds with no files are left out
+fn status_chips(ui: &mut egui::Ui, cache: &mut ChangedFilesCache) {
+    ui.horizontal_wrapped(|ui| {
+        for filter in StatusFilter::ALL {
+            let count = cache
+                .files
+                .iter()
+                .filter(|f| filter.matches(f, &cache.conflicts))
+                .count();
+            let active = cache.filters.contains(&filter);
+            if count == 0 && !active {
+                continue;
+            }
+
+            let chip = ui.selectable_label(active, format!("{} {}", filter.label(), count));
+            if chip.clicked() {
+                if active {
+                    cache.filters.retain(|f| *f != filter);
+                } else {
+                    cache.filters.push(filter);
+                }
+            }
+        }
+    });
+}
+
+/// "*.snap" for "tests/foo.snap"; dotfiles and extensionless files have none
+fn extension_pattern(path: &str) -> Option<String> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let (stem, ext) = name.rsplit_once('.')?;
+    (!stem.is_empty()).then(|| format!("*.{}", ext))
+}
+
+fn changed_file_item(
+    ui: &mut egui::Ui,
+    file: &ChangedFile,
+    color: Option<egui::Color32>,
+    selected: bool,
+    review: Option<FileReview>,
+) -> egui::Response {
     let (rect, response) =
-        ui.allocate_exact_size(egui::vec2(ui.available_width(), height), Sense::click());
+        ui.allocate_exact_size(egui::vec2(ui.available_width(), ROW_HEIGHT), Sense::click());
 
     if ui.is_rect_visible(rect) {
         let visuals = ui.style().visuals.clone();
 
-        let bg_color = if selected {
-            egui::Color32::from_rgb(55, 60, 70)
-        } else if response.hovered() {
-            egui::Color32::from_rgb(45, 48, 54)
-        } else {
-            egui::Color32::TRANSPARENT
-        };
+        let bg_color = theme::row_fill(&visuals, selected, response.hovered());
 
         ui.painter().rect_filled(rect, 6.0, bg_color);
 
@@ -145,33 +904,183 @@ fn changed_file_item(ui: &mut egui::Ui, file: &ChangedFile, selected: bool) -> e
             status_color,
         );
 
-        // Draw file path
+        // Language dot; paths stay aligned for files without one
+        if let Some(color) = color {
+            ui.painter()
+                .circle_filled(rect.left_center() + egui::vec2(34.0, 0.0), 4.0, color);
+        }
+
+        // Long paths lose directories from the middle, keeping the file name
+        let font = egui::TextStyle::Body.resolve(ui.style());
+        let right = if review.is_some() { 30.0 } else { 10.0 };
+        let width = rect.width() - 44.0 - right;
         ui.painter().text(
-            rect.left_center() + egui::vec2(28.0, 0.0),
+            rect.left_center() + egui::vec2(44.0, 0.0),
             egui::Align2::LEFT_CENTER,
-            &file.path,
-            egui::TextStyle::Body.resolve(ui.style()),
+            middle_truncate(ui, &file.path, &font, width),
+            font,
             visuals.text_color(),
         );
+
+        if let Some(review) = review {
+            ui.painter().text(
+                rect.right_center() - egui::vec2(10.0, 0.0),
+                egui::Align2::RIGHT_CENTER,
+                review.symbol(),
+                egui::TextStyle::Body.resolve(ui.style()),
+                review.color(),
+            );
+        }
     }
 
     response
 }
 
-fn fetch_changed_files() -> Vec<ChangedFile> {
-    let output = Command::new("jj").args(["st"]).output();
+/// `path` shortened to `width` by replacing directories in the middle with
+/// `…`, e.g. `src/ui/…/widgets/button.rs`. The file name is always kept.
+fn middle_truncate(ui: &egui::Ui, path: &str, font: &egui::FontId, width: f32) -> String {
+    let chars: Vec<char> = path.chars().collect();
+    let widths: Vec<f32> =
+        ui.fonts_mut(|f| chars.iter().map(|&c| f.glyph_width(font, c)).collect());
+    if widths.iter().sum::<f32>() <= width {
+        return path.to_string();
+    }
 
-    let output = match output {
-        Ok(o) => o,
-        Err(_) => return Vec::new(),
-    };
+    let name = chars.len() - chars.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+    let directories = chars.len() - name;
+    let ellipsis = ui.fonts_mut(|f| f.glyph_width(font, '…'));
+    let mut budget = width - ellipsis - widths[directories..].iter().sum::<f32>();
+    // Directory characters kept from the start and from the end
+    let (mut head, mut tail) = (0, 0);
+    while head + tail < directories {
+        let next = if head <= tail {
+            head
+        } else {
+            directories - tail - 1
+        };
+        budget -= widths[next];
+        if budget < 0.0 {
+            break;
+        }
+        if head <= tail {
+            head += 1;
+        } else {
+            tail += 1;
+        }
+    }
+
+    let mut shortened: String = chars[..head].iter().collect();
+    shortened.push('…');
+    shortened.extend(&chars[directories - tail..]);
+    shortened
+}
+
+/// Full path, status, size on disk and line counts for a hovered row.
+/// `stats` is None while the counts are loading.
+fn file_tooltip(
+    ui: &mut egui::Ui,
+    file: &ChangedFile,
+    on_disk: bool,
+    stats: Option<Option<&(usize, usize)>>,
+) {
+    ui.label(egui::RichText::new(&file.path).monospace());
+    if let Some(old_path) = &file.old_path {
+        ui.label(trf("Renamed from {}", &[old_path]));
+    }
+    ui.horizontal(|ui| {
+        ui.label(egui::RichText::new(file.status.label()).color(file.status.color()));
+        let size = std::fs::metadata(jj::workspace_path(&file.path))
+            .ok()
+            .filter(|_| on_disk && file.status != FileStatus::Deleted);
+        if let Some(metadata) = size {
+            ui.label(format_size(metadata.len() as usize));
+        }
+        match stats {
+            None => {
+                ui.spinner();
+            }
+            Some(Some(&(added, removed))) => {
+                ui.label(
+                    egui::RichText::new(format!("+{}", added)).color(FileStatus::Added.color()),
+                );
+                ui.label(
+                    egui::RichText::new(format!("-{}", removed)).color(FileStatus::Deleted.color()),
+                );
+            }
+            // Binary files, or ones the diff leaves out
+            Some(None) => {}
+        }
+    });
+}
 
+/// Files changed in the working copy, per `jj st`
+pub fn fetch_changed_files() -> Vec<ChangedFile> {
+    try_fetch_changed_files().unwrap_or_default()
+}
+
+/// Like `fetch_changed_files`, but with jj's error when it fails
+pub fn try_fetch_changed_files() -> Result<Vec<ChangedFile>, String> {
+    jj_status().map(|status| parse_jj_status(&status))
+}
+
+fn jj_status() -> Result<String, String> {
+    let output = jj::output_with_retry(jj::command().args(["st"])).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => "jj isn't installed or not on PATH".to_string(),
+        _ => e.to_string(),
+    })?;
     if !output.status.success() {
-        return Vec::new();
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    parse_jj_status(&stdout)
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Files whose content on disk differs from `@` as last snapshotted. Only
+/// files written since the snapshot are read. New files aren't listed,
+/// since telling them from ignored ones takes a snapshot.
+fn fetch_unsnapshotted_files() -> Vec<ChangedFile> {
+    let since = jj::last_snapshot();
+    jj::recorded_files()
+        .into_iter()
+        .filter_map(|path| {
+            let full = jj::workspace_path(&path);
+            let status = match std::fs::metadata(&full).and_then(|meta| meta.modified()) {
+                Err(_) if !full.exists() => FileStatus::Deleted,
+                Ok(modified) if since.is_some_and(|since| modified <= since) => return None,
+                _ => {
+                    let disk = std::fs::read(&full).ok()?;
+                    if String::from_utf8_lossy(&disk) == jj::file_show("@", &path) {
+                        return None;
+                    }
+                    FileStatus::Modified
+                }
+            };
+            Some(ChangedFile {
+                path,
+                old_path: None,
+                status,
+            })
+        })
+        .collect()
+}
+
+fn fetch_range_files(from: &str, to: &str) -> Vec<ChangedFile> {
+    let output = jj::output_with_retry(jj::command().args([
+        "diff",
+        "--summary",
+        "--from",
+        from,
+        "--to",
+        to,
+    ]));
+
+    match output {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout)
+            .lines()
+            .filter_map(parse_status_line)
+            .collect(),
+        _ => Vec::new(),
+    }
 }
 
 fn parse_jj_status(output: &str) -> Vec<ChangedFile> {
@@ -194,27 +1103,122 @@ fn parse_jj_status(output: &str) -> Vec<ChangedFile> {
             continue;
         }
 
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
+        if let Some(file) = parse_status_line(line) {
+            files.push(file);
         }
+    }
 
-        // Parse status and path (format: "A path/to/file")
-        if let Some((status_char, path)) = line.split_once(' ') {
-            let status = match status_char {
-                "A" => FileStatus::Added,
-                "M" => FileStatus::Modified,
-                "D" => FileStatus::Deleted,
-                "R" => FileStatus::Renamed,
-                _ => continue,
-            };
+    files
+}
 
-            files.push(ChangedFile {
-                path: path.to_string(),
-                status,
-            });
+/// Paths listed as "? path" under `jj st`'s "Untracked paths:"
+fn parse_untracked(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip_while(|line| !line.starts_with("Untracked paths:"))
+        .skip(1)
+        .map_while(|line| line.strip_prefix("? "))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse a single summary line (format: "A path/to/file")
+fn parse_status_line(line: &str) -> Option<ChangedFile> {
+    let (status_char, path) = line.trim().split_once(' ')?;
+    let status = match status_char {
+        "A" => FileStatus::Added,
+        "M" => FileStatus::Modified,
+        "D" => FileStatus::Deleted,
+        "R" => FileStatus::Renamed,
+        _ => return None,
+    };
+
+    let (old_path, path) = match status {
+        FileStatus::Renamed => {
+            let (old, new) = parse_rename(path)?;
+            (Some(old), new)
+        }
+        _ => (None, path.to_string()),
+    };
+
+    Some(ChangedFile {
+        path,
+        old_path,
+        status,
+    })
+}
+
+/// "src/{a.rs => b.rs}" or "a.rs => b.rs" -> ("src/a.rs", "src/b.rs")
+fn parse_rename(path: &str) -> Option<(String, String)> {
+    match (path.find('{'), path.rfind('}')) {
+        (Some(open), Some(close)) if open < close => {
+            let (prefix, suffix) = (&path[..open], &path[close + 1..]);
+            let (old, new) = path[open + 1..close].split_once(" => ")?;
+            // An empty side drops its separator, e.g. "{ => sub}/a.rs"
+            let join = |middle: &str| {
+                let joined = format!("{}{}{}", prefix, middle, suffix);
+                joined
+                    .replace("//", "/")
+                    .trim_start_matches('/')
+                    .to_string()
+            };
+            Some((join(old), join(new)))
+        }
+        _ => {
+            let (old, new) = path.split_once(" => ")?;
+            Some((old.to_string(), new.to_string()))
         }
     }
+}
 
-    files
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUS: &str = "\
+Working copy changes:
+M src/lib.rs
+A src/new.rs
+D notes.txt
+R src/{old.rs => renamed.rs}
+Untracked paths:
+? big.bin
+? target/
+Working copy  (@) : abc 123 (no description set)
+Parent commit (@-): def 456 Fixture
+";
+
+    #[test]
+    fn parses_status_sections() {
+        let files: Vec<_> = parse_jj_status(STATUS)
+            .into_iter()
+            .map(|f| (f.path, f.old_path, f.status))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                ("src/lib.rs".to_string(), None, FileStatus::Modified),
+                ("src/new.rs".to_string(), None, FileStatus::Added),
+                ("notes.txt".to_string(), None, FileStatus::Deleted),
+                (
+                    "src/renamed.rs".to_string(),
+                    Some("src/old.rs".to_string()),
+                    FileStatus::Renamed
+                ),
+            ]
+        );
+        assert_eq!(parse_untracked(STATUS), ["big.bin", "target/"]);
+    }
+
+    #[test]
+    fn parses_renames() {
+        let rename = |path| parse_rename(path).unwrap();
+        assert_eq!(rename("a.rs => b.rs"), ("a.rs".into(), "b.rs".into()));
+        assert_eq!(
+            rename("src/{a.rs => b.rs}"),
+            ("src/a.rs".into(), "src/b.rs".into())
+        );
+        assert_eq!(rename("{ => sub}/a.rs"), ("a.rs".into(), "sub/a.rs".into()));
+        assert_eq!(parse_rename("a.rs"), None);
+    }
 }
@@ -0,0 +1,145 @@
+use std::path::Path;
+
+/// Scheme for links into a diff, `le-differ://src/foo.rs:123`
+pub const URI_SCHEME: &str = "le-differ://";
+
+const USAGE: &str = "\
+Usage: le-differ [OPTIONS] [FILE[:LINE]]
+
+Review the changes in the jj repository of the current directory.
+With FILE, open just that file; exits 1 without a window when it has no changes.
+
+Options:
+      --goto FILE[:LINE]   Open FILE at LINE with the sidebar shown
+      --check              Exit without a window: 0 when the working copy has
+                           no changes, 1 when it has, 2 when jj fails
+      --summary            Like --check, also printing the changed files
+      --read-only          Hide every action that changes the repository
+      --profile            Show how long each stage of the diff takes
+      --print-config-path  Print where config.toml is read from and exit
+  -V, --version            Print the version and exit
+  -h, --help               Print this help and exit
+";
+
+/// Flags that print something and exit instead of opening the window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Info {
+    Help,
+    Version,
+    ConfigPath,
+}
+
+impl Info {
+    pub fn print(self) {
+        match self {
+            Info::Help => print!("{}", USAGE),
+            Info::Version => println!("le-differ {}", env!("CARGO_PKG_VERSION")),
+            Info::ConfigPath => match crate::config::config_path() {
+                Some(path) => println!("{}", path.display()),
+                None => eprintln!("No config directory on this system"),
+            },
+        }
+    }
+}
+
+/// Command line flags
+#[derive(Debug, Default)]
+pub struct Args {
+    /// Hide every action that changes the repository
+    pub read_only: bool,
+    /// File, and optionally line, to open on startup
+    pub goto: Option<Location>,
+    /// Show how long each stage of the diff pipeline takes
+    pub profile: bool,
+    /// `goto` came as a bare path: open just that file, sidebar collapsed,
+    /// and exit 1 without a window when it has no changes
+    pub focus: bool,
+    /// Print this and exit before anything else happens
+    pub info: Option<Info>,
+    /// Exit with whether the working copy has changes instead of opening
+    /// the window
+    pub check: bool,
+    /// With `check`, list the changed files on stdout
+    pub summary: bool,
+}
+
+impl Args {
+    /// Errors on unknown flags, so typos in scripts don't go unnoticed
+    pub fn parse() -> Result<Self, String> {
+        let mut args = Self::default();
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--read-only" => args.read_only = true,
+                "--profile" => args.profile = true,
+                "--check" => args.check = true,
+                "--summary" => {
+                    args.check = true;
+                    args.summary = true;
+                }
+                "-h" | "--help" => args.info = Some(Info::Help),
+                "-V" | "--version" => args.info = Some(Info::Version),
+                "--print-config-path" => args.info = Some(Info::ConfigPath),
+                "--goto" => match iter.next() {
+                    Some(value) => args.goto = Some(Location::parse(&value)),
+                    None => return Err("--goto needs a FILE[:LINE] value".to_string()),
+                },
+                other => {
+                    if let Some(value) = other.strip_prefix("--goto=") {
+                        args.goto = Some(Location::parse(value));
+                    } else if other.starts_with(URI_SCHEME) {
+                        // Desktop URL handlers pass the link on its own
+                        args.goto = Some(Location::parse(other));
+                    } else if !other.starts_with('-') {
+                        args.goto = Some(Location::parse(other));
+                        args.focus = true;
+                    } else {
+                        return Err(format!("unknown argument {}", other));
+                    }
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// A file in the repo and a line in its new version
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub path: String,
+    pub line: Option<usize>,
+}
+
+impl Location {
+    /// Accepts `path`, `path:line` and the same with the `le-differ://` scheme
+    pub fn parse(value: &str) -> Self {
+        let value = value.strip_prefix(URI_SCHEME).unwrap_or(value);
+        match value.rsplit_once(':') {
+            Some((path, line)) if !path.is_empty() && line.parse::<usize>().is_ok() => Self {
+                path: path.to_string(),
+                line: line.parse().ok(),
+            },
+            _ => Self {
+                path: value.to_string(),
+                line: None,
+            },
+        }
+    }
+
+    /// Make the path relative to the repo root, resolving it against `cwd`
+    /// first since that's what a terminal user typed it relative to
+    pub fn relative_to(mut self, cwd: &Path, root: &Path) -> Self {
+        let full = cwd.join(&self.path);
+        if let Ok(relative) = full.strip_prefix(root) {
+            self.path = relative.to_string_lossy().replace('\\', "/");
+        }
+        self
+    }
+
+    pub fn uri(&self) -> String {
+        match self.line {
+            Some(line) => format!("{}{}:{}", URI_SCHEME, self.path, line),
+            None => format!("{}{}", URI_SCHEME, self.path),
+        }
+    }
+}
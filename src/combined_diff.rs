@@ -0,0 +1,131 @@
+use eframe::egui::{self, RichText};
+
+use crate::changed_files::{ChangedFile, FileStatus};
+use crate::i18n::{tr, trf};
+use crate::jj::{self, DiffSource};
+use crate::patch::{self, FilePatch, Patch};
+use crate::theme;
+
+/// The diffs of every multi-selected file, stacked in one scroll area
+pub struct CombinedDiff {
+    loaded_for: Option<(DiffSource, Vec<String>)>,
+    patch: Result<Patch, String>,
+}
+
+impl Default for CombinedDiff {
+    fn default() -> Self {
+        Self {
+            loaded_for: None,
+            patch: Ok(Patch { files: Vec::new() }),
+        }
+    }
+}
+
+impl CombinedDiff {
+    /// Drop cached data so the next `show` refetches it
+    pub fn invalidate(&mut self) {
+        self.loaded_for = None;
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, source: &DiffSource, files: &[ChangedFile]) {
+        let paths: Vec<String> = files.iter().map(|f| f.path.clone()).collect();
+        let key = (source.clone(), paths);
+        if self.loaded_for.as_ref() != Some(&key) {
+            self.patch = jj::git_diff_paths(source, &key.1).and_then(|diff| {
+                patch::parse(&diff).map_err(|_| tr("No textual changes").to_string())
+            });
+            self.loaded_for = Some(key);
+        }
+
+        ui.heading(trf("{} files selected", &[&files.len()]));
+        ui.label(
+            RichText::new(tr(
+                "Ctrl-click to add or remove files, Shift-click for a range",
+            ))
+            .small()
+            .color(theme::dim_text(ui.visuals())),
+        );
+        ui.separator();
+
+        let patch = match &self.patch {
+            Ok(patch) => patch,
+            Err(e) => {
+                ui.label(RichText::new(e).color(FileStatus::Deleted.color()));
+                return;
+            }
+        };
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                // Where each file's header starts, to find the one in view
+                let mut headers = Vec::with_capacity(patch.files.len());
+                for file in &patch.files {
+                    let collapsing = egui::CollapsingHeader::new(file_title(file))
+                        .id_salt(&file.path)
+                        .default_open(true)
+                        .show(ui, |ui| {
+                            for hunk in &file.hunks {
+                                ui.label(
+                                    RichText::new(&hunk.header)
+                                        .monospace()
+                                        .color(theme::dim_text(ui.visuals())),
+                                );
+                                patch::show_hunk_lines(ui, hunk);
+                            }
+                        });
+                    headers.push(collapsing.header_response.rect);
+                }
+                sticky_header(ui, patch, &headers);
+            });
+    }
+}
+
+/// Path with its added and removed line counts
+fn file_title(file: &FilePatch) -> RichText {
+    let (added, removed) =
+        file.hunks
+            .iter()
+            .flat_map(|h| &h.lines)
+            .fold((0, 0), |(a, r), (tag, _)| match tag {
+                '+' => (a + 1, r),
+                '-' => (a, r + 1),
+                _ => (a, r),
+            });
+    RichText::new(format!("{}  +{} -{}", file.path, added, removed))
+        .monospace()
+        .strong()
+        .color(file.status().color())
+}
+
+/// Pin the header of the file being read to the top of the scroll area once
+/// its own header has scrolled away; clicking it jumps back to the header
+fn sticky_header(ui: &mut egui::Ui, patch: &Patch, headers: &[egui::Rect]) {
+    let visible = ui.clip_rect();
+    let Some(current) = headers.iter().rposition(|h| h.top() < visible.top()) else {
+        return;
+    };
+
+    let height = headers[current].height();
+    let rect = egui::Rect::from_min_size(visible.left_top(), egui::vec2(visible.width(), height));
+    let response = ui.interact(rect, ui.id().with("sticky_header"), egui::Sense::click());
+    ui.painter().rect_filled(rect, 0.0, ui.visuals().panel_fill);
+    ui.painter().text(
+        rect.left_center() + egui::vec2(4.0, 0.0),
+        egui::Align2::LEFT_CENTER,
+        file_title(&patch.files[current]).text(),
+        egui::TextStyle::Monospace.resolve(ui.style()),
+        patch.files[current].status().color(),
+    );
+    ui.painter().hline(
+        rect.x_range(),
+        rect.bottom(),
+        ui.visuals().widgets.noninteractive.bg_stroke,
+    );
+    if response
+        .on_hover_text(tr("Back to the file header"))
+        .clicked()
+    {
+        ui.scroll_to_rect(headers[current], Some(egui::Align::TOP));
+    }
+}
@@ -0,0 +1,381 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Tab width used when no language override sets one
+const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// Files that are collapsed in the diff view unless loaded explicitly
+const GENERATED_PATTERNS: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "Gemfile.lock",
+    "composer.lock",
+    "flake.lock",
+    "go.sum",
+    "*.pb.rs",
+    "*.pb.go",
+    "*_pb2.py",
+    "*.min.js",
+    "*.min.css",
+    "*.map",
+    "vendor/**",
+    "node_modules/**",
+    "third_party/**",
+];
+
+/// User configuration, read once at startup from `config.toml`
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Per-file overrides, matched against the path in order
+    pub languages: Vec<LanguageOverride>,
+    /// Globs for changed files to leave out of the sidebar in every repo,
+    /// e.g. `*.snap`; ones hidden from the sidebar are kept per repo
+    pub exclude: Vec<String>,
+    /// List dotfiles and files under dot-directories like `.github/`; the
+    /// sidebar toggles it per session
+    pub show_dotfiles: bool,
+    /// Extra globs for generated files, shown collapsed like lockfiles
+    pub generated: Vec<String>,
+    /// Sidebar dot colors by extension or file name, as `#rrggbb`, on top
+    /// of the built-in ones
+    pub file_colors: HashMap<String, String>,
+    /// Mark trailing whitespace on inserted lines, like git's `core.whitespace`
+    pub highlight_trailing_whitespace: bool,
+    /// Files longer than this render without syntax highlighting until
+    /// asked; 0 highlights everything
+    pub highlight_max_lines: usize,
+    /// New files longer than this start folded to their line count; 0
+    /// shows them all
+    pub added_fold_lines: usize,
+    /// Open diffs scrolled to their first change rather than line 1
+    pub scroll_to_first_change: bool,
+    /// Multiplier on top of the OS display scale
+    pub ui_scale: f32,
+    /// Dark or light colors, or whichever the OS uses
+    pub theme: ThemeMode,
+    /// Hide every action that changes the repository, also `--read-only`
+    pub read_only: bool,
+    /// UI language such as `de`; taken from `LANG` when unset or `auto`
+    pub language: Option<String>,
+    /// Start with the diff timings overlay shown, also `--profile`
+    pub profile: bool,
+    /// When the changed files and diffs refetch without the refresh button
+    pub refresh: RefreshMode,
+    /// Context lines around hunks in exported patches, preset in the
+    /// export dialog
+    pub patch_context_lines: usize,
+    /// Export patches with git's extended headers rather than as plain
+    /// unified diffs
+    pub patch_git_headers: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            languages: Vec::new(),
+            exclude: Vec::new(),
+            show_dotfiles: true,
+            generated: Vec::new(),
+            file_colors: HashMap::new(),
+            highlight_trailing_whitespace: true,
+            highlight_max_lines: 20_000,
+            added_fold_lines: 1_000,
+            scroll_to_first_change: true,
+            ui_scale: 1.0,
+            theme: ThemeMode::default(),
+            read_only: false,
+            language: None,
+            profile: false,
+            refresh: RefreshMode::default(),
+            patch_context_lines: 3,
+            patch_git_headers: true,
+        }
+    }
+}
+
+/// `refresh = "focus"` in `config.toml`. Every refresh runs `jj st`, which
+/// can be slow enough in big monorepos to want it manual only.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RefreshMode {
+    /// Only the refresh button and finished operations
+    #[default]
+    Manual,
+    /// Also whenever the window regains focus, e.g. back from the editor
+    Focus,
+    /// Also shortly after any file in the workspace is written
+    Watch,
+}
+
+/// `theme = "light"` in `config.toml`
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeMode {
+    /// Follow the OS color scheme, switching when it does
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl ThemeMode {
+    pub const ALL: [ThemeMode; 3] = [ThemeMode::System, ThemeMode::Dark, ThemeMode::Light];
+
+    /// The `config.toml` value
+    pub fn key(self) -> &'static str {
+        match self {
+            ThemeMode::System => "system",
+            ThemeMode::Dark => "dark",
+            ThemeMode::Light => "light",
+        }
+    }
+}
+
+/// Maps files matching `pattern` to a syntax, tab width or content filter, e.g.
+///
+/// ```toml
+/// [[languages]]
+/// pattern = "*.svelte"
+/// syntax = "HTML"
+///
+/// [[languages]]
+/// pattern = "Makefile"
+/// tab_width = 8
+///
+/// [[languages]]
+/// pattern = "*.md"
+/// highlight_trailing_whitespace = false
+///
+/// [[languages]]
+/// pattern = "*.json"
+/// filter = ["jq", "-S", "."]
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct LanguageOverride {
+    pub pattern: String,
+    pub syntax: Option<String>,
+    pub tab_width: Option<usize>,
+    pub highlight_trailing_whitespace: Option<bool>,
+    /// Command both versions are piped through before diffing, e.g. to
+    /// sort keys or strip timestamps
+    pub filter: Option<Vec<String>>,
+}
+
+/// Language settings resolved for a single file
+#[derive(Clone, Debug, PartialEq)]
+pub struct LanguageSettings {
+    /// Syntax name or extension to use instead of extension lookup
+    pub syntax: Option<String>,
+    pub tab_width: usize,
+    pub highlight_trailing_whitespace: bool,
+    pub filter: Option<Vec<String>>,
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let Some(path) = config_path() else {
+            return Self::default();
+        };
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        toml::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignoring invalid config {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    /// Lockfiles, vendored code, minified assets and the `generated` globs
+    pub fn is_generated(&self, path: &str) -> bool {
+        GENERATED_PATTERNS
+            .iter()
+            .copied()
+            .chain(self.generated.iter().map(String::as_str))
+            .any(|pattern| glob_match(pattern, path))
+    }
+
+    /// The first matching override wins for each setting
+    pub fn language_for(&self, path: &str) -> LanguageSettings {
+        let mut syntax = None;
+        let mut tab_width = None;
+        let mut trailing_whitespace = None;
+        let mut filter = None;
+
+        for lang in self
+            .languages
+            .iter()
+            .filter(|l| glob_match(&l.pattern, path))
+        {
+            syntax = syntax.or_else(|| lang.syntax.clone());
+            tab_width = tab_width.or(lang.tab_width);
+            trailing_whitespace = trailing_whitespace.or(lang.highlight_trailing_whitespace);
+            filter = filter.or_else(|| lang.filter.clone());
+        }
+
+        LanguageSettings {
+            syntax,
+            tab_width: tab_width.unwrap_or(DEFAULT_TAB_WIDTH),
+            highlight_trailing_whitespace: trailing_whitespace
+                .unwrap_or(self.highlight_trailing_whitespace),
+            filter,
+        }
+    }
+}
+
+pub fn save_ui_scale(scale: f32) -> Result<(), String> {
+    edit(|table| {
+        table.insert("ui_scale".to_string(), toml::Value::Float(scale as f64));
+        Ok(())
+    })
+}
+
+pub fn save_theme(theme: ThemeMode) -> Result<(), String> {
+    edit(|table| {
+        table.insert(
+            "theme".to_string(),
+            toml::Value::String(theme.key().to_string()),
+        );
+        Ok(())
+    })
+}
+
+/// Add or replace the `[[languages]]` entry giving files matching `pattern`
+/// a grammar, or drop its `syntax` for None so it's detected again
+pub fn save_syntax(pattern: &str, syntax: Option<&str>) -> Result<(), String> {
+    edit(|table| {
+        let languages = table
+            .entry("languages")
+            .or_insert_with(|| toml::Value::Array(Vec::new()))
+            .as_array_mut()
+            .ok_or("languages isn't a list")?;
+        let existing = languages
+            .iter_mut()
+            .position(|entry| entry.get("pattern").and_then(toml::Value::as_str) == Some(pattern));
+        match (existing, syntax) {
+            (Some(i), Some(syntax)) => {
+                if let Some(entry) = languages[i].as_table_mut() {
+                    entry.insert(
+                        "syntax".to_string(),
+                        toml::Value::String(syntax.to_string()),
+                    );
+                }
+            }
+            (Some(i), None) => {
+                if let Some(entry) = languages[i].as_table_mut() {
+                    entry.remove("syntax");
+                    // Nothing but the pattern left
+                    if entry.len() == 1 {
+                        languages.remove(i);
+                    }
+                }
+            }
+            // First, since the first matching entry wins
+            (None, Some(syntax)) => {
+                let mut entry = toml::Table::new();
+                entry.insert(
+                    "pattern".to_string(),
+                    toml::Value::String(pattern.to_string()),
+                );
+                entry.insert(
+                    "syntax".to_string(),
+                    toml::Value::String(syntax.to_string()),
+                );
+                languages.insert(0, toml::Value::Table(entry));
+            }
+            (None, None) => {}
+        }
+        Ok(())
+    })
+}
+
+/// Rewrite the config file through `change`, creating it if needed.
+/// Other settings are kept, though comments in the file are not.
+fn edit(change: impl FnOnce(&mut toml::Table) -> Result<(), String>) -> Result<(), String> {
+    let path = config_path().ok_or("no config directory")?;
+    let mut table: toml::Table = match std::fs::read_to_string(&path) {
+        Ok(content) => toml::from_str(&content).map_err(|e| e.to_string())?,
+        Err(_) => toml::Table::new(),
+    };
+
+    change(&mut table)?;
+
+    let content = toml::to_string(&table).map_err(|e| e.to_string())?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&path, content).map_err(|e| e.to_string())
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("le-differ").join("config.toml"))
+}
+
+/// Match a path against a glob supporting `*`, `**` and `?`. Patterns
+/// without a `/` only need to match the file name.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let target = if pattern.contains('/') {
+        path
+    } else {
+        path.rsplit('/').next().unwrap_or(path)
+    };
+    match_from(pattern.as_bytes(), target.as_bytes())
+}
+
+fn match_from(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        // `**/` stands for whole directories, so the rest starts a component
+        [b'*', b'*', b'/', rest @ ..] => (0..=text.len())
+            .filter(|&i| i == 0 || text[i - 1] == b'/')
+            .any(|i| match_from(rest, &text[i..])),
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| match_from(rest, &text[i..])),
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != b'/')
+            .any(|i| match_from(rest, &text[i..])),
+        [b'?', rest @ ..] => {
+            matches!(text, [c, tail @ ..] if *c != b'/' && match_from(rest, tail))
+        }
+        [p, rest @ ..] => matches!(text, [c, tail @ ..] if c == p && match_from(rest, tail)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_globs() {
+        let cases = [
+            ("*.lock", "Cargo.lock", true),
+            ("*.lock", "deps/yarn.lock", true),
+            ("*.lock", "lockfile", false),
+            ("src/*.rs", "src/main.rs", true),
+            ("src/*.rs", "src/ui/mod.rs", false),
+            ("src/?.rs", "src/a.rs", true),
+            ("**/foo", "foo", true),
+            ("**/foo", "a/b/foo", true),
+            ("**/foo", "barfoo", false),
+            ("**/foo", "a/barfoo", false),
+            ("vendor/**", "vendor/a/b.js", true),
+            ("vendor/**", "vendored/a.js", false),
+            ("a/**/b.rs", "a/b.rs", true),
+            ("a/**/b.rs", "a/x/y/b.rs", true),
+        ];
+        for (pattern, path, expected) in cases {
+            assert_eq!(
+                glob_match(pattern, path),
+                expected,
+                "{} on {}",
+                pattern,
+                path
+            );
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use eframe::egui::Color32;
+
+use crate::i18n::{tr, trf};
+
+/// Markers are runs of at least this many of one character; jj makes them
+/// longer when the content has marker-like lines of its own
+const MIN_MARKER_LEN: usize = 7;
+
+/// Backgrounds for sides #1, #2, ... cycling past the last
+const SIDE_COLORS: [(u8, u8, u8); 4] = [
+    (90, 140, 220),
+    (180, 110, 220),
+    (80, 190, 190),
+    (220, 160, 70),
+];
+
+/// What a line inside a conflict is
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConflictPart {
+    /// A marker line, drawn as this label instead of the marker
+    Marker(String),
+    /// Content of side `side` (1-based) of `sides`
+    Side {
+        side: usize,
+        sides: usize,
+    },
+    /// A side written as changes from the base, jj's `%%%%%%%` sections
+    Diff {
+        side: usize,
+        sides: usize,
+    },
+    Base,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConflictLine {
+    pub part: ConflictPart,
+    /// 0 for a top-level conflict, more for one inside another's side
+    pub depth: usize,
+}
+
+impl ConflictLine {
+    /// Each side gets its own hue; nested conflicts are a shade stronger
+    pub fn color(&self) -> Color32 {
+        let alpha = 28 + 14 * self.depth.min(4) as u8;
+        let (r, g, b) = match &self.part {
+            ConflictPart::Side { side, .. } | ConflictPart::Diff { side, .. } => {
+                SIDE_COLORS[(side - 1) % SIDE_COLORS.len()]
+            }
+            ConflictPart::Base => (140, 140, 150),
+            ConflictPart::Marker(_) => (220, 180, 80),
+        };
+        Color32::from_rgba_unmultiplied(r, g, b, alpha)
+    }
+}
+
+/// One `<<<<<<<` block still waiting for its `>>>>>>>`
+struct Open {
+    marker_len: usize,
+    start: usize,
+    title: String,
+    /// (marker char, first line) per section; the first is the content
+    /// right after `<<<<<<<`, which git-style conflicts use for side #1
+    sections: Vec<(char, usize)>,
+}
+
+/// Per line of `content`, which conflict part it's in, if any. Unclosed
+/// conflicts are left unmarked.
+pub fn parse(content: &str) -> Vec<Option<ConflictLine>> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut parts: Vec<Option<ConflictLine>> = vec![None; lines.len()];
+    let mut open: Vec<Open> = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let Some((c, len, rest)) = marker(line) else {
+            continue;
+        };
+        match (c, open.last_mut()) {
+            ('<', _) => open.push(Open {
+                marker_len: len,
+                start: i,
+                title: rest.to_string(),
+                sections: vec![('<', i + 1)],
+            }),
+            ('>', Some(top)) if top.marker_len == len => {
+                let Some(conflict) = open.pop() else {
+                    continue;
+                };
+                label(&mut parts, &lines, &conflict, i, open.len());
+            }
+            (_, Some(top)) if top.marker_len == len && c != '>' => top.sections.push((c, i)),
+            _ => {}
+        }
+    }
+
+    parts
+}
+
+/// Mark the lines of a closed conflict. Nested conflicts close first, so
+/// lines that already have a part keep it.
+fn label(
+    parts: &mut [Option<ConflictLine>],
+    lines: &[&str],
+    conflict: &Open,
+    end: usize,
+    depth: usize,
+) {
+    let sections = &conflict.sections;
+    let section_end = |k: usize| sections.get(k + 1).map_or(end, |s| s.1);
+    // Git style puts side #1 right after `<<<<<<<`; jj's own styles open
+    // with a section marker straight away
+    let implicit_side = sections[0].1 < section_end(0)
+        || sections.get(1).is_some_and(|(c, _)| matches!(c, '|' | '='));
+    let sides = sections
+        .iter()
+        .enumerate()
+        .filter(|&(k, (c, _))| match c {
+            '<' => k == 0 && implicit_side,
+            '%' | '+' | '=' => true,
+            _ => false,
+        })
+        .count();
+
+    let mut set = |line: usize, part: ConflictPart| {
+        if parts[line].is_none() {
+            parts[line] = Some(ConflictLine { part, depth });
+        }
+    };
+
+    let title = if conflict.title.is_empty() {
+        tr("Conflict").to_string()
+    } else {
+        conflict.title.clone()
+    };
+    let mut side = 0;
+    let mut current = ConflictPart::Base;
+    for (k, &(c, first)) in sections.iter().enumerate() {
+        let marker_label = match c {
+            '<' if implicit_side => {
+                side += 1;
+                current = ConflictPart::Side { side, sides };
+                format!("{}: {}", title, trf("side #{} of {}", &[&side, &sides]))
+            }
+            '<' => title.clone(),
+            '%' => {
+                side += 1;
+                current = ConflictPart::Diff { side, sides };
+                trf("Side #{} of {}, as changes from the base", &[&side, &sides])
+            }
+            '+' | '=' => {
+                side += 1;
+                current = ConflictPart::Side { side, sides };
+                trf("Side #{} of {}", &[&side, &sides])
+            }
+            '-' | '|' => {
+                current = ConflictPart::Base;
+                tr("Base").to_string()
+            }
+            // jj's `\\\\\\\` continues the previous marker's description
+            _ => marker(lines[first]).map_or_else(String::new, |m| m.2.to_string()),
+        };
+        // The first section has no marker line of its own; `<<<<<<<` heads it
+        let marker_line = if k == 0 { conflict.start } else { first };
+        set(marker_line, ConflictPart::Marker(marker_label));
+        let body = if k == 0 { first } else { first + 1 };
+        for line in body..section_end(k) {
+            set(line, current.clone());
+        }
+    }
+    set(end, ConflictPart::Marker(tr("End of conflict").to_string()));
+}
+
+/// (marker char, run length, text after the run) for a marker line
+fn marker(line: &str) -> Option<(char, usize, &str)> {
+    let c = line.chars().next()?;
+    if !matches!(c, '<' | '>' | '%' | '+' | '-' | '|' | '=' | '\\') {
+        return None;
+    }
+    let len = line.chars().take_while(|&x| x == c).count();
+    let rest = &line[len..];
+    (len >= MIN_MARKER_LEN && (rest.is_empty() || rest.starts_with(' ')))
+        .then(|| (c, len, rest.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Per line: `.` outside a conflict, `M` for a marker, `B` for the
+    /// base, `D` for a diff section and the side number for a side
+    fn parts(content: &str) -> String {
+        parse(content)
+            .iter()
+            .map(|line| match line.as_ref().map(|l| &l.part) {
+                None => '.',
+                Some(ConflictPart::Marker(_)) => 'M',
+                Some(ConflictPart::Base) => 'B',
+                Some(ConflictPart::Diff { .. }) => 'D',
+                Some(ConflictPart::Side { side, .. }) => {
+                    char::from_digit(*side as u32, 10).unwrap_or('?')
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_conflict_markers() {
+        let cases = [
+            (
+                "a\n<<<<<<< left\nx\n=======\ny\n>>>>>>> right\nb\n",
+                ".M1M2M.",
+            ),
+            // diff3 style, with the base between the sides
+            (
+                "<<<<<<< left\nx\n||||||| base\no\n=======\ny\n>>>>>>> right\n",
+                "M1MBM2M",
+            ),
+            // jj style: side #1 as changes from the base, then side #2
+            (
+                "<<<<<<< Conflict 1 of 1\n%%%%%%% Changes from base to side #1\n-o\n+x\n+++++++ Contents of side #2\ny\n>>>>>>> Conflict 1 of 1 ends\n",
+                "MMDDM2M",
+            ),
+            // Too short to be markers
+            ("<<<<<< x\n====== \n>>>>>> y\n", "..."),
+            // Never closed
+            ("<<<<<<< left\nx\n=======\ny\n", "...."),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(parts(content), expected, "{:?}", content);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::jj;
+
+/// A filter still running after this is killed; it's meant to be a quick
+/// formatter, not a job
+const FILTER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a running filter is checked against the deadline
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Pipe `content` through `command`, an argv run from the workspace root
+/// like `["jq", "-S", "."]`, and return what it prints
+pub fn run(command: &[String], content: &str) -> Result<String, String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| "empty filter command".to_string())?;
+    let mut command = Command::new(program);
+    if let Some(root) = jj::workspace_root() {
+        command.current_dir(root);
+    }
+    let mut child = command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{}: {}", program, e))?;
+
+    // Written and read on other threads so a filter that prints before it
+    // has read everything can't deadlock on a full pipe
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = content.to_string();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let stdout = read_to_end(child.stdout.take().expect("stdout is piped"));
+    let stderr = read_to_end(child.stderr.take().expect("stderr is piped"));
+
+    let status = wait_with_deadline(&mut child, FILTER_TIMEOUT)
+        .map_err(|e| format!("{}: {}", program, e))?;
+    let _ = writer.join();
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if status.success() {
+        Ok(String::from_utf8_lossy(&stdout).into_owned())
+    } else {
+        Err(format!(
+            "{} exited with {}: {}",
+            program,
+            status,
+            String::from_utf8_lossy(&stderr).trim()
+        ))
+    }
+}
+
+fn read_to_end(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
+}
+
+/// Wait for `child`, killing it once `timeout` passes
+fn wait_with_deadline(
+    child: &mut Child,
+    timeout: Duration,
+) -> std::io::Result<std::process::ExitStatus> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("timed out after {}s", timeout.as_secs()),
+            ));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
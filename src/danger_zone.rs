@@ -0,0 +1,144 @@
+use eframe::egui::{self, RichText};
+
+use crate::changed_files::FileStatus;
+use crate::i18n::{tr, trf};
+use crate::jj::{self, DiffSource, FileStat};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DangerAction {
+    Abandon,
+    Undo,
+    /// `jj restore` of these working-copy files
+    Revert(Vec<String>),
+}
+
+/// Confirmation for a destructive action, listing what it throws away
+pub struct DangerDialog {
+    action: DangerAction,
+    description: String,
+    stats: Vec<FileStat>,
+}
+
+impl DangerDialog {
+    /// Reads what would be lost up front so the dialog shows a stable picture
+    pub fn new(action: DangerAction) -> Self {
+        let (description, stats) = match &action {
+            DangerAction::Abandon => (
+                jj::description("@"),
+                jj::diff_stats(&DiffSource::WorkingCopy),
+            ),
+            DangerAction::Undo => (jj::last_operation(), Vec::new()),
+            DangerAction::Revert(paths) => {
+                let mut stats = jj::diff_stats(&DiffSource::WorkingCopy);
+                stats.retain(|stat| paths.contains(&stat.path));
+                (String::new(), stats)
+            }
+        };
+        Self {
+            action,
+            description,
+            stats,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match &self.action {
+            DangerAction::Abandon => tr("Abandon current change").to_string(),
+            DangerAction::Undo => tr("Undo last operation").to_string(),
+            DangerAction::Revert(paths) => trf("Revert {} files", &[&paths.len()]),
+        }
+    }
+
+    pub fn args(&self) -> Vec<String> {
+        match &self.action {
+            DangerAction::Abandon => vec!["abandon".to_string()],
+            DangerAction::Undo => vec!["undo".to_string()],
+            DangerAction::Revert(paths) => std::iter::once("restore".to_string())
+                .chain(paths.iter().map(|path| jj::root_file(path)))
+                .collect(),
+        }
+    }
+
+    /// Returns Some(true) when confirmed, Some(false) when cancelled
+    pub fn show(&self, ctx: &egui::Context) -> Option<bool> {
+        let mut choice = None;
+
+        egui::Modal::new(egui::Id::new("danger_zone")).show(ctx, |ui| {
+            ui.set_max_width(480.0);
+            ui.heading(self.label());
+            ui.add_space(4.0);
+
+            match &self.action {
+                DangerAction::Abandon => self.show_abandon_details(ui),
+                DangerAction::Undo => {
+                    ui.label(tr("This reverts the most recent operation:"));
+                    ui.label(RichText::new(&self.description).monospace());
+                }
+                DangerAction::Revert(paths) => {
+                    ui.label(tr("These files lose their changes in the working copy:"));
+                    self.show_files(ui, paths);
+                }
+            }
+
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                let confirm = egui::Button::new(
+                    RichText::new(self.label()).color(FileStatus::Deleted.color()),
+                );
+                if ui.add(confirm).clicked() {
+                    choice = Some(true);
+                }
+                if ui.button(tr("Cancel")).clicked() {
+                    choice = Some(false);
+                }
+            });
+        });
+
+        choice
+    }
+
+    fn show_abandon_details(&self, ui: &mut egui::Ui) {
+        let description = if self.description.is_empty() {
+            tr("(no description)")
+        } else {
+            &self.description
+        };
+        ui.label(tr(
+            "The working-copy change and everything in it will be dropped:",
+        ));
+        ui.label(RichText::new(description).italics());
+
+        if self.stats.is_empty() {
+            ui.label(tr("It has no file changes."));
+            return;
+        }
+        let paths: Vec<String> = self.stats.iter().map(|stat| stat.path.clone()).collect();
+        self.show_files(ui, &paths);
+    }
+
+    /// `paths` with their line counts where jj reported them
+    fn show_files(&self, ui: &mut egui::Ui, paths: &[String]) {
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .show(ui, |ui| {
+                for path in paths {
+                    let stat = self.stats.iter().find(|stat| &stat.path == path);
+                    ui.horizontal(|ui| {
+                        if let Some(stat) = stat {
+                            ui.label(
+                                RichText::new(format!("+{}", stat.added))
+                                    .monospace()
+                                    .color(FileStatus::Added.color()),
+                            );
+                            ui.label(
+                                RichText::new(format!("-{}", stat.removed))
+                                    .monospace()
+                                    .color(FileStatus::Deleted.color()),
+                            );
+                        }
+                        ui.label(RichText::new(path).monospace());
+                    });
+                }
+            });
+    }
+}
@@ -0,0 +1,177 @@
+use eframe::egui::{self, Color32, RichText};
+
+use crate::changed_files::FileStatus;
+use crate::i18n::{tr, trf};
+use crate::jj::{self, DiffSource, FileStat};
+use crate::theme;
+
+pub enum DashboardAction {
+    Describe(String),
+    Squash,
+    New,
+}
+
+/// Overview of the change being reviewed, shown when no file is selected
+#[derive(Default)]
+pub struct Dashboard {
+    loaded_for: Option<DiffSource>,
+    description: String,
+    description_draft: String,
+    stats: Vec<FileStat>,
+    conflicts: Vec<String>,
+    /// Focus the description editor on the next `show`
+    focus_description: bool,
+}
+
+impl Dashboard {
+    /// Drop cached data so the next `show` refetches it
+    pub fn invalidate(&mut self) {
+        self.loaded_for = None;
+    }
+
+    pub fn focus_description(&mut self) {
+        self.focus_description = true;
+    }
+
+    fn refresh(&mut self, source: &DiffSource) {
+        let revision = source.new_revision().unwrap_or("@");
+        self.description = jj::description(revision);
+        self.description_draft = self.description.clone();
+        self.stats = jj::diff_stats(source);
+        self.conflicts = jj::conflicted_files(revision);
+        self.loaded_for = Some(source.clone());
+    }
+
+    /// `busy` disables the quick actions while another operation runs;
+    /// `read_only` hides them
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        source: &DiffSource,
+        busy: bool,
+        read_only: bool,
+    ) -> Option<DashboardAction> {
+        if self.loaded_for.as_ref() != Some(source) {
+            self.refresh(source);
+        }
+
+        let mut action = None;
+        let is_working_copy = *source == DiffSource::WorkingCopy;
+
+        ui.heading(match source {
+            DiffSource::WorkingCopy => tr("Working copy"),
+            DiffSource::Range { .. } => tr("Revision range"),
+            DiffSource::Unsnapshotted => tr("Edits jj hasn't snapshotted"),
+        });
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                if is_working_copy && !read_only {
+                    ui.label(tr("Description"));
+                    let response = ui.add(
+                        egui::TextEdit::multiline(&mut self.description_draft)
+                            .hint_text(tr("(no description)"))
+                            .desired_rows(3)
+                            .desired_width(f32::INFINITY),
+                    );
+                    if std::mem::take(&mut self.focus_description) {
+                        response.request_focus();
+                    }
+
+                    ui.add_enabled_ui(!busy, |ui| {
+                        ui.horizontal(|ui| {
+                            let edited = self.description_draft != self.description;
+                            if ui
+                                .add_enabled(edited, egui::Button::new(tr("Describe")))
+                                .clicked()
+                            {
+                                action =
+                                    Some(DashboardAction::Describe(self.description_draft.clone()));
+                            }
+                            if ui.button(tr("Squash into parent")).clicked() {
+                                action = Some(DashboardAction::Squash);
+                            }
+                            if ui.button(tr("New change")).clicked() {
+                                action = Some(DashboardAction::New);
+                            }
+                        });
+                    });
+                    ui.add_space(12.0);
+                } else if !self.description.is_empty() {
+                    ui.label(&self.description);
+                    ui.add_space(12.0);
+                }
+
+                self.show_stats(ui);
+
+                if !self.conflicts.is_empty() {
+                    ui.add_space(12.0);
+                    ui.label(
+                        RichText::new(trf("{} conflicted files", &[&self.conflicts.len()]))
+                            .color(FileStatus::Deleted.color()),
+                    );
+                    for path in &self.conflicts {
+                        ui.label(RichText::new(path).monospace());
+                    }
+                }
+            });
+
+        action
+    }
+
+    fn show_stats(&self, ui: &mut egui::Ui) {
+        let added: usize = self.stats.iter().map(|s| s.added).sum();
+        let removed: usize = self.stats.iter().map(|s| s.removed).sum();
+
+        ui.horizontal(|ui| {
+            ui.label(trf("{} files changed", &[&self.stats.len()]));
+            ui.label(RichText::new(format!("+{}", added)).color(FileStatus::Added.color()));
+            ui.label(RichText::new(format!("-{}", removed)).color(FileStatus::Deleted.color()));
+        });
+        ui.add_space(4.0);
+
+        // Bars are scaled to the largest file so relative sizes stay readable
+        let max_changes = self
+            .stats
+            .iter()
+            .map(|s| s.added + s.removed)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        for stat in &self.stats {
+            ui.horizontal(|ui| {
+                let (rect, _) =
+                    ui.allocate_exact_size(egui::vec2(120.0, 10.0), egui::Sense::hover());
+                let scale = rect.width() / max_changes as f32;
+                let added_width = stat.added as f32 * scale;
+                let removed_width = stat.removed as f32 * scale;
+
+                ui.painter()
+                    .rect_filled(rect, 2.0, Color32::from_rgb(42, 45, 50));
+                ui.painter().rect_filled(
+                    egui::Rect::from_min_size(rect.min, egui::vec2(added_width, rect.height())),
+                    2.0,
+                    FileStatus::Added.color(),
+                );
+                ui.painter().rect_filled(
+                    egui::Rect::from_min_size(
+                        rect.min + egui::vec2(added_width, 0.0),
+                        egui::vec2(removed_width, rect.height()),
+                    ),
+                    2.0,
+                    FileStatus::Deleted.color(),
+                );
+
+                ui.label(
+                    RichText::new(format!("+{} -{}", stat.added, stat.removed))
+                        .monospace()
+                        .color(theme::dim_text(ui.visuals())),
+                );
+                ui.label(RichText::new(&stat.path).monospace());
+            });
+        }
+    }
+}
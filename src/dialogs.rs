@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+
+use crate::jj;
+
+/// Actions that open a native file dialog. Each remembers the directory it
+/// was last used in, so exporting twice doesn't mean navigating twice.
+#[derive(Clone, Copy, Debug)]
+pub enum DialogKind {
+    OpenRepository,
+    SavePatch,
+    SaveReview,
+}
+
+impl DialogKind {
+    fn key(&self) -> &'static str {
+        match self {
+            DialogKind::OpenRepository => "open_repository",
+            DialogKind::SavePatch => "save_patch",
+            DialogKind::SaveReview => "save_review",
+        }
+    }
+}
+
+pub fn pick_folder(kind: DialogKind, title: &str) -> Option<PathBuf> {
+    let dir = rfd::FileDialog::new()
+        .set_title(title)
+        .set_directory(start_dir(kind))
+        .pick_folder()?;
+    remember(kind, &dir);
+    Some(dir)
+}
+
+/// `filter` is a name and the extensions it matches, e.g. ("Patch", &["patch", "diff"])
+pub fn save_file(
+    kind: DialogKind,
+    title: &str,
+    file_name: &str,
+    filter: (&str, &[&str]),
+) -> Option<PathBuf> {
+    let path = rfd::FileDialog::new()
+        .set_title(title)
+        .set_directory(start_dir(kind))
+        .set_file_name(file_name)
+        .add_filter(filter.0, filter.1)
+        .save_file()?;
+    if let Some(dir) = path.parent() {
+        remember(kind, dir);
+    }
+    Some(path)
+}
+
+/// The last directory for this action, else the repo's parent so exports
+/// land outside the working copy by default
+fn start_dir(kind: DialogKind) -> PathBuf {
+    last_dirs()
+        .get(kind.key())
+        .and_then(|v| v.as_str())
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .or_else(|| jj::workspace_root().and_then(|root| root.parent().map(Path::to_path_buf)))
+        .or_else(dirs::home_dir)
+        .unwrap_or_default()
+}
+
+fn last_dirs() -> toml::Table {
+    state_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Best effort; a failed write only costs the convenience
+fn remember(kind: DialogKind, dir: &Path) {
+    let Some(path) = state_path() else {
+        return;
+    };
+    let mut dirs = last_dirs();
+    dirs.insert(
+        kind.key().to_string(),
+        toml::Value::String(dir.to_string_lossy().into_owned()),
+    );
+    if let Ok(content) = toml::to_string(&dirs) {
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let _ = std::fs::write(path, content);
+    }
+}
+
+/// Kept apart from config.toml, which is the user's to edit
+fn state_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("le-differ").join("dialogs.toml"))
+}
@@ -1,14 +1,37 @@
 use eframe::egui::{self, Color32, RichText, ScrollArea};
+use egui_commonmark::{CommonMarkCache, CommonMarkViewer};
 use similar::{ChangeTag, TextDiff};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::{Range, RangeInclusive};
 use std::path::Path;
-use std::process::Command;
-use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError, channel};
+use std::sync::{Arc, OnceLock};
 use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 use syntect::easy::HighlightLines;
 use syntect::highlighting::ThemeSet;
 use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+use tracing::{debug, debug_span, trace_span, warn};
+use unicode_width::UnicodeWidthChar;
 
 use crate::changed_files::{ChangedFile, FileStatus};
+use crate::cli::Location;
+use crate::config::{self, Config, LanguageSettings};
+use crate::conflicts::{self, ConflictLine, ConflictPart};
+use crate::content_filter;
+use crate::hunk_context;
+use crate::i18n::{tr, trf};
+use crate::ignore_rules;
+use crate::jj::{self, DiffSource};
+use crate::notebook;
+use crate::patch::{self, FilePatch, Hunk, Patch};
+use crate::review::LineRef;
+use crate::semantic_diff;
+use crate::structural_diff;
+use crate::table_diff::{self, RowChange, TableDiff, TableRow};
+use crate::theme;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum DiffViewMode {
@@ -16,67 +39,719 @@ pub enum DiffViewMode {
     Inline,
 }
 
-/// A pre-highlighted text span with color
+/// What the line number gutter shows
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GutterMode {
+    #[default]
+    Absolute,
+    /// Distance from the clicked row, vim `relativenumber` style
+    Relative,
+    OldOnly,
+    NewOnly,
+    Hidden,
+}
+
+impl GutterMode {
+    const ALL: [GutterMode; 5] = [
+        GutterMode::Absolute,
+        GutterMode::Relative,
+        GutterMode::OldOnly,
+        GutterMode::NewOnly,
+        GutterMode::Hidden,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            GutterMode::Absolute => tr("Absolute"),
+            GutterMode::Relative => tr("Relative to cursor"),
+            GutterMode::OldOnly => tr("Old only"),
+            GutterMode::NewOnly => tr("New only"),
+            GutterMode::Hidden => tr("Hidden"),
+        }
+    }
+}
+
+/// Unit of the intra-line highlighting on replaced lines
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DiffGranularity {
+    /// Whole lines only, no intra-line highlighting
+    Line,
+    /// Suits prose, where a changed word is the natural unit
+    #[default]
+    Word,
+    Character,
+}
+
+impl DiffGranularity {
+    const ALL: [DiffGranularity; 3] = [
+        DiffGranularity::Line,
+        DiffGranularity::Word,
+        DiffGranularity::Character,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            DiffGranularity::Line => tr("Lines"),
+            DiffGranularity::Word => tr("Words"),
+            DiffGranularity::Character => tr("Characters"),
+        }
+    }
+}
+
+/// Gutter settings for one frame; `cursor` is the clicked row in the
+/// current view
+#[derive(Clone, Copy)]
+struct Gutter<'a> {
+    mode: GutterMode,
+    cursor: Option<usize>,
+    /// Rows from the cursor to a Shift-clicked row, inclusive and ordered
+    selection: Option<(usize, usize)>,
+    digits: usize,
+    /// Age of each new-side line for the heatmap, see `line_ages`
+    ages: Option<&'a [f32]>,
+}
+
+impl Gutter<'_> {
+    fn shows_old(self) -> bool {
+        matches!(
+            self.mode,
+            GutterMode::Absolute | GutterMode::Relative | GutterMode::OldOnly
+        )
+    }
+
+    fn shows_new(self) -> bool {
+        matches!(
+            self.mode,
+            GutterMode::Absolute | GutterMode::Relative | GutterMode::NewOnly
+        )
+    }
+
+    /// Relative mode keeps the absolute number on the cursor row itself
+    fn number(self, row: usize, absolute: Option<usize>) -> Option<usize> {
+        match (self.mode, self.cursor) {
+            (GutterMode::Relative, Some(cursor)) if cursor != row => {
+                absolute.map(|_| row.abs_diff(cursor))
+            }
+            _ => absolute,
+        }
+    }
+
+    /// Heatmap color behind a new-side line number
+    fn heat(self, new_line_num: Option<usize>) -> Option<Color32> {
+        let age = self.ages?.get(new_line_num?.checked_sub(1)?)?;
+        Some(heat_color(*age))
+    }
+
+    /// The cursor row or part of the selection, drawn highlighted
+    fn is_cursor(self, row: usize) -> bool {
+        self.cursor == Some(row)
+            || self
+                .selection
+                .is_some_and(|(first, last)| (first..=last).contains(&row))
+    }
+}
+
+/// Requests from the viewer that the app has to carry out
+pub enum DiffViewerAction {
+    /// Bring a deleted file back with `jj restore`
+    Restore(String),
+    /// Apply this patch to the working copy to undo a hunk
+    RevertHunk(Patch),
+    /// Start a review comment on a line
+    Comment { path: String, line: LineRef },
+}
+
+/// Options that change how a diff is computed; a change forces a reload
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiffOptions {
+    /// Include cell outputs and metadata when diffing notebooks
+    pub show_notebook_outputs: bool,
+    /// Diff structured config files as key trees instead of text
+    pub semantic_diff: bool,
+    /// Skip syntax highlighting, e.g. when a grammar is wrong or slow
+    pub plain_text: bool,
+    /// Show CSV/TSV files as aligned tables
+    pub table_diff: bool,
+    /// Highlight even past `highlight_max_lines`
+    pub highlight_anyway: bool,
+    /// Diff syntax tokens instead of lines, hiding formatting-only changes
+    pub structural_diff: bool,
+    /// Diff new against old, e.g. to read a change as its revert
+    pub swap_sides: bool,
+    /// Compare lines lowercased, e.g. for SQL dumps; shows the original text
+    pub ignore_case: bool,
+    /// Highlight for a light background; follows the UI theme
+    pub light_theme: bool,
+    /// What changed within replaced lines is highlighted in these units
+    pub granularity: DiffGranularity,
+    /// The old side is the clipboard text and the new side the file on disk
+    pub compare_clipboard: bool,
+    /// Hide hunks that only change comments or blank lines
+    pub ignore_comments: bool,
+    /// Hide hunks that only reorder imports
+    pub ignore_import_order: bool,
+    /// Hunks shown with every line up to the hunks around them, by the
+    /// new-file line where their changes start
+    pub expanded_context: BTreeSet<usize>,
+}
+
+/// What a diff row represents, beyond its change tag
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LineKind {
+    Content,
+    Separator,
+    CellHeader,
+}
+
+/// A pre-highlighted byte range of its line's text
 #[derive(Clone, Debug)]
 struct HighlightedSpan {
-    text: String,
+    range: Range<usize>,
     color: Color32,
 }
 
-/// A diff line with pre-computed highlighting
+/// A diff line with pre-computed highlighting. The text is stored once
+/// and spans index into it, so lines are cheap to share.
 #[derive(Clone, Debug)]
 struct RenderedLine {
     old_line_num: Option<usize>,
     new_line_num: Option<usize>,
+    /// Start of the line in the old file for deletions, else the new file
+    byte_offset: Option<usize>,
+    /// Unexpanded source of changed lines, for the hover details
+    raw: Option<Arc<str>>,
+    /// Start of trailing whitespace in `text` to flag on inserted lines
+    trailing_whitespace: Option<usize>,
+    /// Byte ranges of `text` that differ from the line it replaced or was
+    /// replaced by, sorted
+    emphasis: Vec<Range<usize>>,
+    text: Arc<str>,
     spans: Vec<HighlightedSpan>,
     change_type: ChangeTag,
+    kind: LineKind,
+    /// Set for new-side lines between conflict markers
+    conflict: Option<ConflictLine>,
+    /// Laid out on first draw and reused until the key changes
+    galley: RefCell<Option<(GalleyKey, Arc<egui::Galley>)>>,
+}
+
+/// What a line's galley was laid out for; any change lays it out again
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct GalleyKey {
+    /// Infinite unless the UI wraps text
+    wrap_width: f32,
+    font_size: f32,
+    pixels_per_point: f32,
+    /// Changes when egui rebuilds its font atlas, which moves every glyph
+    atlas_size: [usize; 2],
 }
 
 /// Computed and pre-rendered diff data
 struct DiffData {
     path: String,
-    inline_lines: Vec<RenderedLine>,
-    old_lines: Vec<RenderedLine>,
-    new_lines: Vec<RenderedLine>,
+    options: DiffOptions,
+    /// Shown above the diff, e.g. when a requested mode had to fall back
+    notice: Option<String>,
+    /// Digits needed for the largest line number on either side
+    gutter_digits: usize,
+    /// Every row in inline order; side-by-side rows point into this
+    lines: Vec<RenderedLine>,
+    side_by_side: Vec<SideBySideRow>,
+    /// Empty when the rows don't map back to the file, e.g. semantic diffs
+    hunks: Vec<DiffHunk>,
+    /// Chunks left out by the comment and import order rules
+    ignored_hunks: usize,
+    /// Set for Markdown files so both versions can be previewed rendered
+    markdown: Option<MarkdownSources>,
+    /// Set when a table diff was asked for and both sides parsed
+    table: Option<TableDiff>,
+    file_size: FileSize,
+    /// Content carries an `@generated` marker near the top
+    generated: bool,
+    /// Highlighting was skipped because the file is too long
+    highlight_skipped: bool,
+    /// Name of the grammar the rows were highlighted with, if any
+    syntax: Option<String>,
+    timings: Timings,
+    /// Modification time of the working copy file when it was read
+    disk_modified: Option<SystemTime>,
+}
+
+/// Time spent in each background stage of producing a diff
+#[derive(Clone, Copy, Debug, Default)]
+struct Timings {
+    /// Reading both versions from jj or disk
+    fetch: Duration,
+    /// Line, semantic, structural or table diffing
+    diff: Duration,
+    /// Loading syntect and rendering every row, highlighted or not
+    highlight: Duration,
+}
+
+#[derive(Clone, Copy)]
+struct FileSize {
+    bytes: usize,
+    lines: usize,
+}
+
+struct MarkdownSources {
+    old: String,
+    new: String,
+}
+
+/// A chunk of the diff as a patch hunk, starting at `start` in `lines`
+struct DiffHunk {
+    start: usize,
+    hunk: Hunk,
 }
 
+/// What the user did in a diff view this frame
+#[derive(Default)]
+struct ViewEvents {
+    clicked_row: Option<usize>,
+    /// Row Shift-clicked to extend the selection to
+    extend_to: Option<usize>,
+    /// Row whose context menu asked to copy as Markdown
+    copy_markdown: Option<usize>,
+    revert_hunk: Option<usize>,
+    /// Hunk whose full context was opened or closed
+    toggle_context: Option<usize>,
+    /// Hunk whose checkbox was clicked, with the modifiers held
+    select_hunk: Option<(usize, egui::Modifiers)>,
+    /// Index into `DiffData::lines` of the first visible row
+    top_line: Option<usize>,
+    comment_on: Option<LineRef>,
+}
+
+/// A row to bring into view on the next frame and where it should land
+#[derive(Clone, Copy, Debug)]
+struct ScrollTarget {
+    /// Index into `DiffData::lines`
+    line: usize,
+    align: egui::Align,
+}
+
+impl ScrollTarget {
+    fn top(line: usize) -> Self {
+        Self {
+            line,
+            align: egui::Align::TOP,
+        }
+    }
+}
+
+/// Indices into `DiffData::lines`; `None` is a filler on that side
+#[derive(Clone, Copy, Debug)]
+struct SideBySideRow {
+    old: Option<usize>,
+    new: Option<usize>,
+}
+
+/// Text color when syntax highlighting is off
+fn plain_text_color(light_theme: bool) -> Color32 {
+    if light_theme {
+        Color32::from_rgb(40, 40, 44)
+    } else {
+        Color32::from_rgb(192, 197, 206)
+    }
+}
+
+/// Width of the hunk overview strip beside the diff
+const OVERVIEW_WIDTH: f32 = 12.0;
+
+/// Files on each side of the selection whose diffs are computed ahead
+const PREFETCH_DISTANCE: usize = 2;
+
+/// Unchanged lines left above the first change when a diff opens there
+const FIRST_CHANGE_CONTEXT: usize = 3;
+
+/// Prefetched diffs longer than this are dropped rather than kept around
+const PREFETCH_MAX_LINES: usize = 20_000;
+
+/// How often to check on a diff still being computed
+const LOADING_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often to check whether the working copy file was saved elsewhere
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
 enum DiffState {
     Empty,
-    Loading { path: String },
-    Loaded(DiffData),
+    Loading {
+        path: String,
+        options: DiffOptions,
+    },
+    Loaded(Box<DiffData>),
+    /// The worker went away without sending a result, e.g. it panicked
+    Failed {
+        path: String,
+        options: DiffOptions,
+        error: String,
+    },
+}
+
+/// The find bar, opened with Ctrl+F
+#[derive(Default)]
+struct Find {
+    query: String,
+    /// Indices into `DiffData::lines` of the lines containing the query
+    matches: Vec<usize>,
+    /// (path, query) `matches` was computed for
+    searched: Option<(String, String)>,
+    /// Position in `matches` last jumped to
+    current: usize,
+    focus: bool,
+    /// Whether the query field had keyboard focus this frame
+    focused: bool,
+}
+
+/// `jj file annotate` of one file, for the age heatmap
+struct Annotation {
+    path: String,
+    state: AnnotationState,
+}
+
+enum AnnotationState {
+    Loading(Receiver<Result<Vec<f32>, String>>),
+    Loaded(Result<Vec<f32>, String>),
+}
+
+/// A neighbouring file's diff, computed before it's selected
+struct Prefetched {
+    path: String,
+    options: DiffOptions,
+    state: PrefetchState,
+}
+
+enum PrefetchState {
+    Pending(Receiver<DiffData>),
+    Ready(Box<DiffData>),
+    /// Too long to keep, or the worker failed; loaded normally when selected
+    Skipped,
 }
 
 pub struct DiffViewer {
     state: DiffState,
     receiver: Option<Receiver<DiffData>>,
+    /// Diffs of the files around the selection, so stepping through the
+    /// list doesn't wait on jj
+    prefetched: Vec<Prefetched>,
+    options: DiffOptions,
+    gutter_mode: GutterMode,
+    /// Last clicked row, the origin for relative line numbers
+    cursor_row: Option<usize>,
+    /// Other end of a Shift-click selection starting at the cursor
+    selection_end: Option<usize>,
+    /// Checked hunks, as indices into `DiffData::hunks`, for the batch actions
+    selected_hunks: BTreeSet<usize>,
+    /// Last hunk checked without Shift, where Shift-click ranges start
+    hunk_anchor: Option<usize>,
+    /// First visible line, as an index into `DiffData::lines`
+    top_line: Option<usize>,
+    /// Line to scroll to on the next frame, e.g. after a view mode switch
+    scroll_to_line: Option<ScrollTarget>,
+    /// New-side line number to jump to once the diff has loaded
+    goto_line: Option<usize>,
+    /// New-side line to center once a recomputed diff has loaded, e.g. a
+    /// hunk whose context was opened
+    keep_in_view: Option<usize>,
+    /// Set for a newly opened file, so its first change is scrolled to
+    /// once loaded
+    scroll_to_change: bool,
+    /// Show Markdown files rendered instead of as a text diff
+    markdown_preview: bool,
+    markdown_cache: CommonMarkCache,
+    /// Files shown without syntax highlighting
+    plain_text_paths: HashSet<String>,
+    /// Long files the user asked to highlight regardless
+    highlight_anyway_paths: HashSet<String>,
+    /// Hunks opened up to full context, by file; line numbers only hold
+    /// for one revision, so switching revisions forgets them
+    expanded_context: HashMap<String, BTreeSet<usize>>,
+    /// Grammars picked in the header by `syntax_pattern`, None going back
+    /// to detection; saved to `config.toml` for later sessions
+    syntax_choices: HashMap<String, Option<String>>,
+    /// Working copy file being edited in place of the new side
+    editor: Option<FileEditor>,
+    /// Generated files the user chose to see anyway
+    load_anyway: HashSet<String>,
+    /// New files folded or unfolded against the `added_fold_lines` default
+    fold_toggled: HashSet<String>,
+    /// Color new-side line numbers by how recently each line changed
+    age_heatmap: bool,
+    /// Line ages of the shown file while the heatmap is on
+    annotation: Option<Annotation>,
+    /// (path, text) of a file compared with the clipboard instead of the
+    /// diff source
+    clipboard: Option<(String, String)>,
+    /// Why reading the clipboard failed, shown by its button
+    clipboard_error: Option<String>,
+    find: Option<Find>,
+    /// Draw the stage timings overlay, toggled with F12
+    show_timings: bool,
+    /// How long the last frame took to lay out the diff rows
+    layout_time: Duration,
+    /// When the shown file was last compared against the disk
+    last_disk_check: Option<Instant>,
+    /// The file was written since its diff was computed
+    changed_on_disk: bool,
+    source: DiffSource,
+    config: Arc<Config>,
 }
 
 impl Default for DiffViewer {
     fn default() -> Self {
-        Self::new()
+        Self::new(Arc::default())
     }
 }
 
 impl DiffViewer {
-    pub fn new() -> Self {
+    pub fn new(config: Arc<Config>) -> Self {
         Self {
             state: DiffState::Empty,
             receiver: None,
+            prefetched: Vec::new(),
+            options: DiffOptions::default(),
+            gutter_mode: GutterMode::default(),
+            cursor_row: None,
+            selection_end: None,
+            selected_hunks: BTreeSet::new(),
+            hunk_anchor: None,
+            top_line: None,
+            scroll_to_line: None,
+            goto_line: None,
+            keep_in_view: None,
+            scroll_to_change: true,
+            markdown_preview: false,
+            markdown_cache: CommonMarkCache::default(),
+            plain_text_paths: HashSet::new(),
+            highlight_anyway_paths: HashSet::new(),
+            expanded_context: HashMap::new(),
+            syntax_choices: HashMap::new(),
+            editor: None,
+            load_anyway: HashSet::new(),
+            fold_toggled: HashSet::new(),
+            age_heatmap: false,
+            annotation: None,
+            clipboard: None,
+            clipboard_error: None,
+            find: None,
+            show_timings: config.profile,
+            layout_time: Duration::ZERO,
+            last_disk_check: None,
+            changed_on_disk: false,
+            source: DiffSource::WorkingCopy,
+            config,
+        }
+    }
+
+    pub fn set_source(&mut self, source: DiffSource) {
+        if self.source != source {
+            self.source = source;
+            self.expanded_context.clear();
+            self.invalidate_cache();
         }
     }
 
+    fn set_plain_text(&mut self, path: &str, plain: bool) {
+        if plain {
+            self.plain_text_paths.insert(path.to_string());
+        } else {
+            self.plain_text_paths.remove(path);
+        }
+    }
+
+    /// Drop the shown diff and everything prefetched, e.g. after the repo
+    /// changed
     pub fn invalidate_cache(&mut self) {
-        self.state = DiffState::Empty;
+        // Reloading the same file keeps the reader where they were
+        let scroll_to_change = self.scroll_to_change;
+        self.reset();
+        self.scroll_to_change = scroll_to_change;
+        self.prefetched.clear();
+    }
+
+    /// Clear the view for another file. A loaded diff is kept as prefetched,
+    /// so going back to it is instant.
+    pub fn reset(&mut self) {
+        if let DiffState::Loaded(data) = std::mem::replace(&mut self.state, DiffState::Empty)
+            && data.lines.len() <= PREFETCH_MAX_LINES
+        {
+            self.prefetched.push(Prefetched {
+                path: data.path.clone(),
+                options: data.options.clone(),
+                state: PrefetchState::Ready(data),
+            });
+        }
+        self.changed_on_disk = false;
         self.receiver = None;
+        self.annotation = None;
+        self.cursor_row = None;
+        self.selection_end = None;
+        self.selected_hunks.clear();
+        self.hunk_anchor = None;
+        self.top_line = None;
+        self.scroll_to_line = None;
+        self.keep_in_view = None;
+        self.scroll_to_change = true;
+    }
+
+    /// Start comparing `path` with the clipboard's current text, or stop
+    fn compare_clipboard(&mut self, path: &str, on: bool) {
+        self.clipboard_error = None;
+        if !on {
+            self.clipboard = None;
+            return;
+        }
+        match arboard::Clipboard::new().and_then(|mut c| c.get_text()) {
+            Ok(text) => self.clipboard = Some((path.to_string(), text)),
+            Err(e) => self.clipboard_error = Some(e.to_string()),
+        }
+    }
+
+    /// Rows from the cursor to the Shift-clicked row, in order
+    fn selection(&self) -> Option<(usize, usize)> {
+        let (cursor, end) = (self.cursor_row?, self.selection_end?);
+        Some((cursor.min(end), cursor.max(end)))
+    }
+
+    /// Open the find bar, or focus it again if it's open
+    pub fn open_find(&mut self) {
+        self.find.get_or_insert_with(Find::default).focus = true;
+    }
+
+    pub fn find_open(&self) -> bool {
+        self.find.is_some()
+    }
+
+    pub fn find_focused(&self) -> bool {
+        self.find.as_ref().is_some_and(|find| find.focused)
+    }
+
+    /// "Detected: Rust ▾" menu choosing the grammar for every file with the
+    /// shown file's extension
+    fn syntax_picker(&mut self, ui: &mut egui::Ui, file: &ChangedFile) {
+        let detected = match &self.state {
+            DiffState::Loaded(data) if data.path == file.path => data.syntax.as_deref(),
+            _ => None,
+        };
+        let chosen = self.language_for(&file.path).syntax;
+        // Until the diff has loaded, the chosen name stands in
+        let label = match &chosen {
+            Some(name) => format!("{} ▾", detected.unwrap_or(name)),
+            None => format!(
+                "{} ▾",
+                trf("Detected: {}", &[&detected.unwrap_or(PLAIN_TEXT)])
+            ),
+        };
+
+        let mut picked = None;
+        ui.menu_button(label, |ui| {
+            ui.set_max_height(320.0);
+            if ui
+                .selectable_label(chosen.is_none(), tr("Detect automatically"))
+                .clicked()
+            {
+                picked = Some(None);
+                ui.close();
+            }
+            ui.separator();
+            ScrollArea::vertical().show(ui, |ui| {
+                for name in syntax_names() {
+                    let selected = chosen.as_deref() == Some(name.as_str());
+                    if ui.selectable_label(selected, name).clicked() {
+                        picked = Some(Some(name.clone()));
+                        ui.close();
+                    }
+                }
+            });
+        })
+        .response
+        .on_hover_text(trf(
+            "Grammar for highlighting, remembered for {}",
+            &[&syntax_pattern(&file.path)],
+        ));
+        if let Some(syntax) = picked {
+            self.choose_syntax(&file.path, syntax);
+        }
+    }
+
+    /// Scroll to the hunk containing `line` of the new file when it's shown.
+    /// Survives `invalidate_cache` so it can be set alongside a new selection.
+    pub fn goto_line(&mut self, line: usize) {
+        self.goto_line = Some(line);
+    }
+
+    /// Switch modes keeping the same line at the top. Row indices differ
+    /// between the two layouts, so the cursor is dropped.
+    fn switch_view_mode(&mut self, view_mode: &mut DiffViewMode, new_mode: DiffViewMode) {
+        if *view_mode != new_mode {
+            *view_mode = new_mode;
+            self.scroll_to_line = self.top_line.map(ScrollTarget::top);
+            self.cursor_row = None;
+            self.selection_end = None;
+        }
+    }
+
+    /// The viewer's options with this file's plain text choice applied
+    fn options_for(&self, path: &str) -> DiffOptions {
+        DiffOptions {
+            plain_text: self.plain_text_paths.contains(path),
+            highlight_anyway: self.highlight_anyway_paths.contains(path),
+            compare_clipboard: self.clipboard.as_ref().is_some_and(|(p, _)| p == path),
+            expanded_context: self.expanded_context.get(path).cloned().unwrap_or_default(),
+            ..self.options.clone()
+        }
+    }
+
+    /// The configured language settings with a grammar picked this session
+    fn language_for(&self, path: &str) -> LanguageSettings {
+        let mut language = self.config.language_for(path);
+        if let Some(choice) = self.syntax_choices.get(&syntax_pattern(path)) {
+            language.syntax = choice.clone();
+        }
+        language
+    }
+
+    /// Highlight every file named like `path` with `syntax`, or detect it
+    /// again for None
+    fn choose_syntax(&mut self, path: &str, syntax: Option<String>) {
+        let pattern = syntax_pattern(path);
+        if let Err(e) = config::save_syntax(&pattern, syntax.as_deref()) {
+            warn!("Couldn't save the syntax for {}: {}", pattern, e);
+        }
+        self.syntax_choices.insert(pattern, syntax);
+        self.invalidate_cache();
     }
 
     fn ensure_loading(&mut self, file: &ChangedFile, ctx: &egui::Context) {
+        let wanted = self.options_for(&file.path);
         match &self.state {
-            DiffState::Loaded(data) if data.path == file.path => return,
-            DiffState::Loading { path } if path == &file.path => {
-                if let Some(ref receiver) = self.receiver {
-                    if let Ok(data) = receiver.try_recv() {
-                        self.state = DiffState::Loaded(data);
+            DiffState::Loaded(data) if data.path == file.path && data.options == wanted => {
+                return;
+            }
+            DiffState::Failed { path, options, .. } if path == &file.path && *options == wanted => {
+                return;
+            }
+            DiffState::Loading { path, options } if path == &file.path && *options == wanted => {
+                let result = match &self.receiver {
+                    Some(receiver) => receiver.try_recv(),
+                    None => Err(TryRecvError::Disconnected),
+                };
+                match result {
+                    Ok(data) => {
+                        self.state = DiffState::Loaded(Box::new(data));
+                        self.receiver = None;
+                    }
+                    Err(TryRecvError::Empty) => {
+                        // Keep polling in case the worker's repaint is missed
+                        ctx.request_repaint_after(LOADING_POLL_INTERVAL);
+                    }
+                    Err(TryRecvError::Disconnected) => {
+                        self.state = DiffState::Failed {
+                            path: path.clone(),
+                            options: options.clone(),
+                            error: "The diff worker stopped without a result".to_string(),
+                        };
                         self.receiver = None;
                     }
                 }
@@ -85,331 +760,3080 @@ impl DiffViewer {
             _ => {}
         }
 
+        let prefetched = self
+            .prefetched
+            .iter()
+            .position(|p| p.path == file.path && p.options == wanted)
+            .map(|i| self.prefetched.swap_remove(i).state);
+        let receiver = match prefetched {
+            Some(PrefetchState::Ready(data)) => {
+                self.state = DiffState::Loaded(data);
+                self.receiver = None;
+                return;
+            }
+            Some(PrefetchState::Pending(receiver)) => receiver,
+            Some(PrefetchState::Skipped) | None => self.spawn_worker(file, wanted.clone(), ctx),
+        };
+
+        self.state = DiffState::Loading {
+            path: file.path.clone(),
+            options: wanted,
+        };
+        self.receiver = Some(receiver);
+        ctx.request_repaint_after(LOADING_POLL_INTERVAL);
+    }
+
+    fn spawn_worker(
+        &self,
+        file: &ChangedFile,
+        options: DiffOptions,
+        ctx: &egui::Context,
+    ) -> Receiver<DiffData> {
         let (sender, receiver): (Sender<DiffData>, Receiver<DiffData>) = channel();
-        let path = file.path.clone();
-        let status = file.status.clone();
-        let ctx = ctx.clone();
+        let file = file.clone();
+        let source = self.source.clone();
+        let language = self.language_for(&file.path);
+        let max_lines = self.config.highlight_max_lines;
+        let clipboard = self
+            .clipboard
+            .as_ref()
+            .filter(|_| options.compare_clipboard)
+            .map(|(_, text)| text.clone());
+        let worker_ctx = ctx.clone();
 
         thread::spawn(move || {
-            let data = compute_diff_data(&path, &status);
+            let data = compute_diff_data(&file, &source, &language, options, max_lines, clipboard);
             let _ = sender.send(data);
-            ctx.request_repaint();
+            worker_ctx.request_repaint();
         });
+        receiver
+    }
 
-        self.state = DiffState::Loading {
-            path: file.path.clone(),
+    /// Once the selected diff has loaded, compute the diffs of the files
+    /// around `selected` in the background, going by `visible`, the
+    /// sidebar's order of indices into `files`. Only those are kept.
+    pub fn prefetch_around(
+        &mut self,
+        files: &[ChangedFile],
+        visible: &[usize],
+        selected: usize,
+        ctx: &egui::Context,
+    ) {
+        if !matches!(self.state, DiffState::Loaded(_)) {
+            return;
+        }
+        let row = visible.iter().position(|&i| i == selected);
+        let neighbours: Vec<(&ChangedFile, DiffOptions)> = (1..=PREFETCH_DISTANCE)
+            .flat_map(|d| [row.map(|r| r + d), row.and_then(|r| r.checked_sub(d))])
+            .filter_map(|r| files.get(*visible.get(r?)?))
+            .filter(|f| !self.config.is_generated(&f.path))
+            .map(|f| (f, self.options_for(&f.path)))
+            .collect();
+
+        self.prefetched.retain(|p| {
+            neighbours
+                .iter()
+                .any(|(f, options)| f.path == p.path && *options == p.options)
+        });
+        for prefetched in &mut self.prefetched {
+            let PrefetchState::Pending(receiver) = &prefetched.state else {
+                continue;
+            };
+            prefetched.state = match receiver.try_recv() {
+                Ok(data) if data.lines.len() <= PREFETCH_MAX_LINES => {
+                    PrefetchState::Ready(Box::new(data))
+                }
+                Ok(_) | Err(TryRecvError::Disconnected) => PrefetchState::Skipped,
+                Err(TryRecvError::Empty) => continue,
+            };
+        }
+
+        for (file, options) in neighbours {
+            if self.prefetched.iter().all(|p| p.path != file.path) {
+                let receiver = self.spawn_worker(file, options.clone(), ctx);
+                self.prefetched.push(Prefetched {
+                    path: file.path.clone(),
+                    options,
+                    state: PrefetchState::Pending(receiver),
+                });
+            }
+        }
+    }
+
+    /// Annotate the file in the background the first time the heatmap needs it
+    fn ensure_annotation(&mut self, file: &ChangedFile, ctx: &egui::Context) {
+        let annotation = match &mut self.annotation {
+            Some(annotation) if annotation.path == file.path => annotation,
+            _ => {
+                let (sender, receiver) = channel();
+                let revision = self.source.new_revision().unwrap_or("@").to_string();
+                let (path, ctx) = (file.path.clone(), ctx.clone());
+                thread::spawn(move || {
+                    let ages = jj::annotate_times(&revision, &path).map(|times| line_ages(&times));
+                    let _ = sender.send(ages);
+                    ctx.request_repaint();
+                });
+                self.annotation.insert(Annotation {
+                    path: file.path.clone(),
+                    state: AnnotationState::Loading(receiver),
+                })
+            }
         };
-        self.receiver = Some(receiver);
+        if let AnnotationState::Loading(receiver) = &annotation.state {
+            annotation.state = match receiver.try_recv() {
+                Ok(result) => AnnotationState::Loaded(result),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    AnnotationState::Loaded(Err("annotate worker exited".to_string()))
+                }
+            };
+        }
+    }
+
+    /// Flag the loaded working copy file if it was saved since it was read,
+    /// at most once per `DISK_CHECK_INTERVAL`
+    fn check_disk(&mut self, ctx: &egui::Context) {
+        let DiffState::Loaded(data) = &self.state else {
+            return;
+        };
+        let Some(read_at) = data.disk_modified else {
+            return;
+        };
+        ctx.request_repaint_after(DISK_CHECK_INTERVAL);
+        if self.changed_on_disk
+            || self
+                .last_disk_check
+                .is_some_and(|t| t.elapsed() < DISK_CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_disk_check = Some(Instant::now());
+        self.changed_on_disk = modified_time(&data.path).is_some_and(|t| t != read_at);
+    }
+
+    /// Whether the worker has finished with the file last shown
+    #[cfg(test)]
+    pub fn is_loaded(&self) -> bool {
+        matches!(self.state, DiffState::Loaded(_) | DiffState::Failed { .. })
     }
 
+    /// `focused` takes the arrow keys and Ctrl+A for the diff
     pub fn show(
         &mut self,
         ui: &mut egui::Ui,
         file: Option<&ChangedFile>,
         view_mode: &mut DiffViewMode,
-    ) {
+        focused: bool,
+    ) -> Option<DiffViewerAction> {
+        let mut action = None;
+        let mut reload = false;
+        if ui.input(|i| i.key_pressed(egui::Key::F12)) {
+            self.show_timings = !self.show_timings;
+        }
+        // Switching theme rehighlights, since the options are part of the cache key
+        self.options.light_theme = !ui.visuals().dark_mode;
+
         ui.horizontal(|ui| {
             if let Some(f) = file {
-                ui.heading(&f.path);
+                path_heading(ui, &f.path);
                 ui.label(
                     RichText::new(format!("({})", status_label(&f.status))).color(f.status.color()),
                 );
+                if let Some(old_path) = &f.old_path {
+                    ui.label(RichText::new(tr("from")).color(theme::dim_text(ui.visuals())));
+                    path_label(ui, RichText::new(old_path).monospace());
+                }
+                if let DiffState::Loaded(data) = &self.state
+                    && data.path == f.path
+                {
+                    ui.label(
+                        RichText::new(format!(
+                            "{}, {} lines",
+                            format_size(data.file_size.bytes),
+                            data.file_size.lines
+                        ))
+                        .color(theme::dim_text(ui.visuals())),
+                    );
+                }
+                if self.changed_on_disk {
+                    ui.label(
+                        RichText::new(tr("File changed on disk"))
+                            .color(Color32::from_rgb(220, 180, 80)),
+                    );
+                    if ui.small_button(tr("Reload")).clicked() {
+                        reload = true;
+                    }
+                }
+                if let Some(error) = &self.clipboard_error {
+                    ui.label(RichText::new("⚠").color(FileStatus::Deleted.color()))
+                        .on_hover_text(trf("Couldn't read the clipboard: {}", &[error]));
+                }
             } else {
-                ui.heading("No file selected");
+                ui.heading(tr("No file selected"));
             }
 
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if let Some(f) = file {
+                    let comparing = self.clipboard.as_ref().is_some_and(|(p, _)| *p == f.path);
+                    if ui
+                        .selectable_label(comparing, "📋")
+                        .on_hover_text(tr(
+                            "Compare with clipboard: the clipboard text as the old side, the file on disk as the new",
+                        ))
+                        .clicked()
+                    {
+                        self.compare_clipboard(&f.path, !comparing);
+                    }
+                }
+                ui.toggle_value(&mut self.options.swap_sides, "⇄")
+                    .on_hover_text(tr("Swap sides, showing the diff from new to old"));
                 if ui
-                    .selectable_label(*view_mode == DiffViewMode::Inline, "Inline")
+                    .selectable_label(*view_mode == DiffViewMode::Inline, tr("Inline"))
                     .clicked()
                 {
-                    *view_mode = DiffViewMode::Inline;
+                    self.switch_view_mode(view_mode, DiffViewMode::Inline);
                 }
                 if ui
-                    .selectable_label(*view_mode == DiffViewMode::SideBySide, "Side-by-side")
+                    .selectable_label(*view_mode == DiffViewMode::SideBySide, tr("Side-by-side"))
                     .clicked()
                 {
-                    *view_mode = DiffViewMode::SideBySide;
+                    self.switch_view_mode(view_mode, DiffViewMode::SideBySide);
+                }
+                ui.menu_button(tr("Gutter"), |ui| {
+                    for mode in GutterMode::ALL {
+                        ui.radio_value(&mut self.gutter_mode, mode, mode.label());
+                    }
+                });
+                ui.menu_button(tr("Highlight"), |ui| {
+                    for granularity in DiffGranularity::ALL {
+                        ui.radio_value(
+                            &mut self.options.granularity,
+                            granularity,
+                            granularity.label(),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text(tr(
+                    "Highlight changes within replaced lines by word or character",
+                ));
+                if file.is_some_and(|f| notebook::is_notebook(&f.path)) {
+                    ui.checkbox(&mut self.options.show_notebook_outputs, tr("Show outputs"))
+                        .on_hover_text(tr("Include cell outputs and metadata in the diff"));
+                }
+                if file.is_some_and(|f| semantic_diff::supports(&f.path)) {
+                    ui.checkbox(&mut self.options.semantic_diff, tr("Semantic"))
+                        .on_hover_text(tr("Compare parsed keys, ignoring ordering and formatting"));
+                }
+                if file.is_some_and(|f| structural_diff::supports(&f.path)) {
+                    ui.checkbox(&mut self.options.structural_diff, tr("Structural"))
+                        .on_hover_text(tr(
+                            "Match syntax tokens instead of lines, so reformatting isn't a change",
+                        ));
+                }
+                if let Some(f) = file {
+                    let mut plain = self.plain_text_paths.contains(&f.path);
+                    if ui
+                        .checkbox(&mut plain, tr("Plain text"))
+                        .on_hover_text(tr("Show this file without syntax highlighting"))
+                        .changed()
+                    {
+                        self.set_plain_text(&f.path, plain);
+                    }
+                    if !plain {
+                        self.syntax_picker(ui, f);
+                    }
+                }
+                ui.toggle_value(&mut self.age_heatmap, tr("Age"))
+                    .on_hover_text(tr(
+                        "Color line numbers by how recently each line changed, newest brightest",
+                    ));
+                if self.age_heatmap
+                    && let Some(Annotation {
+                        state: AnnotationState::Loaded(Err(error)),
+                        ..
+                    }) = &self.annotation
+                {
+                    ui.label(RichText::new("⚠").color(FileStatus::Deleted.color()))
+                        .on_hover_text(trf("jj file annotate failed: {}", &[error]));
+                }
+                ui.checkbox(&mut self.options.ignore_case, tr("Ignore case"))
+                    .on_hover_text(tr("Compare lines ignoring upper and lower case"));
+                if file.is_some_and(|f| ignore_rules::supports_comments(&f.path)) {
+                    ui.checkbox(&mut self.options.ignore_comments, tr("Ignore comments"))
+                        .on_hover_text(tr("Hide hunks that only change comments or blank lines"));
+                }
+                if file.is_some_and(|f| ignore_rules::supports_imports(&f.path)) {
+                    ui.checkbox(
+                        &mut self.options.ignore_import_order,
+                        tr("Ignore import order"),
+                    )
+                    .on_hover_text(tr("Hide hunks that only reorder imports"));
+                }
+                if file.is_some_and(|f| table_diff::supports(&f.path)) {
+                    ui.checkbox(&mut self.options.table_diff, tr("Table"))
+                        .on_hover_text(tr("Align columns and highlight changed cells"));
+                }
+                if file.is_some_and(|f| is_markdown(&f.path)) {
+                    ui.checkbox(&mut self.markdown_preview, tr("Preview"))
+                        .on_hover_text(tr("Render the old and new versions side by side"));
+                }
+                if let Some(f) = file
+                    && self.source == DiffSource::WorkingCopy
+                    && !self.config.read_only
+                    && f.status != FileStatus::Deleted
+                {
+                    let mut editing = self.editor.as_ref().is_some_and(|e| e.path == f.path);
+                    if ui
+                        .checkbox(&mut editing, tr("Edit"))
+                        .on_hover_text(tr(
+                            "Edit the working copy file here; saving updates the diff",
+                        ))
+                        .changed()
+                    {
+                        self.editor = editing.then(|| FileEditor::open(&f.path));
+                    }
                 }
             });
         });
 
-        ui.separator();
+        ui.separator();
+
+        // The header's Reload, handled before the views below return early
+        if std::mem::take(&mut reload) {
+            self.invalidate_cache();
+        }
+
+        let Some(file) = file else {
+            ui.label(tr("Select a file from the sidebar to view its diff."));
+            return None;
+        };
+
+        // Skip even loading files that are known to be generated by name
+        if self.config.is_generated(&file.path) && !self.load_anyway.contains(&file.path) {
+            if generated_placeholder(ui, tr("Generated or vendored file")) {
+                self.load_anyway.insert(file.path.clone());
+            }
+            return action;
+        }
+
+        self.ensure_loading(file, ui.ctx());
+        self.check_disk(ui.ctx());
+        if self.age_heatmap && file.status != FileStatus::Deleted {
+            self.ensure_annotation(file, ui.ctx());
+        }
+
+        match &self.state {
+            DiffState::Empty | DiffState::Loading { .. } => {
+                ui.vertical_centered(|ui| {
+                    ui.add_space(50.0);
+                    ui.spinner();
+                    ui.label(tr("Loading diff..."));
+                });
+            }
+            DiffState::Failed { error, options, .. } => {
+                let (mut retry, mut plain) = (false, false);
+                ui.vertical_centered(|ui| {
+                    ui.add_space(50.0);
+                    ui.label(RichText::new(error).color(FileStatus::Deleted.color()));
+                    retry = ui.button(tr("Retry")).clicked();
+                    // A grammar panicking on odd content is the usual culprit
+                    if !options.plain_text {
+                        plain = ui.button(tr("Retry as plain text")).clicked();
+                    }
+                });
+                if plain {
+                    self.set_plain_text(&file.path, true);
+                } else if retry {
+                    self.invalidate_cache();
+                }
+            }
+            DiffState::Loaded(data) => {
+                if data.generated && !self.load_anyway.contains(&data.path) {
+                    if generated_placeholder(ui, tr("File is marked @generated")) {
+                        self.load_anyway.insert(data.path.clone());
+                    }
+                    return action;
+                }
+                let scroll_to_change = std::mem::take(&mut self.scroll_to_change);
+                if let Some(line) = self.goto_line.take() {
+                    self.scroll_to_line = goto_index(data, line).map(ScrollTarget::top);
+                } else if let Some(line) = self.keep_in_view.take() {
+                    self.scroll_to_line = line_index(data, line).map(|line| ScrollTarget {
+                        line,
+                        align: egui::Align::Center,
+                    });
+                } else if scroll_to_change && self.config.scroll_to_first_change {
+                    self.scroll_to_line = first_change_index(data).map(ScrollTarget::top);
+                }
+                if let Some(notice) = &data.notice {
+                    ui.label(RichText::new(notice).color(Color32::from_rgb(220, 180, 80)));
+                }
+                if data.ignored_hunks > 0 {
+                    ui.label(
+                        RichText::new(trf(
+                            "{} hunks hidden by the ignore options",
+                            &[&data.ignored_hunks],
+                        ))
+                        .color(theme::dim_text(ui.visuals())),
+                    );
+                }
+                if data.highlight_skipped {
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            RichText::new(trf(
+                                "Syntax highlighting skipped for {} lines",
+                                &[&data.file_size.lines],
+                            ))
+                            .color(Color32::from_rgb(220, 180, 80)),
+                        );
+                        if ui
+                            .small_button(tr("Highlight anyway"))
+                            .on_hover_text(tr(
+                                "Raise `highlight_max_lines` in config.toml to keep it",
+                            ))
+                            .clicked()
+                        {
+                            self.highlight_anyway_paths.insert(data.path.clone());
+                        }
+                    });
+                }
+
+                if let Some(table) = &data.table {
+                    show_table(ui, table);
+                    return action;
+                }
+
+                if self.markdown_preview
+                    && let Some(markdown) = &data.markdown
+                {
+                    show_markdown_preview(ui, markdown, &mut self.markdown_cache);
+                    return action;
+                }
+
+                if let Some(find) = &mut self.find {
+                    let (line, open) = find_bar(ui, find, data);
+                    if let Some(line) = line {
+                        self.scroll_to_line = Some(ScrollTarget {
+                            line,
+                            align: egui::Align::Center,
+                        });
+                    }
+                    if !open {
+                        self.find = None;
+                    }
+                }
+                let matches = self.find.as_ref().map_or(&[][..], |f| f.matches.as_slice());
+
+                let layout_start = Instant::now();
+                let _layout_span = trace_span!("layout", path = %data.path).entered();
+                let rows = match view_mode {
+                    DiffViewMode::SideBySide => data.side_by_side.len(),
+                    DiffViewMode::Inline => data.lines.len(),
+                };
+                let mut gutter = Gutter {
+                    mode: self.gutter_mode,
+                    cursor: self.cursor_row,
+                    selection: self.selection(),
+                    digits: data.gutter_digits,
+                    // Swapped, the new side is the old file, which wasn't annotated
+                    ages: match &self.annotation {
+                        Some(Annotation {
+                            path,
+                            state: AnnotationState::Loaded(Ok(ages)),
+                        }) if self.age_heatmap
+                            && *path == data.path
+                            && !data.options.swap_sides =>
+                        {
+                            Some(ages.as_slice())
+                        }
+                        _ => None,
+                    },
+                };
+                if gutter.mode == GutterMode::Relative {
+                    gutter.digits = gutter.digits.max(rows.to_string().len());
+                }
+
+                let scroll_to = self.scroll_to_line.take();
+                // Restoring and reverting edit the working copy
+                let writable = self.source == DiffSource::WorkingCopy && !self.config.read_only;
+                // Swapped or against the clipboard, both sides have content
+                let one_sided = !data.options.swap_sides && !data.options.compare_clipboard;
+
+                // Recomputing with other options can leave fewer hunks
+                self.selected_hunks.retain(|&i| i < data.hunks.len());
+                let keyboard = focused && !ui.ctx().wants_keyboard_input();
+                if keyboard
+                    && !data.hunks.is_empty()
+                    && ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::A))
+                {
+                    self.selected_hunks = (0..data.hunks.len()).collect();
+                }
+                if keyboard && let Some(start) = step_hunk(ui, data, self.top_line) {
+                    self.scroll_to_line = Some(ScrollTarget::top(start));
+                }
+                let picked: Vec<Hunk> = self
+                    .selected_hunks
+                    .iter()
+                    .map(|&i| data.hunks[i].hunk.clone())
+                    .collect();
+                match hunk_selection_bar(ui, picked.len(), writable) {
+                    Some(HunkSelectionAction::Revert) => {
+                        action = Some(DiffViewerAction::RevertHunk(Patch {
+                            files: vec![FilePatch {
+                                path: data.path.clone(),
+                                is_new: false,
+                                is_delete: false,
+                                hunks: picked.iter().map(Hunk::reversed).collect(),
+                            }],
+                        }));
+                    }
+                    Some(HunkSelectionAction::Copy) => {
+                        ui.ctx().copy_text(patch::format(&FilePatch {
+                            path: data.path.clone(),
+                            is_new: false,
+                            is_delete: false,
+                            hunks: picked,
+                        }));
+                    }
+                    Some(HunkSelectionAction::Clear) => self.selected_hunks.clear(),
+                    None => {}
+                }
+                let selected_hunks = &self.selected_hunks;
+
+                let editor = self.editor.as_mut().filter(|e| e.path == data.path);
+                // Deletions have nothing to compare against, so show the old file whole
+                let events = if let Some(editor) = editor {
+                    // The editor takes the new side, so the diff goes inline beside it
+                    ui.columns(2, |columns| {
+                        let events = show_inline(
+                            &mut columns[0],
+                            data,
+                            gutter,
+                            writable,
+                            selected_hunks,
+                            scroll_to,
+                        );
+                        reload = editor.show(&mut columns[1]);
+                        events
+                    })
+                } else if file.status == FileStatus::Deleted && one_sided {
+                    if deleted_banner(ui, writable) {
+                        action = Some(DiffViewerAction::Restore(file.path.clone()));
+                    }
+                    show_whole_file(ui, data, gutter, scroll_to, ChangeTag::Delete)
+                } else if file.status == FileStatus::Added && one_sided {
+                    let fold_lines = self.config.added_fold_lines;
+                    let long = fold_lines > 0 && data.file_size.lines > fold_lines;
+                    let folded = long != self.fold_toggled.contains(&data.path);
+                    if added_banner(ui, data.file_size.lines, folded) {
+                        toggle(&mut self.fold_toggled, &data.path);
+                    }
+                    if folded {
+                        ViewEvents::default()
+                    } else {
+                        show_whole_file(ui, data, gutter, scroll_to, ChangeTag::Insert)
+                    }
+                } else {
+                    if !data.hunks.is_empty() || !matches.is_empty() {
+                        let overview = egui::SidePanel::right(ui.id().with("hunk_overview"))
+                            .exact_width(OVERVIEW_WIDTH)
+                            .resizable(false)
+                            .show_separator_line(false)
+                            .frame(egui::Frame::NONE)
+                            .show_inside(ui, |ui| hunk_overview(ui, data, *view_mode, matches))
+                            .inner;
+                        if let Some(line) = overview {
+                            self.scroll_to_line = Some(ScrollTarget {
+                                line,
+                                align: egui::Align::Center,
+                            });
+                            ui.ctx().request_repaint();
+                        }
+                    }
+                    match view_mode {
+                        DiffViewMode::SideBySide => {
+                            show_side_by_side(ui, data, gutter, writable, selected_hunks, scroll_to)
+                        }
+                        DiffViewMode::Inline => {
+                            show_inline(ui, data, gutter, writable, selected_hunks, scroll_to)
+                        }
+                    }
+                };
+                self.layout_time = layout_start.elapsed();
+                if self.show_timings {
+                    timings_overlay(ui, &data.timings, self.layout_time);
+                }
+                self.top_line = events.top_line;
+                if events.clicked_row.is_some() {
+                    self.cursor_row = events.clicked_row;
+                    self.selection_end = None;
+                }
+                if let Some(row) = events.extend_to {
+                    self.cursor_row.get_or_insert(row);
+                    self.selection_end = Some(row);
+                }
+                if let Some(row) = events.copy_markdown {
+                    // The whole selection when clicked inside it, else the row
+                    let (first, last) = self
+                        .selection()
+                        .filter(|&(first, last)| (first..=last).contains(&row))
+                        .unwrap_or((row, row));
+                    if let Some(lines) = row_lines(data, *view_mode, first, last) {
+                        ui.ctx().copy_text(markdown_block(&data.lines[lines]));
+                    }
+                }
+                if let Some(line) = events.comment_on {
+                    action = Some(DiffViewerAction::Comment {
+                        path: data.path.clone(),
+                        line,
+                    });
+                }
+                if let Some((i, modifiers)) = events.select_hunk {
+                    select_hunk(
+                        &mut self.selected_hunks,
+                        &mut self.hunk_anchor,
+                        i,
+                        modifiers,
+                    );
+                }
+                if let Some(i) = events.toggle_context {
+                    let hunk = &data.hunks[i].hunk;
+                    let starts = change_starts(hunk);
+                    let expanded = self.expanded_context.entry(data.path.clone()).or_default();
+                    if starts.iter().any(|start| expanded.contains(start)) {
+                        for start in &starts {
+                            expanded.remove(start);
+                        }
+                    } else {
+                        expanded.extend(starts.first());
+                    }
+                    self.keep_in_view = starts.first().copied();
+                }
+                if let Some(i) = events.revert_hunk {
+                    action = Some(DiffViewerAction::RevertHunk(Patch {
+                        files: vec![FilePatch {
+                            path: data.path.clone(),
+                            is_new: false,
+                            is_delete: false,
+                            hunks: vec![data.hunks[i].hunk.reversed()],
+                        }],
+                    }));
+                }
+            }
+        }
+
+        if reload {
+            self.invalidate_cache();
+        }
+
+        action
+    }
+}
+
+/// Per-stage times in the viewer's top right corner, for finding out why a
+/// file is slow
+fn timings_overlay(ui: &mut egui::Ui, timings: &Timings, layout: Duration) {
+    let stages = [
+        (tr("fetch"), timings.fetch),
+        (tr("diff"), timings.diff),
+        (tr("highlight"), timings.highlight),
+        (tr("layout"), layout),
+    ];
+    egui::Area::new(ui.id().with("timings_overlay"))
+        .order(egui::Order::Foreground)
+        .pivot(egui::Align2::RIGHT_TOP)
+        .fixed_pos(ui.max_rect().right_top() + egui::vec2(-8.0, 8.0))
+        .interactable(false)
+        .show(ui.ctx(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                egui::Grid::new("timings").num_columns(2).show(ui, |ui| {
+                    for (stage, time) in stages {
+                        ui.label(RichText::new(stage).monospace());
+                        ui.label(
+                            RichText::new(format!("{:.1} ms", time.as_secs_f64() * 1000.0))
+                                .monospace(),
+                        );
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+}
+
+/// Collapsed stand-in for a generated file; returns true on "Load anyway"
+fn generated_placeholder(ui: &mut egui::Ui, reason: &str) -> bool {
+    let mut load = false;
+    ui.vertical_centered(|ui| {
+        ui.add_space(50.0);
+        ui.label(RichText::new(reason).color(theme::dim_text(ui.visuals())));
+        load = ui
+            .button(tr("Load anyway"))
+            .on_hover_text(tr(
+                "Add patterns under `generated` in config.toml to collapse more files",
+            ))
+            .clicked();
+    });
+    load
+}
+
+/// In-app editor for the new side of a working copy diff
+struct FileEditor {
+    path: String,
+    text: String,
+    /// Whether `text` differs from what was last loaded or saved
+    dirty: bool,
+    error: Option<String>,
+}
+
+impl FileEditor {
+    fn open(path: &str) -> Self {
+        let (text, error) = match std::fs::read_to_string(jj::workspace_path(path)) {
+            Ok(text) => (text, None),
+            Err(e) => (
+                String::new(),
+                Some(format!("Couldn't read {}: {}", path, e)),
+            ),
+        };
+        Self {
+            path: path.to_string(),
+            text,
+            dirty: false,
+            error,
+        }
+    }
+
+    /// Returns true after a save, when the diff needs recomputing
+    fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut save = false;
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(self.dirty, egui::Button::new(tr("Save")))
+                .on_hover_text(tr("Write to disk (Ctrl+S)"))
+                .clicked()
+            {
+                save = true;
+            }
+            if ui
+                .add_enabled(self.dirty, egui::Button::new(tr("Discard")))
+                .on_hover_text(tr("Reload the file from disk"))
+                .clicked()
+            {
+                *self = Self::open(&self.path);
+            }
+            if self.dirty {
+                ui.label(RichText::new(tr("unsaved")).color(Color32::from_rgb(220, 180, 80)));
+            }
+            if let Some(error) = &self.error {
+                ui.label(RichText::new(error).color(FileStatus::Deleted.color()));
+            }
+        });
+
+        let response = ScrollArea::both()
+            .id_salt("file_editor")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.text)
+                        .code_editor()
+                        .desired_width(f32::INFINITY),
+                )
+            })
+            .inner;
+        self.dirty |= response.changed();
+        if response.has_focus()
+            && ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::S))
+        {
+            save = true;
+        }
+
+        if !save || !self.dirty {
+            return false;
+        }
+        match std::fs::write(jj::workspace_path(&self.path), &self.text) {
+            Ok(()) => {
+                self.dirty = false;
+                self.error = None;
+                true
+            }
+            Err(e) => {
+                self.error = Some(format!("Couldn't save: {}", e));
+                false
+            }
+        }
+    }
+}
+
+/// CSV/TSV rows in an aligned grid; changed cells show `old → new`
+fn show_table(ui: &mut egui::Ui, table: &TableDiff) {
+    let changed_bg = Color32::from_rgba_unmultiplied(220, 180, 80, 40);
+    let dim = theme::dim_text(ui.visuals());
+
+    ScrollArea::both()
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            egui::Grid::new("table_diff")
+                .striped(true)
+                .min_col_width(24.0)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label("");
+                    for col in 0..table.columns {
+                        let new = table.header.get(col).map_or("", String::as_str);
+                        let old = table
+                            .old_header
+                            .as_ref()
+                            .map(|h| h.get(col).map_or("", String::as_str));
+                        match old {
+                            Some(old) if old != new => {
+                                ui.label(
+                                    RichText::new(format!("{} → {}", old, new))
+                                        .strong()
+                                        .background_color(changed_bg),
+                                );
+                            }
+                            _ => {
+                                ui.label(RichText::new(new).strong());
+                            }
+                        }
+                    }
+                    ui.end_row();
+
+                    for row in &table.rows {
+                        match row {
+                            TableRow::Skipped(count) => {
+                                ui.label("");
+                                ui.label(
+                                    RichText::new(trf("{} unchanged rows", &[&count]))
+                                        .italics()
+                                        .color(dim),
+                                );
+                            }
+                            TableRow::Record {
+                                change,
+                                line,
+                                cells,
+                                old_cells,
+                            } => {
+                                let (marker, color) = match change {
+                                    RowChange::Added => ("+", FileStatus::Added.color()),
+                                    RowChange::Removed => ("-", FileStatus::Deleted.color()),
+                                    RowChange::Changed => ("~", FileStatus::Renamed.color()),
+                                    RowChange::Equal => (" ", dim),
+                                };
+                                ui.label(RichText::new(marker).monospace().color(color));
+                                ui.label(RichText::new(line.to_string()).monospace().color(dim));
+                                for col in 0..table.columns {
+                                    let new = cells.get(col).map_or("", String::as_str);
+                                    let old = old_cells
+                                        .as_ref()
+                                        .map(|c| c.get(col).map_or("", String::as_str));
+                                    let text = match (change, old) {
+                                        (_, Some(old)) if old != new => {
+                                            RichText::new(format!("{} → {}", old, new))
+                                                .background_color(changed_bg)
+                                        }
+                                        (RowChange::Added | RowChange::Removed, _) => {
+                                            RichText::new(new).color(color)
+                                        }
+                                        _ => RichText::new(new),
+                                    };
+                                    ui.label(text.monospace());
+                                }
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+/// Rendered old and new Markdown in two columns, scrolled together
+fn show_markdown_preview(
+    ui: &mut egui::Ui,
+    markdown: &MarkdownSources,
+    cache: &mut CommonMarkCache,
+) {
+    ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            ui.columns(2, |columns| {
+                for (ui, (title, text)) in columns
+                    .iter_mut()
+                    .zip([(tr("Before"), &markdown.old), (tr("After"), &markdown.new)])
+                {
+                    ui.push_id(title, |ui| {
+                        ui.label(RichText::new(title).strong());
+                        ui.separator();
+                        if text.is_empty() {
+                            ui.label(RichText::new("(empty)").italics());
+                        } else {
+                            CommonMarkViewer::new().show(ui, cache, text);
+                        }
+                    });
+                }
+            });
+        });
+}
+
+/// Code generators conventionally put `@generated` in a header comment
+fn is_marked_generated(content: &str) -> bool {
+    content
+        .lines()
+        .take(5)
+        .any(|line| line.contains("@generated"))
+}
+
+fn is_markdown(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("md") || e.eq_ignore_ascii_case("markdown"))
+}
+
+fn path_heading(ui: &mut egui::Ui, path: &str) {
+    path_label(ui, RichText::new(path).heading());
+}
+
+/// Selectable path text that copies itself when clicked
+fn path_label(ui: &mut egui::Ui, text: RichText) {
+    let copy = text.text().to_string();
+    let response = ui
+        .add(
+            egui::Label::new(text)
+                .selectable(true)
+                .sense(egui::Sense::click()),
+        )
+        .on_hover_text(tr("Click to copy"));
+    if response.clicked() {
+        ui.ctx().copy_text(copy);
+    }
+}
+
+pub fn format_size(bytes: usize) -> String {
+    const KB: f64 = 1024.0;
+    let bytes_f = bytes as f64;
+    if bytes_f < KB {
+        format!("{} B", bytes)
+    } else if bytes_f < KB * KB {
+        format!("{:.1} KB", bytes_f / KB)
+    } else {
+        format!("{:.1} MB", bytes_f / (KB * KB))
+    }
+}
+
+/// Header for a new file with its line count; returns true when the fold
+/// button is clicked
+fn added_banner(ui: &mut egui::Ui, lines: usize, folded: bool) -> bool {
+    let mut toggled = false;
+
+    egui::Frame::new()
+        .fill(Color32::from_rgba_unmultiplied(80, 200, 120, 30))
+        .stroke(egui::Stroke::new(1.0, FileStatus::Added.color()))
+        .corner_radius(4.0)
+        .inner_margin(egui::Margin::symmetric(12, 8))
+        .show(ui, |ui| {
+            ui.set_width(ui.available_width());
+            ui.horizontal(|ui| {
+                ui.label(
+                    RichText::new(tr("New file"))
+                        .strong()
+                        .color(FileStatus::Added.color()),
+                );
+                ui.label(
+                    RichText::new(trf("+{} lines", &[&lines]))
+                        .monospace()
+                        .color(FileStatus::Added.color()),
+                );
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let label = if folded {
+                        tr("Show content")
+                    } else {
+                        tr("Fold")
+                    };
+                    toggled = ui
+                        .button(label)
+                        .on_hover_text(tr(
+                            "New files longer than `added_fold_lines` in config.toml start folded",
+                        ))
+                        .clicked();
+                });
+            });
+        });
+    ui.add_space(8.0);
+
+    toggled
+}
+
+fn toggle(set: &mut HashSet<String>, path: &str) {
+    if !set.remove(path) {
+        set.insert(path.to_string());
+    }
+}
+
+/// Returns true when "Restore file" is clicked
+fn deleted_banner(ui: &mut egui::Ui, restorable: bool) -> bool {
+    let mut restore = false;
+
+    egui::Frame::new()
+        .fill(Color32::from_rgba_unmultiplied(220, 80, 80, 40))
+        .stroke(egui::Stroke::new(1.0, FileStatus::Deleted.color()))
+        .corner_radius(4.0)
+        .inner_margin(egui::Margin::symmetric(12, 8))
+        .show(ui, |ui| {
+            ui.set_width(ui.available_width());
+            ui.horizontal(|ui| {
+                ui.label(
+                    RichText::new(tr("This file was deleted"))
+                        .strong()
+                        .color(FileStatus::Deleted.color()),
+                );
+                if restorable {
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        restore = ui
+                            .button(tr("Restore file"))
+                            .on_hover_text("jj restore <path>")
+                            .clicked();
+                    });
+                }
+            });
+        });
+    ui.add_space(8.0);
+
+    restore
+}
+
+/// A deleted or added file as one block of its lines, with only the
+/// side it has in the gutter
+fn show_whole_file(
+    ui: &mut egui::Ui,
+    data: &DiffData,
+    gutter: Gutter,
+    scroll_to: Option<ScrollTarget>,
+    tag: ChangeTag,
+) -> ViewEvents {
+    let mut events = ViewEvents::default();
+
+    ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            for (row, line) in data.lines.iter().enumerate() {
+                let response = ui
+                    .horizontal(|ui| {
+                        let rect = ui.available_rect_before_wrap();
+                        let bg_color = match &line.conflict {
+                            Some(conflict) => conflict.color(),
+                            None => change_tag_to_bg_color(tag),
+                        };
+                        ui.painter().rect_filled(rect, 0.0, bg_color);
+
+                        if gutter.mode != GutterMode::Hidden {
+                            let (num, heat) = match tag {
+                                ChangeTag::Delete => (line.old_line_num, None),
+                                _ => (line.new_line_num, gutter.heat(line.new_line_num)),
+                            };
+                            line_number_cell(
+                                ui,
+                                gutter.number(row, num),
+                                gutter.digits,
+                                gutter.is_cursor(row),
+                                heat,
+                            );
+                        }
+
+                        ui.horizontal(|ui| {
+                            render_spans(ui, line);
+                        });
+                    })
+                    .response;
+                track_scroll(ui, &response, Some(row), scroll_to, &mut events);
+                row_interaction(&response, row, line, data.options.swap_sides, &mut events);
+                line_details_tooltip(response, line);
+            }
+        });
+
+    events
+}
+
+/// `revertable` offers "Revert hunk", which only makes sense for the working copy
+fn show_side_by_side(
+    ui: &mut egui::Ui,
+    data: &DiffData,
+    gutter: Gutter,
+    revertable: bool,
+    selected_hunks: &BTreeSet<usize>,
+    scroll_to: Option<ScrollTarget>,
+) -> ViewEvents {
+    let available_width = ui.available_width();
+    let half_width = (available_width - 20.0) / 2.0;
+    let mut events = ViewEvents::default();
+    let mut next_hunk = 0;
+
+    ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            for (i, row) in data.side_by_side.iter().enumerate() {
+                // Deletions sort before insertions, so the smaller index leads
+                let first_line = row.old.into_iter().chain(row.new).min();
+                if let Some(hunk) = data.hunks.get(next_hunk)
+                    && first_line.is_some_and(|l| l >= hunk.start)
+                {
+                    let selected = selected_hunks.contains(&next_hunk);
+                    let expanded = context_expanded(data, &hunk.hunk);
+                    match hunk_bar(ui, &data.path, &hunk.hunk, revertable, selected, expanded) {
+                        Some(HunkBarClick::Revert) => events.revert_hunk = Some(next_hunk),
+                        Some(HunkBarClick::Select(modifiers)) => {
+                            events.select_hunk = Some((next_hunk, modifiers));
+                        }
+                        Some(HunkBarClick::ToggleContext) => {
+                            events.toggle_context = Some(next_hunk);
+                        }
+                        None => {}
+                    }
+                    next_hunk += 1;
+                }
+
+                let old = row.old.map(|i| &data.lines[i]);
+                let new = row.new.map(|i| &data.lines[i]);
+                let response = ui
+                    .horizontal(|ui| {
+                        render_pane_line(ui, old, half_width, true, gutter, i);
+                        ui.add_space(4.0);
+                        ui.separator();
+                        ui.add_space(4.0);
+                        render_pane_line(ui, new, half_width, false, gutter, i);
+                    })
+                    .response;
+                // Inline shows the old line first, so it's the better anchor
+                let line = row.old.or(row.new);
+                let target = scroll_to
+                    .filter(|t| row.old == Some(t.line) || row.new == Some(t.line))
+                    .and_then(|t| Some(ScrollTarget { line: line?, ..t }));
+                track_scroll(ui, &response, line, target, &mut events);
+                // Comments go on the new side unless the line was deleted
+                let commented = row.new.or(row.old).map(|l| &data.lines[l]);
+                if let Some(commented) = commented {
+                    row_interaction(
+                        &response,
+                        i,
+                        commented,
+                        data.options.swap_sides,
+                        &mut events,
+                    );
+                }
+            }
+        });
+
+    events
+}
+
+fn render_pane_line(
+    ui: &mut egui::Ui,
+    line: Option<&RenderedLine>,
+    width: f32,
+    is_old: bool,
+    gutter: Gutter,
+    row: usize,
+) {
+    let shows_gutter = if is_old {
+        gutter.shows_old()
+    } else {
+        gutter.shows_new()
+    };
+
+    let Some(line) = line else {
+        // Filler for a line that only exists on the other side, hatched so
+        // it can't be mistaken for an empty line
+        ui.horizontal(|ui| {
+            ui.set_width(width);
+            filler_background(ui, ui.available_rect_before_wrap());
+            if shows_gutter {
+                line_number_cell(ui, None, gutter.digits, false, None);
+            }
+        });
+        return;
+    };
+
+    let bg_color = line_bg_color(line);
+    let (line_num, heat) = if is_old {
+        (line.old_line_num, None)
+    } else {
+        (line.new_line_num, gutter.heat(line.new_line_num))
+    };
+
+    let response = ui
+        .horizontal(|ui| {
+            ui.set_width(width);
+
+            // Background
+            let rect = ui.available_rect_before_wrap();
+            ui.painter().rect_filled(rect, 0.0, bg_color);
+
+            // Line number
+            if shows_gutter {
+                line_number_cell(
+                    ui,
+                    gutter.number(row, line_num),
+                    gutter.digits,
+                    gutter.is_cursor(row),
+                    heat,
+                );
+            }
+
+            // Pre-rendered spans
+            ui.horizontal(|ui| {
+                render_spans(ui, line);
+            });
+        })
+        .response;
+    line_details_tooltip(response, line);
+}
+
+/// Spacing of the diagonal lines on filler rows
+const HATCH_SPACING: f32 = 6.0;
+
+/// Darker background with diagonal lines, for rows with nothing on this side
+fn filler_background(ui: &egui::Ui, rect: egui::Rect) {
+    let painter = ui.painter().with_clip_rect(rect.intersect(ui.clip_rect()));
+    painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+    let stroke = egui::Stroke::new(1.0, ui.visuals().weak_text_color().gamma_multiply(0.3));
+    let mut x = rect.left() - rect.height();
+    while x < rect.right() {
+        painter.line_segment(
+            [
+                egui::pos2(x, rect.bottom()),
+                egui::pos2(x + rect.height(), rect.top()),
+            ],
+            stroke,
+        );
+        x += HATCH_SPACING;
+    }
+}
+
+/// Draw the line's highlighted text, laying it out only when the font,
+/// zoom or wrap width changed since the last frame
+fn render_spans(ui: &mut egui::Ui, line: &RenderedLine) {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let wrap_width = match ui.wrap_mode() {
+        egui::TextWrapMode::Wrap => ui.available_width(),
+        _ => f32::INFINITY,
+    };
+    let key = GalleyKey {
+        wrap_width,
+        font_size: font_id.size,
+        pixels_per_point: ui.pixels_per_point(),
+        atlas_size: ui.fonts(|f| f.font_image_size()),
+    };
+
+    let galley = {
+        let mut cached = line.galley.borrow_mut();
+        match &*cached {
+            Some((cached_key, galley)) if *cached_key == key => galley.clone(),
+            _ => {
+                let job = layout_job(line, font_id, wrap_width, ui.text_valign());
+                let galley = ui.painter().layout_job(job);
+                *cached = Some((key, galley.clone()));
+                galley
+            }
+        }
+    };
+    ui.add(egui::Label::new(galley));
+}
+
+fn layout_job(
+    line: &RenderedLine,
+    font_id: egui::FontId,
+    wrap_width: f32,
+    valign: egui::Align,
+) -> egui::text::LayoutJob {
+    let mut job = egui::text::LayoutJob::default();
+    job.wrap.max_width = wrap_width;
+    let format = |color| egui::TextFormat {
+        valign,
+        ..egui::TextFormat::simple(font_id.clone(), color)
+    };
+    let trailing = line.trailing_whitespace.unwrap_or(line.text.len());
+    for span in &line.spans {
+        // Split spans where the background changes: at intra-line changes,
+        // and where trailing whitespace starts so it can be boxed
+        let mut start = span.range.start;
+        while start < span.range.end {
+            let mut end = span.range.end;
+            if start < trailing {
+                end = end.min(trailing);
+            }
+            let mut emphasized = false;
+            for range in &line.emphasis {
+                if range.start > start {
+                    end = end.min(range.start);
+                } else if range.end > start {
+                    end = end.min(range.end);
+                    emphasized = true;
+                }
+            }
+
+            let piece = if start >= trailing {
+                egui::TextFormat {
+                    background: Color32::from_rgba_unmultiplied(220, 80, 80, 110),
+                    ..format(Color32::PLACEHOLDER)
+                }
+            } else if emphasized {
+                egui::TextFormat {
+                    background: emphasis_color(line.change_type),
+                    ..format(span.color)
+                }
+            } else {
+                format(span.color)
+            };
+            job.append(&line.text[start..end], 0.0, piece);
+            start = end;
+        }
+    }
+    job
+}
+
+/// `revertable` offers "Revert hunk", which only makes sense for the working copy
+fn show_inline(
+    ui: &mut egui::Ui,
+    data: &DiffData,
+    gutter: Gutter,
+    revertable: bool,
+    selected_hunks: &BTreeSet<usize>,
+    scroll_to: Option<ScrollTarget>,
+) -> ViewEvents {
+    let mut events = ViewEvents::default();
+    let mut next_hunk = 0;
+
+    ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            for (row, line) in data.lines.iter().enumerate() {
+                if let Some(hunk) = data.hunks.get(next_hunk)
+                    && row >= hunk.start
+                {
+                    let selected = selected_hunks.contains(&next_hunk);
+                    let expanded = context_expanded(data, &hunk.hunk);
+                    match hunk_bar(ui, &data.path, &hunk.hunk, revertable, selected, expanded) {
+                        Some(HunkBarClick::Revert) => events.revert_hunk = Some(next_hunk),
+                        Some(HunkBarClick::Select(modifiers)) => {
+                            events.select_hunk = Some((next_hunk, modifiers));
+                        }
+                        Some(HunkBarClick::ToggleContext) => {
+                            events.toggle_context = Some(next_hunk);
+                        }
+                        None => {}
+                    }
+                    next_hunk += 1;
+                }
+
+                let bg_color = line_bg_color(line);
+
+                let prefix = match line.change_type {
+                    ChangeTag::Delete => "-",
+                    ChangeTag::Insert => "+",
+                    ChangeTag::Equal => " ",
+                };
+
+                let response = ui.horizontal(|ui| {
+                    // Background
+                    let rect = ui.available_rect_before_wrap();
+                    let line_rect =
+                        egui::Rect::from_min_size(rect.min, egui::vec2(ui.available_width(), 20.0));
+                    ui.painter().rect_filled(line_rect, 0.0, bg_color);
+
+                    // Line numbers; relative mode needs only one column
+                    let is_cursor = gutter.is_cursor(row);
+                    let heat = gutter.heat(line.new_line_num);
+                    if gutter.mode == GutterMode::Relative {
+                        let num = line.new_line_num.or(line.old_line_num);
+                        let number = gutter.number(row, num);
+                        line_number_cell(ui, number, gutter.digits, is_cursor, heat);
+                    } else {
+                        if gutter.shows_old() {
+                            line_number_cell(ui, line.old_line_num, gutter.digits, is_cursor, None);
+                        }
+                        if gutter.shows_new() {
+                            line_number_cell(ui, line.new_line_num, gutter.digits, is_cursor, heat);
+                        }
+                    }
+
+                    // Prefix
+                    let prefix_color = match line.change_type {
+                        ChangeTag::Delete => FileStatus::Deleted.color(),
+                        ChangeTag::Insert => FileStatus::Added.color(),
+                        ChangeTag::Equal => theme::dim_text(ui.visuals()),
+                    };
+                    ui.label(RichText::new(prefix).color(prefix_color).monospace());
+
+                    // Pre-rendered spans
+                    ui.horizontal(|ui| {
+                        render_spans(ui, line);
+                    });
+                });
+                track_scroll(ui, &response.response, Some(row), scroll_to, &mut events);
+                row_interaction(
+                    &response.response,
+                    row,
+                    line,
+                    data.options.swap_sides,
+                    &mut events,
+                );
+                line_details_tooltip(response.response, line);
+            }
+        });
+
+    events
+}
+
+/// Click to move the cursor, Shift-click to select up to the row,
+/// right-click to comment on the line or copy it
+fn row_interaction(
+    response: &egui::Response,
+    row: usize,
+    line: &RenderedLine,
+    swapped: bool,
+    events: &mut ViewEvents,
+) {
+    let response = response.interact(egui::Sense::click());
+    if response.clicked() {
+        if response.ctx.input(|i| i.modifiers.shift) {
+            events.extend_to = Some(row);
+        } else {
+            events.clicked_row = Some(row);
+        }
+    }
+    let (old, new) = file_line_nums(line, swapped);
+    let line_ref = match (new, old) {
+        (Some(line), _) => LineRef { line, old: false },
+        (None, Some(line)) => LineRef { line, old: true },
+        (None, None) => return,
+    };
+    response.context_menu(|ui| {
+        if ui.button(tr("Comment on this line...")).clicked() {
+            events.comment_on = Some(line_ref);
+        }
+        if ui
+            .button(tr("Copy as Markdown"))
+            .on_hover_text(tr("Copy the selected lines as a ```diff block"))
+            .clicked()
+        {
+            events.copy_markdown = Some(row);
+        }
+    });
+}
+
+/// The span of `DiffData::lines` shown on view rows `first..=last`. A
+/// side-by-side row holds up to two lines, so it's mapped through them.
+fn row_lines(
+    data: &DiffData,
+    view_mode: DiffViewMode,
+    first: usize,
+    last: usize,
+) -> Option<RangeInclusive<usize>> {
+    match view_mode {
+        DiffViewMode::Inline => {
+            let last = last.min(data.lines.len().checked_sub(1)?);
+            (first <= last).then_some(first..=last)
+        }
+        DiffViewMode::SideBySide => {
+            let last = last.min(data.side_by_side.len().checked_sub(1)?);
+            let rows = data.side_by_side.get(first..=last)?;
+            let lines = rows
+                .iter()
+                .flat_map(|row| row.old.into_iter().chain(row.new));
+            let (min, max) = lines.fold((usize::MAX, 0), |(min, max), l| (min.min(l), max.max(l)));
+            (min <= max).then_some(min..=max)
+        }
+    }
+}
+
+/// Rows as a fenced `diff` block with +/- prefixes, for pasting into
+/// GitHub comments or chat
+fn markdown_block(lines: &[RenderedLine]) -> String {
+    let mut out = String::from("```diff\n");
+    for line in lines.iter().filter(|l| l.kind == LineKind::Content) {
+        let prefix = match line.change_type {
+            ChangeTag::Delete => '-',
+            ChangeTag::Insert => '+',
+            ChangeTag::Equal => ' ',
+        };
+        out.push(prefix);
+        out.push_str(line.raw.as_deref().unwrap_or(&line.text));
+        out.push('\n');
+    }
+    out.push_str("```\n");
+    out
+}
+
+/// Record the first row below the top of the viewport, and scroll to
+/// `line` when it's the one asked for
+fn track_scroll(
+    ui: &egui::Ui,
+    response: &egui::Response,
+    line: Option<usize>,
+    scroll_to: Option<ScrollTarget>,
+    events: &mut ViewEvents,
+) {
+    if events.top_line.is_none() && response.rect.bottom() > ui.clip_rect().top() {
+        events.top_line = line;
+    }
+    if let Some(target) = scroll_to
+        && line == Some(target.line)
+    {
+        ui.scroll_to_rect(response.rect, Some(target.align));
+    }
+}
+
+/// A mark per hunk, placed where it falls in the whole diff, and a tick
+/// per find match. Hovering a hunk shows its lines and counts. Returns the
+/// middle of a clicked hunk's changed lines, or the match nearest to a
+/// click elsewhere, as an index into `DiffData::lines`.
+fn hunk_overview(
+    ui: &mut egui::Ui,
+    data: &DiffData,
+    view_mode: DiffViewMode,
+    matches: &[usize],
+) -> Option<usize> {
+    let (rect, background) = ui.allocate_exact_size(ui.available_size(), egui::Sense::click());
+    ui.painter()
+        .rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    // Side-by-side pairs lines into rows, so marks go by row there
+    let rows = match view_mode {
+        DiffViewMode::SideBySide => data.side_by_side.len(),
+        DiffViewMode::Inline => data.lines.len(),
+    }
+    .max(1);
+    let row_of = |line: usize| match view_mode {
+        DiffViewMode::SideBySide => data
+            .side_by_side
+            .partition_point(|row| row.old.into_iter().chain(row.new).min() < Some(line)),
+        DiffViewMode::Inline => line,
+    };
+    let y = |row: usize| rect.top() + rect.height() * row as f32 / rows as f32;
+
+    let mut clicked = None;
+    for (i, hunk) in data.hunks.iter().enumerate() {
+        let end = (hunk.start + hunk.hunk.lines.len()).min(data.lines.len());
+        let lines = &data.lines[hunk.start..end];
+        let changed = |l: &RenderedLine| l.change_type != ChangeTag::Equal;
+        let (Some(first), Some(last)) = (
+            lines.iter().position(changed),
+            lines.iter().rposition(changed),
+        ) else {
+            continue;
+        };
+        let (first, last) = (hunk.start + first, hunk.start + last);
+
+        let changed = &data.lines[first..=last];
+        let count = |tag| changed.iter().filter(|l| l.change_type == tag).count();
+        let (added, removed) = (count(ChangeTag::Insert), count(ChangeTag::Delete));
+        let color = match (added, removed) {
+            (_, 0) => FileStatus::Added.color(),
+            (0, _) => FileStatus::Deleted.color(),
+            _ => FileStatus::Modified.color(),
+        };
+
+        let top = y(row_of(first));
+        let bottom = y(row_of(last) + 1).max(top + 3.0);
+        let mark = egui::Rect::from_x_y_ranges(rect.x_range().shrink(2.0), top..=bottom);
+        let response = ui.interact(mark, ui.id().with(("hunk_mark", i)), egui::Sense::click());
+        let color = if response.hovered() {
+            color
+        } else {
+            color.gamma_multiply(0.7)
+        };
+        ui.painter().rect_filled(mark, 1.0, color);
+
+        let response = response
+            .on_hover_cursor(egui::CursorIcon::PointingHand)
+            .on_hover_ui(|ui| {
+                let swapped = data.options.swap_sides;
+                let nums = changed.iter().map(|l| file_line_nums(l, swapped));
+                let new = line_span(nums.clone().filter_map(|(_, new)| new));
+                match (new, line_span(nums.filter_map(|(old, _)| old))) {
+                    (Some(new), _) => ui.label(trf("Lines {}", &[&new])),
+                    (None, Some(old)) => ui.label(trf("Old lines {}", &[&old])),
+                    (None, None) => ui.label(&hunk.hunk.header),
+                };
+                ui.horizontal(|ui| {
+                    ui.label(
+                        RichText::new(format!("+{}", added))
+                            .monospace()
+                            .color(FileStatus::Added.color()),
+                    );
+                    ui.label(
+                        RichText::new(format!("-{}", removed))
+                            .monospace()
+                            .color(FileStatus::Deleted.color()),
+                    );
+                });
+            });
+        if response.clicked() {
+            clicked = Some((first + last) / 2);
+        }
+    }
+
+    for &line in matches {
+        let top = y(row_of(line));
+        let tick = egui::Rect::from_x_y_ranges(rect.x_range(), top..=top + 2.0);
+        ui.painter()
+            .rect_filled(tick, 0.0, Color32::from_rgb(220, 180, 80));
+    }
+    if background.clicked()
+        && let Some(pointer) = background.interact_pointer_pos()
+    {
+        let row = ((pointer.y - rect.top()) / rect.height() * rows as f32) as usize;
+        let line = match view_mode {
+            DiffViewMode::SideBySide => data
+                .side_by_side
+                .get(row)
+                .and_then(|row| row.old.or(row.new)),
+            DiffViewMode::Inline => Some(row),
+        };
+        clicked = line.and_then(|line| matches.iter().copied().min_by_key(|m| m.abs_diff(line)));
+    }
+
+    clicked
+}
+
+/// Query field with the match count and stepping buttons. Returns the
+/// line to scroll to, and false once closed.
+fn find_bar(ui: &mut egui::Ui, find: &mut Find, data: &DiffData) -> (Option<usize>, bool) {
+    let mut step = None;
+    let mut open = true;
+    ui.horizontal(|ui| {
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut find.query)
+                .hint_text(tr("Find in diff"))
+                .desired_width(220.0),
+        );
+        if std::mem::take(&mut find.focus) {
+            response.request_focus();
+        }
+        find.focused = response.has_focus();
+        // Enter leaves the field, so focus goes back for the next press
+        if response.lost_focus() {
+            if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                open = false;
+            } else if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                step = Some(if ui.input(|i| i.modifiers.shift) {
+                    -1
+                } else {
+                    1
+                });
+                response.request_focus();
+            }
+        }
+
+        let key = (data.path.clone(), find.query.clone());
+        if find.searched.as_ref() != Some(&key) {
+            find.matches = find_matches(data, &find.query);
+            find.current = 0;
+            find.searched = Some(key);
+            // Typing jumps to the first match as it goes
+            if response.changed() {
+                step = Some(0);
+            }
+        }
+
+        let count = match find.matches.len() {
+            0 if !find.query.is_empty() => tr("No matches").to_string(),
+            0 => String::new(),
+            total => format!("{}/{}", find.current + 1, total),
+        };
+        ui.label(RichText::new(count).color(theme::dim_text(ui.visuals())));
+        let any = !find.matches.is_empty();
+        if ui
+            .add_enabled(any, egui::Button::new("⬆").small())
+            .on_hover_text(tr("Previous match (Shift+Enter)"))
+            .clicked()
+        {
+            step = Some(-1);
+        }
+        if ui
+            .add_enabled(any, egui::Button::new("⬇").small())
+            .on_hover_text(tr("Next match (Enter)"))
+            .clicked()
+        {
+            step = Some(1);
+        }
+        if ui.small_button("✕").clicked() {
+            open = false;
+        }
+    });
+
+    let line = step
+        .filter(|_| !find.matches.is_empty())
+        .map(|step: isize| {
+            let total = find.matches.len() as isize;
+            find.current = (find.current as isize + step).rem_euclid(total) as usize;
+            find.matches[find.current]
+        });
+    (line, open)
+}
+
+/// Lines containing `query`, ignoring case
+fn find_matches(data: &DiffData, query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let query = query.to_lowercase();
+    data.lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| {
+            line.kind == LineKind::Content && line.text.to_lowercase().contains(&query)
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// `12–40`, or just `12` for a single line
+fn line_span(mut nums: impl Iterator<Item = usize>) -> Option<String> {
+    let start = nums.next()?;
+    Some(match nums.last() {
+        Some(end) if end != start => format!("{}–{}", start, end),
+        _ => start.to_string(),
+    })
+}
+
+/// What was clicked in a hunk's bar
+enum HunkBarClick {
+    /// The checkbox, with the modifiers held
+    Select(egui::Modifiers),
+    Revert,
+    ToggleContext,
+}
+
+/// New-file lines where each run of changes in `hunk` starts, the way
+/// `filter_to_chunks` finds expanded chunks
+fn change_starts(hunk: &Hunk) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut new_line = hunk.new_start.max(1);
+    let mut in_change = false;
+    for (tag, _) in &hunk.lines {
+        if *tag != ' ' && !in_change {
+            starts.push(new_line);
+        }
+        in_change = *tag != ' ';
+        if *tag != '-' {
+            new_line += 1;
+        }
+    }
+    starts
+}
+
+/// Whether `hunk` is shown with full context. Opened hunks merge with
+/// their neighbours, so any of the changes in it counts.
+fn context_expanded(data: &DiffData, hunk: &Hunk) -> bool {
+    let expanded = &data.options.expanded_context;
+    !expanded.is_empty()
+        && change_starts(hunk)
+            .iter()
+            .any(|start| expanded.contains(start))
+}
+
+/// Actions for one hunk. The copy buttons are handled here since they
+/// don't touch the repo.
+fn hunk_bar(
+    ui: &mut egui::Ui,
+    path: &str,
+    hunk: &Hunk,
+    revertable: bool,
+    selected: bool,
+    expanded: bool,
+) -> Option<HunkBarClick> {
+    let mut click = None;
+
+    ui.horizontal(|ui| {
+        let mut checked = selected;
+        if ui
+            .checkbox(&mut checked, "")
+            .on_hover_text(tr("Select for batch actions; Shift-click selects a range"))
+            .clicked()
+        {
+            click = Some(HunkBarClick::Select(ui.input(|i| i.modifiers)));
+        }
+        let context = hunk.context().unwrap_or("");
+        let ranges = match hunk.header.split_once(context) {
+            Some((ranges, _)) if !context.is_empty() => ranges.trim_end(),
+            _ => &hunk.header,
+        };
+        ui.label(
+            RichText::new(ranges)
+                .monospace()
+                .small()
+                .color(theme::dim_text(ui.visuals())),
+        );
+        if !context.is_empty() {
+            ui.label(RichText::new(context).monospace().small())
+                .on_hover_text(tr("Function or section the hunk is in"));
+        }
+        let (label, hover) = if expanded {
+            (
+                tr("Less context"),
+                tr("Back to a few lines around the changes"),
+            )
+        } else {
+            (
+                tr("Full context"),
+                tr("Show every line up to the hunks before and after this one"),
+            )
+        };
+        if ui.small_button(label).on_hover_text(hover).clicked() {
+            click = Some(HunkBarClick::ToggleContext);
+        }
+        if revertable
+            && ui
+                .small_button(tr("Revert hunk"))
+                .on_hover_text(tr("Undo this hunk in the working copy"))
+                .clicked()
+        {
+            click = Some(HunkBarClick::Revert);
+        }
+        if ui.small_button(tr("Copy as patch")).clicked() {
+            let file = FilePatch {
+                path: path.to_string(),
+                is_new: false,
+                is_delete: false,
+                hunks: vec![hunk.clone()],
+            };
+            ui.ctx().copy_text(patch::format(&file));
+        }
+        if ui
+            .small_button(tr("Copy link"))
+            .on_hover_text(tr("le-differ --goto link to this hunk"))
+            .clicked()
+        {
+            let location = Location {
+                path: path.to_string(),
+                line: Some(hunk.new_start),
+            };
+            ui.ctx().copy_text(location.uri());
+        }
+        if ui.small_button(tr("Copy new text")).clicked() {
+            let added: Vec<&str> = hunk
+                .lines
+                .iter()
+                .filter(|(tag, _)| *tag == '+')
+                .map(|(_, text)| text.as_str())
+                .collect();
+            ui.ctx().copy_text(added.join("\n"));
+        }
+    });
+
+    click
+}
+
+/// Toggle a hunk, or with Shift check every hunk from the anchor, the last
+/// one toggled
+fn select_hunk(
+    selected: &mut BTreeSet<usize>,
+    anchor: &mut Option<usize>,
+    hunk: usize,
+    modifiers: egui::Modifiers,
+) {
+    match *anchor {
+        Some(from) if modifiers.shift => selected.extend(from.min(hunk)..=from.max(hunk)),
+        _ => {
+            if !selected.remove(&hunk) {
+                selected.insert(hunk);
+            }
+            *anchor = Some(hunk);
+        }
+    }
+}
+
+enum HunkSelectionAction {
+    Revert,
+    Copy,
+    Clear,
+}
+
+/// Batch actions for the checked hunks, shown while there are any
+fn hunk_selection_bar(
+    ui: &mut egui::Ui,
+    count: usize,
+    revertable: bool,
+) -> Option<HunkSelectionAction> {
+    if count == 0 {
+        return None;
+    }
+    let mut action = None;
+    ui.horizontal(|ui| {
+        ui.label(RichText::new(trf("{} hunks selected", &[&count])).strong());
+        if revertable && ui.small_button(trf("Revert {} hunks", &[&count])).clicked() {
+            action = Some(HunkSelectionAction::Revert);
+        }
+        if ui.small_button(tr("Copy as patch")).clicked() {
+            action = Some(HunkSelectionAction::Copy);
+        }
+        if ui
+            .small_button(tr("Clear selection"))
+            .on_hover_text(tr("Ctrl+A selects every hunk"))
+            .clicked()
+        {
+            action = Some(HunkSelectionAction::Clear);
+        }
+    });
+    action
+}
+
+/// (old, new) line numbers in terms of the files rather than the sides
+/// they're drawn on
+fn file_line_nums(line: &RenderedLine, swapped: bool) -> (Option<usize>, Option<usize>) {
+    if swapped {
+        (line.new_line_num, line.old_line_num)
+    } else {
+        (line.old_line_num, line.new_line_num)
+    }
+}
+
+/// Row to scroll to for new-side `line`: the start of the hunk containing
+/// it, or the line itself when it's outside every hunk
+fn goto_index(data: &DiffData, line: usize) -> Option<usize> {
+    let hunk = data.hunks.iter().find(|h| {
+        let new_count = h.hunk.lines.iter().filter(|(tag, _)| *tag != '-').count();
+        (h.hunk.new_start..h.hunk.new_start + new_count.max(1)).contains(&line)
+    });
+    hunk.map(|h| h.start).or_else(|| line_index(data, line))
+}
+
+/// First row at or past new-side `line`
+fn line_index(data: &DiffData, line: usize) -> Option<usize> {
+    data.lines.iter().position(|l| {
+        file_line_nums(l, data.options.swap_sides)
+            .1
+            .is_some_and(|n| n >= line)
+    })
+}
+
+/// Up and Down scroll to the start of the previous or next hunk from `top`
+fn step_hunk(ui: &mut egui::Ui, data: &DiffData, top: Option<usize>) -> Option<usize> {
+    let down = ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown));
+    let up = ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp));
+    let top = top.unwrap_or(0);
+    let mut starts = data.hunks.iter().map(|h| h.start);
+    match (down, up) {
+        (true, _) => starts.find(|&start| start > top),
+        (_, true) => starts.rfind(|&start| start < top),
+        _ => None,
+    }
+}
+
+/// A few lines above the first changed one, as an index into
+/// `DiffData::lines`; None when the change starts near the top anyway
+fn first_change_index(data: &DiffData) -> Option<usize> {
+    let first = data
+        .lines
+        .iter()
+        .position(|l| l.kind == LineKind::Content && l.change_type != ChangeTag::Equal)?;
+    (first > FIRST_CHANGE_CONTEXT).then(|| first - FIRST_CHANGE_CONTEXT)
+}
+
+/// Raw text and position of a changed line, with whitespace made visible
+fn line_details_tooltip(response: egui::Response, line: &RenderedLine) {
+    let Some(raw) = &line.raw else {
+        return;
+    };
+    response.on_hover_ui(|ui| {
+        let (label, line_num) = match line.change_type {
+            ChangeTag::Delete => ("old line {}", line.old_line_num),
+            _ => ("new line {}", line.new_line_num),
+        };
+        if let Some(n) = line_num {
+            ui.label(trf(label, &[&n]));
+        }
+        if let Some(offset) = line.byte_offset {
+            ui.label(trf("byte offset {}, {} bytes", &[&offset, &raw.len()]));
+        }
+
+        let visible: String = raw
+            .chars()
+            .map(|c| match c {
+                ' ' => '·',
+                '\t' => '→',
+                c => c,
+            })
+            .collect();
+        ui.label(RichText::new(visible).monospace());
+
+        let trailing = raw.chars().rev().take_while(|c| c.is_whitespace()).count();
+        if trailing > 0 {
+            ui.label(
+                RichText::new(trf("{} trailing whitespace characters", &[&trailing]))
+                    .color(FileStatus::Modified.color()),
+            );
+        }
+    });
+}
+
+/// Right-aligned line number in a cell sized from the font's digit width,
+/// so columns line up however many digits the file needs. The cursor row
+/// is drawn brighter. When the age heatmap is on, `heat` fills the cell.
+fn line_number_cell(
+    ui: &mut egui::Ui,
+    num: Option<usize>,
+    digits: usize,
+    is_cursor: bool,
+    heat: Option<Color32>,
+) {
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    let digit_width = ui.fonts_mut(|f| f.glyph_width(&font_id, '0'));
+    let height = ui.text_style_height(&egui::TextStyle::Monospace);
+
+    let (rect, _) = ui.allocate_exact_size(
+        egui::vec2(digit_width * (digits + 1) as f32, height),
+        egui::Sense::hover(),
+    );
+    if let Some(heat) = heat {
+        ui.painter().rect_filled(rect, 0.0, heat);
+    }
+
+    if let Some(n) = num {
+        ui.painter().text(
+            rect.right_center() - egui::vec2(digit_width, 0.0),
+            egui::Align2::RIGHT_CENTER,
+            n.to_string(),
+            font_id,
+            if is_cursor {
+                Color32::from_rgb(220, 180, 80)
+            } else {
+                theme::dim_text(ui.visuals())
+            },
+        );
+    }
+}
+
+/// Newest lines glow rust orange, the oldest fade to a dim blue
+fn heat_color(age: f32) -> Color32 {
+    let (old, new) = ([60.0, 90.0, 160.0, 40.0], [230.0, 120.0, 50.0, 150.0]);
+    let [r, g, b, a] = std::array::from_fn(|i| (old[i] + (new[i] - old[i]) * age) as u8);
+    Color32::from_rgba_unmultiplied(r, g, b, a)
+}
+
+/// Rank each line's change time among the file's distinct ones, from 0 for
+/// the oldest to 1 for the newest. Ranks rather than times, so one ancient
+/// line doesn't wash the rest out.
+fn line_ages(times: &[i64]) -> Vec<f32> {
+    let mut distinct = times.to_vec();
+    distinct.sort_unstable();
+    distinct.dedup();
+    let newest = distinct.len().saturating_sub(1).max(1) as f32;
+    times
+        .iter()
+        .map(|time| distinct.binary_search(time).unwrap_or(0) as f32 / newest)
+        .collect()
+}
+
+fn status_label(status: &FileStatus) -> &'static str {
+    match status {
+        FileStatus::Added => tr("added"),
+        FileStatus::Modified => tr("modified"),
+        FileStatus::Deleted => tr("deleted"),
+        FileStatus::Renamed => tr("renamed"),
+    }
+}
+
+fn line_bg_color(line: &RenderedLine) -> Color32 {
+    if let Some(conflict) = &line.conflict {
+        return conflict.color();
+    }
+    match (line.kind, line.change_type) {
+        (LineKind::CellHeader, ChangeTag::Equal) => Color32::from_rgb(44, 48, 58),
+        _ => change_tag_to_bg_color(line.change_type),
+    }
+}
+
+pub fn change_tag_to_bg_color(tag: ChangeTag) -> Color32 {
+    match tag {
+        ChangeTag::Delete => Color32::from_rgba_unmultiplied(220, 80, 80, 20),
+        ChangeTag::Insert => Color32::from_rgba_unmultiplied(80, 200, 120, 20),
+        ChangeTag::Equal => Color32::TRANSPARENT,
+    }
+}
+
+/// Background of the changed part of a replaced line, over its row's tint
+fn emphasis_color(tag: ChangeTag) -> Color32 {
+    match tag {
+        ChangeTag::Delete => Color32::from_rgba_unmultiplied(220, 80, 80, 70),
+        ChangeTag::Insert => Color32::from_rgba_unmultiplied(80, 200, 120, 70),
+        ChangeTag::Equal => Color32::TRANSPARENT,
+    }
+}
+
+// ============================================================================
+// Background computation (all heavy work happens here, off the UI thread)
+// ============================================================================
+
+fn compute_diff_data(
+    file: &ChangedFile,
+    source: &DiffSource,
+    language: &LanguageSettings,
+    options: DiffOptions,
+    highlight_max_lines: usize,
+    clipboard: Option<String>,
+) -> DiffData {
+    let path = file.path.as_str();
+    let _span = debug_span!("compute_diff_data", path).entered();
+    let started = Instant::now();
+    // Taken before reading so a save in between shows up as a change
+    let on_disk = clipboard.is_some() || source.new_revision().is_none();
+    let disk_modified = on_disk.then(|| modified_time(path)).flatten();
+    let mut notice = None;
+    let (mut old_content, mut new_content) = debug_span!("fetch").in_scope(|| match clipboard {
+        Some(text) => {
+            notice = Some(tr("Comparing the clipboard with the file on disk").to_string());
+            let disk = std::fs::read_to_string(jj::workspace_path(path)).unwrap_or_default();
+            (text, disk)
+        }
+        None => get_file_contents(file, source),
+    });
+    // Filtered content no longer matches the file, so its hunks can't be applied
+    let mut filtered = false;
+    if let Some(filter) = &language.filter {
+        let _filter_span = debug_span!("filter").entered();
+        let run = |content: &str| match content {
+            "" => Ok(String::new()),
+            _ => content_filter::run(filter, content),
+        };
+        match run(&old_content).and_then(|old| Ok((old, run(&new_content)?))) {
+            Ok((old, new)) => {
+                old_content = old;
+                new_content = new;
+                filtered = true;
+                notice = Some(trf("Filtered through `{}`", &[&filter.join(" ")]));
+            }
+            Err(e) => notice = Some(trf("Filter failed, showing the raw files: {}", &[&e])),
+        }
+    }
+    if options.swap_sides {
+        std::mem::swap(&mut old_content, &mut new_content);
+    }
+    let fetched = Instant::now();
+    let diff_span = debug_span!("diff").entered();
+    // Size of the version on screen, before any notebook or semantic transform
+    let shown = if (file.status == FileStatus::Deleted) != options.swap_sides {
+        &old_content
+    } else {
+        &new_content
+    };
+    let generated = is_marked_generated(shown);
+    let file_size = FileSize {
+        bytes: shown.len(),
+        lines: shown.lines().count(),
+    };
+
+    // Notebooks diff as flattened cell sources rather than raw JSON
+    let is_notebook = notebook::is_notebook(path);
+    if is_notebook {
+        let show_outputs = options.show_notebook_outputs;
+        if let (Some(old), Some(new)) = (
+            notebook::to_text(&old_content, show_outputs),
+            notebook::to_text(&new_content, show_outputs),
+        ) {
+            old_content = old;
+            new_content = new;
+        }
+    }
+
+    let markdown = is_markdown(path).then(|| MarkdownSources {
+        old: old_content.clone(),
+        new: new_content.clone(),
+    });
+
+    let mut table = None;
+    if options.table_diff && table_diff::supports(path) {
+        match table_diff::diff(path, &old_content, &new_content) {
+            Ok(diff) => table = Some(diff),
+            Err(e) => notice = Some(format!("Table diff unavailable, {}", e)),
+        }
+    }
+
+    let mut semantic_lines = None;
+    if options.semantic_diff && semantic_diff::supports(path) {
+        match semantic_diff::diff(path, &old_content, &new_content) {
+            Ok(tree) if tree.is_empty() => {
+                notice = Some("No semantic changes (formatting or ordering only)".to_string());
+                semantic_lines = Some(Vec::new());
+            }
+            Ok(tree) => semantic_lines = Some(tree_to_diff_lines(tree)),
+            Err(e) => notice = Some(format!("Semantic diff unavailable, {}", e)),
+        }
+    }
+
+    let mut structural_lines = None;
+    if options.structural_diff && structural_diff::supports(path) {
+        match structural_diff::diff(path, &old_content, &new_content) {
+            Ok(rows) => {
+                structural_lines = Some(structural_to_diff_lines(rows, &old_content, &new_content))
+            }
+            Err(e) => notice = Some(format!("Structural diff unavailable, {}", e)),
+        }
+    }
+
+    let is_semantic = semantic_lines.is_some();
+    // Structural rows pair lines that differ in formatting, so they can't be patched
+    let is_structural = structural_lines.is_some();
+    // New files have nothing to line up against, so skip the diff
+    let added = file.status == FileStatus::Added && !options.swap_sides && !is_notebook;
+    let line_based = semantic_lines.is_none() && structural_lines.is_none() && !is_notebook;
+    let mut diff_lines = semantic_lines.or(structural_lines).unwrap_or_else(|| {
+        if added {
+            added_lines(&new_content)
+        } else {
+            compute_diff(
+                &old_content,
+                &new_content,
+                options.ignore_case,
+                &options.expanded_context,
+                is_notebook,
+            )
+        }
+    });
+    let ignored_hunks = if line_based {
+        drop_ignored_chunks(&mut diff_lines, path, &old_content, &new_content, &options)
+    } else {
+        0
+    };
+    drop(diff_span);
+    let diffed = Instant::now();
+    let highlight_span = debug_span!("highlight").entered();
+
+    // Detect syntax; plain text skips loading syntect at all
+    let longest = old_content.lines().count().max(new_content.lines().count());
+    let highlight_skipped = !options.plain_text
+        && !options.highlight_anyway
+        && highlight_max_lines > 0
+        && longest > highlight_max_lines;
+    let syntax_set;
+    let theme_set;
+    let highlighting = if options.plain_text || highlight_skipped {
+        None
+    } else {
+        syntax_set = SyntaxSet::load_defaults_newlines();
+        theme_set = ThemeSet::load_defaults();
+        // A deleted file only has its old side to sniff
+        let content = if new_content.is_empty() {
+            &old_content
+        } else {
+            &new_content
+        };
+        Some(detect_highlighting(
+            path,
+            content,
+            language,
+            is_notebook,
+            is_semantic,
+            &syntax_set,
+            &theme_set.themes[if options.light_theme {
+                "InspiredGitHub"
+            } else {
+                "base16-ocean.dark"
+            }],
+        ))
+    };
+
+    let syntax = highlighting.map(|h| h.syntax.name.clone());
+
+    // Pre-render all lines, once for both view modes.
+    // Semantic rows are key paths, not lines of either file
+    let sources = (!is_semantic).then_some((old_content.as_str(), new_content.as_str()));
+    let plain_color = plain_text_color(options.light_theme);
+    let mut lines = render_lines(&diff_lines, sources, highlighting, language, plain_color);
+    let pairs = similar_line_pairs(&diff_lines);
+    if options.granularity != DiffGranularity::Line {
+        mark_intra_line_changes(&mut lines, &pairs, options.granularity);
+    }
+    if let Some((_, new)) = sources
+        && new.contains("<<<<<<<")
+    {
+        mark_conflicts(&mut lines, new);
+    }
+    drop(highlight_span);
+    let timings = Timings {
+        fetch: fetched - started,
+        diff: diffed - fetched,
+        highlight: diffed.elapsed(),
+    };
+    debug!(?timings, lines = lines.len(), "Diff computed");
+    let side_by_side = side_by_side_rows(&diff_lines, &pairs);
+    // Swapped hunks would revert the wrong way round, and case-folded ones
+    // would keep the new casing of lines they count as unchanged. Reverting
+    // against the clipboard would paste it into the file.
+    let hunks = if is_notebook
+        || is_semantic
+        || is_structural
+        || filtered
+        || options.swap_sides
+        || options.ignore_case
+        || options.compare_clipboard
+    {
+        Vec::new()
+    } else {
+        diff_hunks(&diff_lines, path, &old_content, &new_content)
+    };
+
+    let max_line_num = diff_lines
+        .iter()
+        .flat_map(|l| [l.old_line_num, l.new_line_num])
+        .flatten()
+        .max()
+        .unwrap_or(0);
+    let gutter_digits = max_line_num.to_string().len().max(3);
+
+    DiffData {
+        path: path.to_string(),
+        options,
+        notice,
+        gutter_digits,
+        lines,
+        side_by_side,
+        hunks,
+        ignored_hunks,
+        markdown,
+        table,
+        file_size,
+        generated,
+        highlight_skipped,
+        syntax,
+        timings,
+        disk_modified,
+    }
+}
+
+/// syntect's name for its fallback grammar
+const PLAIN_TEXT: &str = "Plain Text";
 
-        let Some(file) = file else {
-            ui.label("Select a file from the sidebar to view its diff.");
-            return;
-        };
+/// Every grammar syntect knows, sorted by name
+fn syntax_names() -> &'static [String] {
+    static NAMES: OnceLock<Vec<String>> = OnceLock::new();
+    NAMES.get_or_init(|| {
+        let mut names: Vec<String> = SyntaxSet::load_defaults_newlines()
+            .syntaxes()
+            .iter()
+            .map(|s| s.name.clone())
+            .collect();
+        names.sort_by_key(|name| name.to_lowercase());
+        names.dedup();
+        names
+    })
+}
 
-        self.ensure_loading(file, ui.ctx());
+/// `*.ext` for files with an extension, else the file name, like
+/// `Makefile`; grammar choices apply to every file it matches
+fn syntax_pattern(path: &str) -> String {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match Path::new(name).extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("*.{}", extension),
+        None => name.to_string(),
+    }
+}
 
-        match &self.state {
-            DiffState::Empty | DiffState::Loading { .. } => {
-                ui.vertical_centered(|ui| {
-                    ui.add_space(50.0);
-                    ui.spinner();
-                    ui.label("Loading diff...");
-                });
-            }
-            DiffState::Loaded(data) => match view_mode {
-                DiffViewMode::SideBySide => {
-                    show_side_by_side(ui, &data.old_lines, &data.new_lines);
-                }
-                DiffViewMode::Inline => {
-                    show_inline(ui, &data.inline_lines);
-                }
-            },
+/// Leave out the chunks whose changes are all comments, blank lines or
+/// reordered imports, as `options` asks. Returns how many were dropped.
+fn drop_ignored_chunks(
+    lines: &mut Vec<DiffLineRaw>,
+    path: &str,
+    old: &str,
+    new: &str,
+    options: &DiffOptions,
+) -> usize {
+    if !options.ignore_comments && !options.ignore_import_order {
+        return 0;
+    }
+    let comments = options.ignore_comments.then(|| {
+        (
+            ignore_rules::comment_lines(path, old),
+            ignore_rules::comment_lines(path, new),
+        )
+    });
+    let is_comment = |line: &DiffLineRaw| {
+        let Some((old, new)) = &comments else {
+            return false;
+        };
+        let (side, num) = match line.change_type {
+            ChangeTag::Delete => (old, line.old_line_num),
+            _ => (new, line.new_line_num),
+        };
+        line.content.trim().is_empty()
+            || num.and_then(|n| side.get(n - 1)).copied().unwrap_or(false)
+    };
+    let ignored = |chunk: &[DiffLineRaw]| {
+        let changed: Vec<&DiffLineRaw> = chunk
+            .iter()
+            .filter(|l| l.change_type != ChangeTag::Equal && l.kind == LineKind::Content)
+            .collect();
+        if changed.is_empty() {
+            return false;
+        }
+        let side = |tag| -> Vec<&str> {
+            changed
+                .iter()
+                .filter(|l| l.change_type == tag)
+                .map(|l| l.content.as_str())
+                .collect()
+        };
+        changed.iter().all(|l| is_comment(l))
+            || (options.ignore_import_order
+                && ignore_rules::reorders_imports(
+                    path,
+                    &side(ChangeTag::Delete),
+                    &side(ChangeTag::Insert),
+                ))
+    };
+
+    let separator = lines
+        .iter()
+        .find(|l| l.kind == LineKind::Separator)
+        .cloned();
+    let mut kept = Vec::with_capacity(lines.len());
+    let mut dropped = 0;
+    for chunk in lines.split(|l| l.kind == LineKind::Separator) {
+        if ignored(chunk) {
+            dropped += 1;
+            continue;
         }
+        if let Some(separator) = separator.as_ref().filter(|_| !kept.is_empty()) {
+            kept.push(separator.clone());
+        }
+        kept.extend_from_slice(chunk);
     }
+    *lines = kept;
+    dropped
 }
 
-fn show_side_by_side(ui: &mut egui::Ui, old_lines: &[RenderedLine], new_lines: &[RenderedLine]) {
-    let available_width = ui.available_width();
-    let half_width = (available_width - 20.0) / 2.0;
+fn modified_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(jj::workspace_path(path))
+        .and_then(|m| m.modified())
+        .ok()
+}
 
-    ScrollArea::vertical()
-        .auto_shrink([false, false])
-        .show(ui, |ui| {
-            for (old, new) in old_lines.iter().zip(new_lines.iter()) {
-                ui.horizontal(|ui| {
-                    render_pane_line(ui, old, half_width, true);
-                    ui.add_space(4.0);
-                    ui.separator();
-                    ui.add_space(4.0);
-                    render_pane_line(ui, new, half_width, false);
-                });
-            }
-        });
+/// Split filtered diff rows into patch hunks at the chunk separators
+fn diff_hunks(lines: &[DiffLineRaw], path: &str, old: &str, new: &str) -> Vec<DiffHunk> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let mut hunks = Vec::new();
+    let mut start = 0;
+
+    for (i, line) in lines.iter().enumerate() {
+        let end_of_chunk = line.kind == LineKind::Separator || i + 1 == lines.len();
+        if !end_of_chunk {
+            continue;
+        }
+        let end = if line.kind == LineKind::Separator {
+            i
+        } else {
+            i + 1
+        };
+        let chunk = &lines[start..end];
+        if !chunk.is_empty() {
+            // A side with no lines in the chunk starts at 0, e.g. a new file
+            let old_start = chunk.iter().find_map(|l| l.old_line_num).unwrap_or(0);
+            let new_start = chunk.iter().find_map(|l| l.new_line_num).unwrap_or(0);
+            let tagged = chunk
+                .iter()
+                .map(|l| {
+                    let tag = match l.change_type {
+                        ChangeTag::Delete => '-',
+                        ChangeTag::Insert => '+',
+                        ChangeTag::Equal => ' ',
+                    };
+                    (tag, l.content.clone())
+                })
+                .collect();
+            let hunk = Hunk::new(old_start, new_start, tagged);
+            // Deleted files have no new side to look in
+            let context = match new_start {
+                0 => hunk_context::enclosing(path, &old, old_start),
+                _ => hunk_context::enclosing(path, &new, new_start),
+            };
+            hunks.push(DiffHunk {
+                start,
+                hunk: match context {
+                    Some(context) => hunk.with_context(&context),
+                    None => hunk,
+                },
+            });
+        }
+        start = i + 1;
+    }
+
+    hunks
 }
 
-fn render_pane_line(ui: &mut egui::Ui, line: &RenderedLine, width: f32, is_old: bool) {
-    let bg_color = change_tag_to_bg_color(line.change_type);
-    let line_num = if is_old {
-        line.old_line_num
+/// Configured syntax first, then a modeline, the extension and finally the
+/// first line, which catches extensionless scripts with a shebang
+fn detect_highlighting<'a>(
+    path: &str,
+    content: &str,
+    language: &LanguageSettings,
+    is_notebook: bool,
+    is_semantic: bool,
+    syntax_set: &'a SyntaxSet,
+    theme: &'a syntect::highlighting::Theme,
+) -> Highlighting<'a> {
+    let extension = if is_notebook {
+        "py"
+    } else if is_semantic {
+        // Tree rows are `key: value`, which reads well as YAML whatever the source
+        "yaml"
     } else {
-        line.new_line_num
+        Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
     };
-    let line_num_text = line_num
-        .map(|n| format!("{:>4} ", n))
-        .unwrap_or_else(|| "     ".to_string());
+    let configured = language
+        .syntax
+        .as_deref()
+        .filter(|_| !is_semantic)
+        .and_then(|name| syntax_set.find_syntax_by_token(name));
+    // Notebook and semantic rows aren't the file's own text
+    let sniff = !is_notebook && !is_semantic;
+    let syntax = configured
+        .or_else(|| {
+            modeline_syntax(content)
+                .filter(|_| sniff)
+                .and_then(|name| syntax_set.find_syntax_by_token(name))
+        })
+        .or_else(|| syntax_set.find_syntax_by_extension(extension))
+        .or_else(|| {
+            content
+                .lines()
+                .next()
+                .filter(|_| sniff)
+                .and_then(|line| syntax_set.find_syntax_by_first_line(line))
+        })
+        .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
 
-    ui.horizontal(|ui| {
-        ui.set_width(width);
+    Highlighting {
+        syntax,
+        theme,
+        syntax_set,
+    }
+}
 
-        // Background
-        let rect = ui.available_rect_before_wrap();
-        ui.painter().rect_filled(rect, 0.0, bg_color);
+/// Everything syntect needs to color a file
+#[derive(Clone, Copy)]
+struct Highlighting<'a> {
+    syntax: &'a syntect::parsing::SyntaxReference,
+    theme: &'a syntect::highlighting::Theme,
+    syntax_set: &'a SyntaxSet,
+}
 
-        // Line number
-        ui.label(
-            RichText::new(&line_num_text)
-                .color(Color32::from_rgb(100, 100, 110))
-                .monospace(),
-        );
+/// Language named by a vim modeline in the first or last five lines
+/// (`vim: set ft=python:`) or an emacs one in the first two
+/// (`-*- mode: ruby -*-`)
+fn modeline_syntax(content: &str) -> Option<&str> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut edges = lines
+        .iter()
+        .take(5)
+        .chain(lines.iter().skip(5).rev().take(5));
+    let vim = edges.find_map(|line| {
+        let start = ["vim:", "vi:", "ex:"]
+            .iter()
+            .filter_map(|tag| {
+                line.match_indices(tag)
+                    .find(|(i, _)| *i == 0 || line[..*i].ends_with(char::is_whitespace))
+                    .map(|(i, tag)| i + tag.len())
+            })
+            .min()?;
+        line[start..]
+            .split(|c: char| c.is_whitespace() || c == ':')
+            .find_map(|option| {
+                let (key, value) = option.split_once('=')?;
+                matches!(key, "ft" | "filetype" | "syn" | "syntax").then_some(value)
+            })
+    });
 
-        // Pre-rendered spans
-        ui.horizontal(|ui| {
-            ui.spacing_mut().item_spacing.x = 0.0;
-            for span in &line.spans {
-                ui.label(RichText::new(&span.text).color(span.color).monospace());
+    vim.or_else(|| {
+        lines.iter().take(2).find_map(|line| {
+            let (_, rest) = line.split_once("-*-")?;
+            let (inner, _) = rest.split_once("-*-")?;
+            let inner = inner.trim();
+            if !inner.contains(':') {
+                return Some(inner);
             }
-        });
-    });
+            inner.split(';').find_map(|var| {
+                let (key, value) = var.split_once(':')?;
+                (key.trim().eq_ignore_ascii_case("mode")).then(|| value.trim())
+            })
+        })
+    })
+    .filter(|name| !name.is_empty())
 }
 
-fn show_inline(ui: &mut egui::Ui, lines: &[RenderedLine]) {
-    ScrollArea::vertical()
-        .auto_shrink([false, false])
-        .show(ui, |ui| {
-            for line in lines {
-                let bg_color = change_tag_to_bg_color(line.change_type);
-                let line_num_text = match (line.old_line_num, line.new_line_num) {
-                    (Some(o), Some(n)) => format!("{:>4} {:>4} ", o, n),
-                    (Some(o), None) => format!("{:>4}      ", o),
-                    (None, Some(n)) => format!("     {:>4} ", n),
-                    (None, None) => "          ".to_string(),
-                };
+/// Without `highlighting` every line is a single plain span
+fn render_lines(
+    lines: &[DiffLineRaw],
+    sources: Option<(&str, &str)>,
+    highlighting: Option<Highlighting>,
+    language: &LanguageSettings,
+    plain_color: Color32,
+) -> Vec<RenderedLine> {
+    // Each side is highlighted as a whole file so block comments and strings
+    // aren't thrown off by lines from the other side
+    let files = highlighting.zip(sources).map(|(h, (old, new))| {
+        (
+            highlight_file(old, h, language),
+            highlight_file(new, h, language),
+        )
+    });
 
-                let prefix = match line.change_type {
-                    ChangeTag::Delete => "-",
-                    ChangeTag::Insert => "+",
-                    ChangeTag::Equal => " ",
-                };
+    // Rows that don't come from either file share one running highlighter
+    let mut highlighter = highlighting.map(|h| (HighlightLines::new(h.syntax, h.theme), h));
+    let mut result = Vec::with_capacity(lines.len());
 
-                ui.horizontal(|ui| {
-                    // Background
-                    let rect = ui.available_rect_before_wrap();
-                    let line_rect =
-                        egui::Rect::from_min_size(rect.min, egui::vec2(ui.available_width(), 20.0));
-                    ui.painter().rect_filled(line_rect, 0.0, bg_color);
+    for line in lines {
+        // Structural rows aren't source, keep them out of the highlighter state
+        if line.kind != LineKind::Content {
+            result.push(RenderedLine {
+                old_line_num: line.old_line_num,
+                new_line_num: line.new_line_num,
+                byte_offset: None,
+                raw: None,
+                trailing_whitespace: None,
+                emphasis: Vec::new(),
+                text: Arc::from(line.content.as_str()),
+                spans: vec![HighlightedSpan {
+                    range: 0..line.content.len(),
+                    color: Color32::from_rgb(150, 160, 180),
+                }],
+                change_type: line.change_type,
+                kind: line.kind,
+                conflict: None,
+                galley: RefCell::new(None),
+            });
+            continue;
+        }
 
-                    // Line numbers
-                    ui.label(
-                        RichText::new(&line_num_text)
-                            .color(Color32::from_rgb(100, 100, 110))
-                            .monospace(),
-                    );
+        let from_file = files
+            .as_ref()
+            .and_then(|(old, new)| match line.change_type {
+                ChangeTag::Delete => old.get(line.old_line_num? - 1),
+                _ => new.get(line.new_line_num? - 1),
+            });
+        let (content, spans) = match from_file {
+            Some((text, spans)) => (text.clone(), spans.clone()),
+            None => {
+                // Tabs are expanded only for display; offsets still refer to the file
+                let content = expand_tabs(&line.content, language.tab_width);
+                let spans = match &mut highlighter {
+                    Some((highlighter, h)) => highlight_line(highlighter, &content, h.syntax_set),
+                    None => vec![HighlightedSpan {
+                        range: 0..content.len(),
+                        color: plain_color,
+                    }],
+                };
+                (content, spans)
+            }
+        };
 
-                    // Prefix
-                    let prefix_color = match line.change_type {
-                        ChangeTag::Delete => FileStatus::Deleted.color(),
-                        ChangeTag::Insert => FileStatus::Added.color(),
-                        ChangeTag::Equal => Color32::from_rgb(100, 100, 110),
-                    };
-                    ui.label(RichText::new(prefix).color(prefix_color).monospace());
+        let trimmed = content.trim_end().len();
+        let trailing_whitespace = (language.highlight_trailing_whitespace
+            && line.change_type == ChangeTag::Insert
+            && trimmed < content.len())
+        .then_some(trimmed);
 
-                    // Pre-rendered spans
-                    ui.horizontal(|ui| {
-                        ui.spacing_mut().item_spacing.x = 0.0;
-                        for span in &line.spans {
-                            ui.label(RichText::new(&span.text).color(span.color).monospace());
-                        }
-                    });
-                });
-            }
+        result.push(RenderedLine {
+            old_line_num: line.old_line_num,
+            new_line_num: line.new_line_num,
+            byte_offset: line.byte_offset,
+            raw: (line.change_type != ChangeTag::Equal).then(|| Arc::from(line.content.as_str())),
+            trailing_whitespace,
+            emphasis: Vec::new(),
+            text: Arc::from(content),
+            spans,
+            change_type: line.change_type,
+            kind: line.kind,
+            conflict: None,
+            galley: RefCell::new(None),
         });
+    }
+
+    result
 }
 
-fn status_label(status: &FileStatus) -> &'static str {
-    match status {
-        FileStatus::Added => "added",
-        FileStatus::Modified => "modified",
-        FileStatus::Deleted => "deleted",
-        FileStatus::Renamed => "renamed",
+/// Deleted and inserted lines at least this alike are edits of each other
+/// and share a row; less alike, they stay a removal and an addition
+const PAIR_MIN_RATIO: f32 = 0.6;
+
+/// Every deletion in a run is compared with every insertion after it, so
+/// longer runs are left unpaired
+const PAIR_MAX_RUN: usize = 64;
+
+/// (deleted, inserted) indices into `diff_lines` of lines that are edits of
+/// each other. Within each run of deletions and the insertions after it,
+/// each deletion takes the most similar insertion past the last one taken.
+fn similar_line_pairs(diff_lines: &[DiffLineRaw]) -> Vec<(usize, usize)> {
+    let is_run =
+        |line: &DiffLineRaw, tag| line.kind == LineKind::Content && line.change_type == tag;
+    let mut pairs = Vec::new();
+    let mut i = 0;
+    while i < diff_lines.len() {
+        let deleted = diff_lines[i..]
+            .iter()
+            .take_while(|l| is_run(l, ChangeTag::Delete))
+            .count();
+        if deleted == 0 {
+            i += 1;
+            continue;
+        }
+        let inserts = i + deleted;
+        let inserted = diff_lines[inserts..]
+            .iter()
+            .take_while(|l| is_run(l, ChangeTag::Insert))
+            .count();
+        if deleted <= PAIR_MAX_RUN && inserted <= PAIR_MAX_RUN {
+            let mut next = inserts;
+            for old in i..inserts {
+                // Reversed so the earliest of equally good matches wins
+                let best = (next..inserts + inserted)
+                    .rev()
+                    .map(|new| {
+                        let ratio =
+                            line_similarity(&diff_lines[old].content, &diff_lines[new].content);
+                        (new, ratio)
+                    })
+                    .filter(|&(_, ratio)| ratio >= PAIR_MIN_RATIO)
+                    .max_by(|a, b| a.1.total_cmp(&b.1));
+                if let Some((new, _)) = best {
+                    pairs.push((old, new));
+                    next = new + 1;
+                }
+            }
+        }
+        i = inserts + inserted;
     }
+    pairs
 }
 
-fn change_tag_to_bg_color(tag: ChangeTag) -> Color32 {
-    match tag {
-        ChangeTag::Delete => Color32::from_rgba_unmultiplied(220, 80, 80, 20),
-        ChangeTag::Insert => Color32::from_rgba_unmultiplied(80, 200, 120, 20),
-        ChangeTag::Equal => Color32::TRANSPARENT,
+/// Share of characters two lines have in common, ignoring indentation
+fn line_similarity(old: &str, new: &str) -> f32 {
+    let (old, new) = (old.trim(), new.trim());
+    // Lengths this far apart can't reach the threshold, so skip the diff
+    let (short, long) = (old.len().min(new.len()), old.len().max(new.len()));
+    if long > 0 && 2.0 * short as f32 / ((short + long) as f32) < PAIR_MIN_RATIO {
+        return 0.0;
     }
+    TextDiff::configure()
+        .timeout(INTRA_LINE_TIMEOUT)
+        .diff_chars(old, new)
+        .ratio()
 }
 
-// ============================================================================
-// Background computation (all heavy work happens here, off the UI thread)
-// ============================================================================
+/// Mark what changed within each pair of similar deleted and inserted lines
+fn mark_intra_line_changes(
+    lines: &mut [RenderedLine],
+    pairs: &[(usize, usize)],
+    granularity: DiffGranularity,
+) {
+    for &(old, new) in pairs {
+        let (old_ranges, new_ranges) =
+            intra_line_changes(&lines[old].text, &lines[new].text, granularity);
+        lines[old].emphasis = old_ranges;
+        lines[new].emphasis = new_ranges;
+    }
+}
 
-fn compute_diff_data(path: &str, status: &FileStatus) -> DiffData {
-    let syntax_set = SyntaxSet::load_defaults_newlines();
-    let theme_set = ThemeSet::load_defaults();
+/// Pairs sharing less than this are unrelated lines rather than edits of
+/// each other, and highlighting bits of them is just noise
+const INTRA_LINE_MIN_RATIO: f32 = 0.5;
 
-    let (old_content, new_content) = get_file_contents(path, status);
-    let diff_lines = compute_diff(&old_content, &new_content);
+/// Bounds the time spent on one pathological pair of lines
+const INTRA_LINE_TIMEOUT: Duration = Duration::from_millis(5);
 
-    // Detect syntax
-    let extension = Path::new(path)
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("");
-    let syntax = syntax_set
-        .find_syntax_by_extension(extension)
-        .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
-    let theme = &theme_set.themes["base16-ocean.dark"];
+/// Changed byte ranges of `old` and `new`, empty when the lines are too
+/// different to be worth it
+fn intra_line_changes(
+    old: &str,
+    new: &str,
+    granularity: DiffGranularity,
+) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let mut config = TextDiff::configure();
+    config.timeout(INTRA_LINE_TIMEOUT);
+    let diff = match granularity {
+        DiffGranularity::Line => return (Vec::new(), Vec::new()),
+        DiffGranularity::Word => config.diff_words(old, new),
+        DiffGranularity::Character => config.diff_chars(old, new),
+    };
+    if diff.ratio() < INTRA_LINE_MIN_RATIO {
+        return (Vec::new(), Vec::new());
+    }
 
-    // Pre-render all lines with syntax highlighting
-    let inline_lines = render_lines(&diff_lines, syntax, theme, &syntax_set);
+    let (mut old_ranges, mut new_ranges) = (Vec::new(), Vec::new());
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for change in diff.iter_all_changes() {
+        let len = change.value().len();
+        match change.tag() {
+            ChangeTag::Equal => {
+                old_pos += len;
+                new_pos += len;
+            }
+            ChangeTag::Delete => {
+                push_range(&mut old_ranges, old_pos..old_pos + len);
+                old_pos += len;
+            }
+            ChangeTag::Insert => {
+                push_range(&mut new_ranges, new_pos..new_pos + len);
+                new_pos += len;
+            }
+        }
+    }
+    (old_ranges, new_ranges)
+}
 
-    // Split for side-by-side and render
-    let (old_diff, new_diff) = split_for_side_by_side(&diff_lines);
-    let old_lines = render_lines(&old_diff, syntax, theme, &syntax_set);
-    let new_lines = render_lines(&new_diff, syntax, theme, &syntax_set);
+/// Append `range`, merging it into the last one when they touch
+fn push_range(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    }
+}
 
-    DiffData {
-        path: path.to_string(),
-        inline_lines,
-        old_lines,
-        new_lines,
+/// Tint lines inside conflict markers by side and draw the markers as labels
+fn mark_conflicts(lines: &mut [RenderedLine], new: &str) {
+    let parts = conflicts::parse(new);
+    for line in lines.iter_mut() {
+        if line.kind != LineKind::Content || line.change_type == ChangeTag::Delete {
+            continue;
+        }
+        let Some(conflict) = line
+            .new_line_num
+            .and_then(|n| parts.get(n - 1))
+            .and_then(Option::as_ref)
+        else {
+            continue;
+        };
+        if let ConflictPart::Marker(label) = &conflict.part {
+            line.text = Arc::from(label.as_str());
+            line.spans = vec![HighlightedSpan {
+                range: 0..label.len(),
+                color: Color32::from_rgb(220, 180, 80),
+            }];
+            line.trailing_whitespace = None;
+        }
+        line.conflict = Some(conflict.clone());
     }
 }
 
-fn render_lines(
-    lines: &[DiffLineRaw],
-    syntax: &syntect::parsing::SyntaxReference,
-    theme: &syntect::highlighting::Theme,
+/// Tab-expanded text and spans for every line of `content`
+fn highlight_file(
+    content: &str,
+    highlighting: Highlighting,
+    language: &LanguageSettings,
+) -> Vec<(String, Vec<HighlightedSpan>)> {
+    let mut highlighter = HighlightLines::new(highlighting.syntax, highlighting.theme);
+
+    LinesWithEndings::from(content)
+        .map(|line| {
+            // The newline is fed to the highlighter, which ends line comments
+            // on it, but isn't displayed
+            let mut text = expand_tabs(line, language.tab_width);
+            let mut spans = highlight_line(&mut highlighter, &text, highlighting.syntax_set);
+            let shown = text.trim_end_matches('\n').len();
+            text.truncate(shown);
+            spans.retain_mut(|span| {
+                span.range.end = span.range.end.min(shown);
+                span.range.start < span.range.end
+            });
+            (text, spans)
+        })
+        .collect()
+}
+
+fn highlight_line(
+    highlighter: &mut HighlightLines,
+    text: &str,
     syntax_set: &SyntaxSet,
-) -> Vec<RenderedLine> {
-    let mut highlighter = HighlightLines::new(syntax, theme);
-    let mut result = Vec::with_capacity(lines.len());
+) -> Vec<HighlightedSpan> {
+    let regions = highlighter
+        .highlight_line(text, syntax_set)
+        .unwrap_or_default();
 
-    for line in lines {
-        let regions = highlighter
-            .highlight_line(&line.content, syntax_set)
-            .unwrap_or_default();
-
-        let spans: Vec<HighlightedSpan> = regions
-            .into_iter()
-            .map(|(style, text)| HighlightedSpan {
-                text: text.to_string(),
+    // Regions are contiguous slices of the line, in order
+    let mut start = 0;
+    regions
+        .into_iter()
+        .map(|(style, text)| {
+            let range = start..start + text.len();
+            start = range.end;
+            HighlightedSpan {
+                range,
                 color: Color32::from_rgba_unmultiplied(
                     style.foreground.r,
                     style.foreground.g,
                     style.foreground.b,
                     style.foreground.a,
                 ),
-            })
-            .collect();
-
-        result.push(RenderedLine {
-            old_line_num: line.old_line_num,
-            new_line_num: line.new_line_num,
-            spans,
-            change_type: line.change_type,
-        });
-    }
-
-    result
+            }
+        })
+        .collect()
 }
 
 /// Raw diff line before rendering
+#[derive(Clone)]
 struct DiffLineRaw {
     old_line_num: Option<usize>,
     new_line_num: Option<usize>,
+    byte_offset: Option<usize>,
     content: String,
     change_type: ChangeTag,
+    kind: LineKind,
 }
 
-fn get_file_contents(path: &str, status: &FileStatus) -> (String, String) {
-    match status {
-        FileStatus::Added => {
-            let new_content = std::fs::read_to_string(path).unwrap_or_default();
-            (String::new(), new_content)
-        }
-        FileStatus::Deleted => {
-            let old_content = get_jj_file_content(path);
-            (old_content, String::new())
-        }
-        FileStatus::Modified | FileStatus::Renamed => {
-            let old_content = get_jj_file_content(path);
-            let new_content = std::fs::read_to_string(path).unwrap_or_default();
-            (old_content, new_content)
-        }
+/// Renamed files are read from their old path on the old side
+fn get_file_contents(file: &ChangedFile, source: &DiffSource) -> (String, String) {
+    let path = file.path.as_str();
+    let old_path = file.old_path.as_deref().unwrap_or(path);
+    let read_old = || jj::file_show(source.old_revision(), old_path);
+    let read_new = || match source.new_revision() {
+        Some(revision) => jj::file_show(revision, path),
+        None => std::fs::read_to_string(jj::workspace_path(path)).unwrap_or_default(),
+    };
+
+    match file.status {
+        FileStatus::Added => (String::new(), read_new()),
+        FileStatus::Deleted => (read_old(), String::new()),
+        FileStatus::Modified | FileStatus::Renamed => (read_old(), read_new()),
     }
 }
 
-fn get_jj_file_content(path: &str) -> String {
-    let output = Command::new("jj")
-        .args(["file", "show", "-r", "@-", path])
-        .output();
+/// Replace tabs with spaces up to the next tab stop, so indentation
+/// renders at the configured width in the monospace view. Columns count
+/// display width, so wide CJK/emoji glyphs advance two cells.
+fn expand_tabs(content: &str, tab_width: usize) -> String {
+    if !content.contains('\t') || tab_width == 0 {
+        return content.to_string();
+    }
 
-    match output {
-        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).to_string(),
-        _ => String::new(),
+    let mut out = String::with_capacity(content.len());
+    let mut column = 0;
+    for c in content.chars() {
+        match c {
+            '\t' => {
+                let spaces = tab_width - column % tab_width;
+                out.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\n' => {
+                out.push(c);
+                column = 0;
+            }
+            _ => {
+                out.push(c);
+                column += c.width().unwrap_or(0);
+            }
+        }
     }
+    out
+}
+
+/// Semantic tree rows have no meaningful line numbers in either file
+fn tree_to_diff_lines(tree: Vec<semantic_diff::TreeLine>) -> Vec<DiffLineRaw> {
+    tree.into_iter()
+        .map(|line| DiffLineRaw {
+            old_line_num: None,
+            new_line_num: None,
+            byte_offset: None,
+            content: line.text,
+            change_type: line.change_type,
+            kind: LineKind::Content,
+        })
+        .collect()
+}
+
+fn structural_to_diff_lines(
+    rows: Vec<structural_diff::StructuralLine>,
+    old: &str,
+    new: &str,
+) -> Vec<DiffLineRaw> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let all_lines: Vec<DiffLineRaw> = rows
+        .into_iter()
+        .map(|row| {
+            let content = match row.change_type {
+                ChangeTag::Delete => row.old_line.and_then(|n| old_lines.get(n - 1)),
+                _ => row.new_line.and_then(|n| new_lines.get(n - 1)),
+            };
+            DiffLineRaw {
+                old_line_num: row.old_line,
+                new_line_num: row.new_line,
+                byte_offset: None,
+                content: content.copied().unwrap_or_default().to_string(),
+                change_type: row.change_type,
+                kind: LineKind::Content,
+            }
+        })
+        .collect();
+    filter_to_chunks(&all_lines, &BTreeSet::new())
 }
 
 /// Number of context lines to show around changes
 const CONTEXT_LINES: usize = 3;
 
-fn compute_diff(old: &str, new: &str) -> Vec<DiffLineRaw> {
-    let diff = TextDiff::from_lines(old, new);
+/// Every line of a new file as an insertion
+fn added_lines(new: &str) -> Vec<DiffLineRaw> {
+    let mut offset = 0;
+    LinesWithEndings::from(new)
+        .enumerate()
+        .map(|(i, line)| {
+            let byte_offset = offset;
+            offset += line.len();
+            DiffLineRaw {
+                old_line_num: None,
+                new_line_num: Some(i + 1),
+                byte_offset: Some(byte_offset),
+                content: line.trim_end_matches('\n').to_string(),
+                change_type: ChangeTag::Insert,
+                kind: LineKind::Content,
+            }
+        })
+        .collect()
+}
+
+fn compute_diff(
+    old: &str,
+    new: &str,
+    ignore_case: bool,
+    expanded: &BTreeSet<usize>,
+    is_notebook: bool,
+) -> Vec<DiffLineRaw> {
+    let changes = if ignore_case {
+        case_insensitive_changes(old, new)
+    } else {
+        TextDiff::from_lines(old, new)
+            .iter_all_changes()
+            .map(|change| {
+                let value = change.value();
+                match change.tag() {
+                    ChangeTag::Delete => (ChangeTag::Delete, Some(value), None),
+                    ChangeTag::Insert => (ChangeTag::Insert, None, Some(value)),
+                    ChangeTag::Equal => (ChangeTag::Equal, Some(value), Some(value)),
+                }
+            })
+            .collect()
+    };
     let mut all_lines = Vec::new();
     let mut old_line_num = 1usize;
     let mut new_line_num = 1usize;
+    let mut old_offset = 0usize;
+    let mut new_offset = 0usize;
 
-    for change in diff.iter_all_changes() {
-        let (old_num, new_num) = match change.tag() {
+    for (tag, old_value, new_value) in changes {
+        let byte_offset = match tag {
+            ChangeTag::Delete => old_offset,
+            _ => new_offset,
+        };
+        old_offset += old_value.map_or(0, str::len);
+        new_offset += new_value.map_or(0, str::len);
+
+        let (old_num, new_num) = match tag {
             ChangeTag::Delete => {
                 let num = old_line_num;
                 old_line_num += 1;
@@ -429,20 +3853,69 @@ fn compute_diff(old: &str, new: &str) -> Vec<DiffLineRaw> {
             }
         };
 
+        let content = new_value.or(old_value).unwrap_or_default();
+        let content = content.trim_end_matches('\n');
+        // Other files may contain the private-use character themselves
+        let cell = content
+            .strip_prefix(notebook::CELL_MARKER)
+            .filter(|_| is_notebook);
+        let (kind, content) = match cell {
+            Some(label) => (LineKind::CellHeader, label),
+            None => (LineKind::Content, content),
+        };
+
         all_lines.push(DiffLineRaw {
             old_line_num: old_num,
             new_line_num: new_num,
-            content: change.value().trim_end_matches('\n').to_string(),
-            change_type: change.tag(),
+            byte_offset: Some(byte_offset),
+            content: content.to_string(),
+            change_type: tag,
+            kind,
         });
     }
 
     // Filter to only show chunks with context
-    filter_to_chunks(&all_lines)
+    filter_to_chunks(&all_lines, expanded)
+}
+
+/// (tag, old line, new line) with lines compared lowercased; lines equal
+/// that way keep both their texts
+fn case_insensitive_changes<'a>(
+    old: &'a str,
+    new: &'a str,
+) -> Vec<(ChangeTag, Option<&'a str>, Option<&'a str>)> {
+    let old_lines: Vec<&str> = LinesWithEndings::from(old).collect();
+    let new_lines: Vec<&str> = LinesWithEndings::from(new).collect();
+    let lower = |lines: &[&str]| lines.iter().map(|l| l.to_lowercase()).collect::<Vec<_>>();
+    let ops = similar::capture_diff_slices(
+        similar::Algorithm::Myers,
+        &lower(&old_lines),
+        &lower(&new_lines),
+    );
+
+    let mut changes = Vec::new();
+    for op in ops {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        if tag == similar::DiffTag::Equal {
+            for (o, n) in old_range.zip(new_range) {
+                changes.push((ChangeTag::Equal, Some(old_lines[o]), Some(new_lines[n])));
+            }
+            continue;
+        }
+        for o in old_range {
+            changes.push((ChangeTag::Delete, Some(old_lines[o]), None));
+        }
+        for n in new_range {
+            changes.push((ChangeTag::Insert, None, Some(new_lines[n])));
+        }
+    }
+    changes
 }
 
-/// Filter diff lines to only show changed lines and surrounding context
-fn filter_to_chunks(lines: &[DiffLineRaw]) -> Vec<DiffLineRaw> {
+/// Filter diff lines to only show changed lines and surrounding context.
+/// Chunks whose changes start at an `expanded` new-file line keep every
+/// line up to the changes before and after them.
+fn filter_to_chunks(lines: &[DiffLineRaw], expanded: &BTreeSet<usize>) -> Vec<DiffLineRaw> {
     if lines.is_empty() {
         return Vec::new();
     }
@@ -470,88 +3943,162 @@ fn filter_to_chunks(lines: &[DiffLineRaw]) -> Vec<DiffLineRaw> {
         }
     }
 
+    if !expanded.is_empty() {
+        let mut new_lines_before = 0;
+        for (i, line) in lines.iter().enumerate() {
+            let starts_run = line.change_type != ChangeTag::Equal
+                && (i == 0 || lines[i - 1].change_type == ChangeTag::Equal);
+            if starts_run && expanded.contains(&(new_lines_before + 1)) {
+                // From the change before this chunk to the one after it
+                let at = changed_indices.partition_point(|&c| c < i);
+                let from = at.checked_sub(1).map_or(0, |p| changed_indices[p] + 1);
+                let mut last = at;
+                while let Some(&next) = changed_indices.get(last + 1)
+                    && next - changed_indices[last] <= 2 * CONTEXT_LINES + 1
+                {
+                    last += 1;
+                }
+                let to = changed_indices
+                    .get(last + 1)
+                    .copied()
+                    .unwrap_or(lines.len());
+                include_line_chunks.extend(from..to);
+            }
+            if line.new_line_num.is_some() {
+                new_lines_before += 1;
+            }
+        }
+    }
+
     // Build result with chunk separators
     let mut result = Vec::new();
     let mut in_chunk = false;
     let mut last_included_idx: Option<usize> = None;
+    let mut last_header_idx: Option<usize> = None;
 
     for (i, line) in lines.iter().enumerate() {
-        if include_line_chunks.get(&i).is_some() {
+        if include_line_chunks.contains(&i) {
             if let Some(last) = last_included_idx {
                 // check if gap between lines, as if there is a gap then its the new chunk
                 if i > last + 1 && in_chunk {
                     result.push(DiffLineRaw {
                         old_line_num: None,
                         new_line_num: None,
+                        byte_offset: None,
                         content: "─────────────────────────────────────".to_string(),
                         change_type: ChangeTag::Equal,
+                        kind: LineKind::Separator,
                     });
                 }
             }
-            result.push(DiffLineRaw {
-                old_line_num: line.old_line_num,
-                new_line_num: line.new_line_num,
-                content: line.content.clone(),
-                change_type: line.change_type,
-            });
+
+            // A chunk starting mid-cell still needs to say which cell it's in
+            let chunk_start = last_included_idx.is_none_or(|last| i > last + 1);
+            if chunk_start
+                && line.kind != LineKind::CellHeader
+                && let Some(header) = last_header_idx.map(|h| &lines[h])
+            {
+                result.push(header.clone());
+            }
+
+            result.push(line.clone());
             last_included_idx = Some(i);
             in_chunk = true;
         }
+
+        if line.kind == LineKind::CellHeader {
+            last_header_idx = Some(i);
+        }
     }
 
     result
 }
 
-fn split_for_side_by_side(diff_lines: &[DiffLineRaw]) -> (Vec<DiffLineRaw>, Vec<DiffLineRaw>) {
-    let mut old_lines = Vec::new();
-    let mut new_lines = Vec::new();
-
-    for line in diff_lines {
-        match line.change_type {
-            ChangeTag::Equal => {
-                old_lines.push(DiffLineRaw {
-                    old_line_num: line.old_line_num,
-                    new_line_num: line.new_line_num,
-                    content: line.content.clone(),
-                    change_type: line.change_type,
-                });
-                new_lines.push(DiffLineRaw {
-                    old_line_num: line.old_line_num,
-                    new_line_num: line.new_line_num,
-                    content: line.content.clone(),
-                    change_type: line.change_type,
-                });
-            }
+/// Paired lines share a row as a modification. Other deleted lines only
+/// appear on the left and inserted lines only on the right, with the other
+/// side of those rows left empty.
+fn side_by_side_rows(diff_lines: &[DiffLineRaw], pairs: &[(usize, usize)]) -> Vec<SideBySideRow> {
+    let row = |old, new| SideBySideRow { old, new };
+    let mut rows = Vec::with_capacity(diff_lines.len());
+    let mut pairs = pairs.iter().peekable();
+    let mut i = 0;
+    while i < diff_lines.len() {
+        match diff_lines[i].change_type {
+            ChangeTag::Equal => rows.push(row(Some(i), Some(i))),
+            ChangeTag::Insert => rows.push(row(None, Some(i))),
             ChangeTag::Delete => {
-                old_lines.push(DiffLineRaw {
-                    old_line_num: line.old_line_num,
-                    new_line_num: line.new_line_num,
-                    content: line.content.clone(),
-                    change_type: line.change_type,
-                });
-                new_lines.push(DiffLineRaw {
-                    old_line_num: None,
-                    new_line_num: None,
-                    content: String::new(),
-                    change_type: ChangeTag::Equal,
-                });
-            }
-            ChangeTag::Insert => {
-                old_lines.push(DiffLineRaw {
-                    old_line_num: None,
-                    new_line_num: None,
-                    content: String::new(),
-                    change_type: ChangeTag::Equal,
-                });
-                new_lines.push(DiffLineRaw {
-                    old_line_num: line.old_line_num,
-                    new_line_num: line.new_line_num,
-                    content: line.content.clone(),
-                    change_type: line.change_type,
-                });
+                let is = |tag| move |l: &&DiffLineRaw| l.change_type == tag;
+                let inserts = i + diff_lines[i..]
+                    .iter()
+                    .take_while(is(ChangeTag::Delete))
+                    .count();
+                let end = inserts
+                    + diff_lines[inserts..]
+                        .iter()
+                        .take_while(is(ChangeTag::Insert))
+                        .count();
+                // Lines before each pair go on their own rows, keeping
+                // both sides in order
+                let (mut old, mut new) = (i, inserts);
+                while let Some(&(d, n)) = pairs.next_if(|&&(d, _)| d < inserts) {
+                    rows.extend((old..d).map(|l| row(Some(l), None)));
+                    rows.extend((new..n).map(|l| row(None, Some(l))));
+                    rows.push(row(Some(d), Some(n)));
+                    (old, new) = (d + 1, n + 1);
+                }
+                rows.extend((old..inserts).map(|l| row(Some(l), None)));
+                rows.extend((new..end).map(|l| row(None, Some(l))));
+                i = end;
+                continue;
             }
         }
+        i += 1;
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui_tests::numbered;
+
+    #[test]
+    fn expands_one_hunk_to_its_neighbours() {
+        let (old, new) = (numbered(&[]), numbered(&[3, 17]));
+        let separators = |lines: &[DiffLineRaw]| {
+            lines
+                .iter()
+                .filter(|l| l.kind == LineKind::Separator)
+                .count()
+        };
+
+        let collapsed = compute_diff(&old, &new, false, &BTreeSet::new(), false);
+        assert_eq!(separators(&collapsed), 1);
+        let hunks = diff_hunks(&collapsed, "a.txt", &old, &new);
+        assert_eq!(change_starts(&hunks[1].hunk), [17]);
+
+        let expanded = compute_diff(&old, &new, false, &BTreeSet::from([17]), false);
+        assert_eq!(separators(&expanded), 0);
+        // Every line of both files, with the two changes as -/+ pairs
+        assert_eq!(expanded.len(), 32);
+        let hunks = diff_hunks(&expanded, "a.txt", &old, &new);
+        assert_eq!(change_starts(&hunks[0].hunk), [3, 17]);
     }
 
-    (old_lines, new_lines)
+    #[test]
+    fn reads_modelines() {
+        let cases = [
+            ("# vim: set ft=python:\nx = 1\n", Some("python")),
+            ("x\n// vim: syntax=rust\n", Some("rust")),
+            ("#!/bin/sh\n# -*- mode: ruby -*-\n", Some("ruby")),
+            ("# -*- yaml -*-\n", Some("yaml")),
+            ("# -*- coding: utf-8 -*-\n", None),
+            // Not a modeline: "vim:" inside a word
+            ("novim: ft=c\n", None),
+            ("plain text\n", None),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(modeline_syntax(content), expected, "{:?}", content);
+        }
+    }
 }
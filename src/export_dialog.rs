@@ -0,0 +1,67 @@
+use eframe::egui::{self, RichText};
+
+use crate::i18n::{tr, trf};
+use crate::jj::PatchFormat;
+use crate::theme;
+
+/// Asks how to write a patch before picking where to save it
+pub struct ExportDialog {
+    /// Files to export; empty exports the whole diff
+    pub paths: Vec<String>,
+    pub format: PatchFormat,
+}
+
+impl ExportDialog {
+    pub fn new(paths: Vec<String>, format: PatchFormat) -> Self {
+        Self { paths, format }
+    }
+
+    /// Returns Some(true) when confirmed, Some(false) when cancelled
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<bool> {
+        let mut open = true;
+        let mut choice = None;
+
+        egui::Window::new(tr("Export patch"))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(match self.paths.len() {
+                    0 => tr("Every changed file").to_string(),
+                    n => trf("{} files", &[&n]),
+                });
+                ui.horizontal(|ui| {
+                    ui.label(tr("Context lines"));
+                    ui.add(egui::DragValue::new(&mut self.format.context).range(0..=1000));
+                });
+                ui.checkbox(&mut self.format.git_headers, tr("Git headers"))
+                    .on_hover_text(tr(
+                        "diff --git lines with file modes and renames, for git apply and git am",
+                    ));
+                if !self.format.git_headers {
+                    ui.label(
+                        RichText::new(tr(
+                            "Plain unified diff for patch and similar tools; renames and mode changes are left out",
+                        ))
+                        .small()
+                        .color(theme::dim_text(ui.visuals())),
+                    );
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(tr("Save...")).clicked() {
+                        choice = Some(true);
+                    }
+                    if ui.button(tr("Cancel")).clicked() {
+                        choice = Some(false);
+                    }
+                });
+            });
+
+        if !open {
+            choice = Some(false);
+        }
+        choice
+    }
+}
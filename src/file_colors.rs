@@ -0,0 +1,101 @@
+use eframe::egui::Color32;
+use std::collections::HashMap;
+use tracing::warn;
+
+/// Dot colors by extension or whole file name, roughly each language's
+/// own color so mixed changes are quick to scan
+const BUILTIN: &[(&str, (u8, u8, u8))] = &[
+    ("rs", (222, 120, 60)),
+    ("toml", (156, 66, 33)),
+    ("ts", (49, 120, 198)),
+    ("tsx", (49, 120, 198)),
+    ("js", (241, 224, 90)),
+    ("jsx", (241, 224, 90)),
+    ("mjs", (241, 224, 90)),
+    ("py", (53, 114, 165)),
+    ("go", (0, 173, 216)),
+    ("java", (176, 114, 25)),
+    ("kt", (169, 123, 255)),
+    ("c", (110, 140, 190)),
+    ("h", (110, 140, 190)),
+    ("cpp", (243, 75, 125)),
+    ("hpp", (243, 75, 125)),
+    ("cs", (23, 134, 0)),
+    ("rb", (204, 52, 45)),
+    ("php", (79, 93, 149)),
+    ("swift", (240, 81, 56)),
+    ("sh", (137, 224, 81)),
+    ("nix", (126, 126, 255)),
+    ("lua", (80, 80, 210)),
+    ("html", (227, 76, 38)),
+    ("css", (86, 61, 124)),
+    ("scss", (198, 83, 140)),
+    ("svelte", (255, 62, 0)),
+    ("vue", (65, 184, 131)),
+    ("md", (8, 63, 161)),
+    ("json", (203, 170, 60)),
+    ("yaml", (203, 23, 30)),
+    ("yml", (203, 23, 30)),
+    ("sql", (227, 140, 0)),
+    ("ipynb", (218, 91, 11)),
+    ("lock", (120, 120, 130)),
+    ("Dockerfile", (36, 150, 237)),
+    ("Makefile", (66, 120, 25)),
+];
+
+/// Sidebar dot colors: the built-in map plus `[file_colors]` from
+/// `config.toml`, e.g. `rs = "#dea584"` or `Justfile = "#384d54"`
+#[derive(Clone, Debug)]
+pub struct FileColors {
+    /// Keyed by extension, or by file name for files like `Makefile`
+    colors: HashMap<String, Color32>,
+}
+
+impl Default for FileColors {
+    fn default() -> Self {
+        Self::new(&HashMap::new())
+    }
+}
+
+impl FileColors {
+    /// Invalid configured colors are skipped with a warning
+    pub fn new(overrides: &HashMap<String, String>) -> Self {
+        let mut colors: HashMap<String, Color32> = BUILTIN
+            .iter()
+            .map(|&(key, (r, g, b))| (key.to_string(), Color32::from_rgb(r, g, b)))
+            .collect();
+        for (key, value) in overrides {
+            match parse_hex(value) {
+                Some(color) => {
+                    colors.insert(key.trim_start_matches('.').to_string(), color);
+                }
+                None => warn!(
+                    "Ignoring file color {} = {:?}, expected #rrggbb",
+                    key, value
+                ),
+            }
+        }
+        Self { colors }
+    }
+
+    /// The file name wins over its extension
+    pub fn color(&self, path: &str) -> Option<Color32> {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        self.colors.get(name).copied().or_else(|| {
+            let (stem, ext) = name.rsplit_once('.')?;
+            (!stem.is_empty())
+                .then(|| self.colors.get(ext).copied())
+                .flatten()
+        })
+    }
+}
+
+/// `#rrggbb`, with or without the `#`
+fn parse_hex(value: &str) -> Option<Color32> {
+    let hex = value.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(Color32::from_rgb(channel(0)?, channel(2)?, channel(4)?))
+}
@@ -0,0 +1,76 @@
+use eframe::egui;
+
+/// The part of the window keyboard actions go to, moved with Tab and
+/// Shift+Tab or by clicking into a pane
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Focus {
+    Sidebar,
+    #[default]
+    Diff,
+    /// The diff's find bar
+    Search,
+    Log,
+}
+
+impl Focus {
+    /// Tab order, matching the layout from left to bottom
+    const ALL: [Focus; 4] = [Focus::Sidebar, Focus::Diff, Focus::Search, Focus::Log];
+
+    /// The next area in Tab order, or the previous one for Shift+Tab,
+    /// skipping the ones that aren't `available`
+    pub fn cycle(self, backwards: bool, available: impl Fn(Focus) -> bool) -> Focus {
+        let len = Self::ALL.len();
+        let at = Self::ALL.iter().position(|&f| f == self).unwrap_or(0);
+        (1..len)
+            .map(|step| match backwards {
+                false => Self::ALL[(at + step) % len],
+                true => Self::ALL[(at + len - step) % len],
+            })
+            .find(|&f| available(f))
+            .unwrap_or(self)
+    }
+
+    /// The pane this lies in, which the find bar shares with the diff
+    pub fn pane(self) -> Focus {
+        match self {
+            Focus::Search => Focus::Diff,
+            other => other,
+        }
+    }
+}
+
+/// Takes Tab presses out of `raw_input` before egui moves widget focus with
+/// them. Returns whether the last one held Shift, if there was any.
+pub fn take_tab(raw_input: &mut egui::RawInput) -> Option<bool> {
+    let mut backwards = None;
+    raw_input.events.retain(|event| match event {
+        egui::Event::Key {
+            key: egui::Key::Tab,
+            pressed,
+            modifiers,
+            ..
+        } => {
+            if *pressed {
+                backwards = Some(modifiers.shift);
+            }
+            false
+        }
+        _ => true,
+    });
+    backwards
+}
+
+/// Outline around the focused pane, painted over the panels' contents
+pub fn ring(ctx: &egui::Context, rect: egui::Rect) {
+    ctx.layer_painter(egui::LayerId::background()).rect_stroke(
+        rect.shrink(1.0),
+        4.0,
+        ctx.style().visuals.selection.stroke,
+        egui::StrokeKind::Inside,
+    );
+}
+
+/// Whether this frame's press landed in `rect`, to move focus there
+pub fn clicked_in(ui: &egui::Ui, rect: egui::Rect) -> bool {
+    ui.input(|i| i.pointer.primary_pressed()) && ui.rect_contains_pointer(rect)
+}
@@ -0,0 +1,287 @@
+use eframe::egui;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{Receiver, channel};
+use std::thread;
+
+use crate::i18n::{tr, trf};
+use crate::jj;
+use crate::theme;
+
+/// A `git` command running in the selected workspace
+fn command() -> Command {
+    let mut command = Command::new("git");
+    if let Some(root) = jj::workspace_root() {
+        command.current_dir(root);
+    }
+    command
+}
+
+/// Whether the workspace has a `.git` next to its `.jj`, so git sees the
+/// same working copy
+pub fn is_colocated() -> bool {
+    jj::workspace_path(".jj").is_dir() && jj::workspace_path(".git").exists()
+}
+
+/// Files jj and git disagree about in a colocated repo
+#[derive(Default)]
+pub struct Mismatches {
+    /// Recorded by jj though `.gitignore` matches them, e.g. tracked before
+    /// the pattern was added
+    pub tracked_but_ignored: Vec<String>,
+    /// Not ignored by git but missing from jj's last snapshot, e.g. over
+    /// `snapshot.max-new-file-size`
+    pub untracked: Vec<String>,
+}
+
+impl Mismatches {
+    pub fn len(&self) -> usize {
+        self.tracked_but_ignored.len() + self.untracked.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn check() -> Self {
+        let recorded = jj::recorded_files();
+        let tracked_but_ignored = git_ignored(&recorded);
+        let recorded: HashSet<String> = recorded.into_iter().collect();
+        let untracked = git_lines(&["ls-files", "--others", "--exclude-standard"])
+            .into_iter()
+            .filter(|path| !recorded.contains(path))
+            .collect();
+        Self {
+            tracked_but_ignored,
+            untracked,
+        }
+    }
+}
+
+/// Whether a workspace is colocated, with the mismatch check running in
+/// the background
+pub struct Colocation {
+    root: Option<PathBuf>,
+    colocated: bool,
+    receiver: Option<Receiver<Mismatches>>,
+    mismatches: Mismatches,
+}
+
+impl Colocation {
+    /// Looks at the current workspace and starts checking it if colocated
+    pub fn detect(ctx: &egui::Context) -> Self {
+        let mut colocation = Self {
+            root: jj::workspace_root(),
+            colocated: is_colocated(),
+            receiver: None,
+            mismatches: Mismatches::default(),
+        };
+        colocation.recheck(ctx);
+        colocation
+    }
+
+    /// Workspace this was detected for
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    fn recheck(&mut self, ctx: &egui::Context) {
+        if !self.colocated {
+            return;
+        }
+        let (sender, receiver) = channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(Mismatches::check());
+            ctx.request_repaint();
+        });
+        self.receiver = Some(receiver);
+    }
+
+    /// Status bar entry naming the git backend, with a menu of mismatched
+    /// files. Returns a tracked file that was clicked.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<String> {
+        if !self.colocated {
+            return None;
+        }
+        if let Some(receiver) = &self.receiver
+            && let Ok(mismatches) = receiver.try_recv()
+        {
+            self.mismatches = mismatches;
+            self.receiver = None;
+        }
+
+        let dim = theme::dim_text(ui.visuals());
+        let label = match self.mismatches.len() {
+            0 => egui::RichText::new("git + jj").color(dim),
+            n => egui::RichText::new(format!("git + jj ⚠ {}", n))
+                .color(egui::Color32::from_rgb(220, 180, 80)),
+        };
+        let mut open = None;
+        let mut recheck = false;
+        ui.menu_button(label, |ui| {
+            ui.set_max_width(480.0);
+            if self.receiver.is_some() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(tr("Comparing jj's files with git's ignore rules..."));
+                });
+            } else if self.mismatches.is_empty() {
+                ui.label(tr("jj and git agree on which files are tracked"));
+            }
+            let tracked = &self.mismatches.tracked_but_ignored;
+            if !tracked.is_empty() {
+                ui.label(tr("Tracked by jj but ignored by git:"));
+                for path in tracked.iter().take(MENU_PATHS) {
+                    if ui
+                        .link(egui::RichText::new(path).monospace())
+                        .on_hover_text(tr("Open in the diff view"))
+                        .clicked()
+                    {
+                        open = Some(path.clone());
+                        ui.close();
+                    }
+                }
+                more_label(ui, tracked.len());
+                ui.separator();
+            }
+            let untracked = &self.mismatches.untracked;
+            if !untracked.is_empty() {
+                ui.label(tr("Seen by git but not in jj's last snapshot:"));
+                for path in untracked.iter().take(MENU_PATHS) {
+                    ui.label(egui::RichText::new(path).monospace());
+                }
+                more_label(ui, untracked.len());
+                ui.separator();
+            }
+            if ui.button(tr("Check again")).clicked() {
+                recheck = true;
+            }
+        })
+        .response
+        .on_hover_text(tr(
+            "Colocated with git: both share this working copy, and jj imports git's changes on every command",
+        ));
+        if recheck {
+            self.recheck(ui.ctx());
+        }
+        open
+    }
+}
+
+/// Paths listed per kind of mismatch before the rest are counted
+const MENU_PATHS: usize = 30;
+
+fn more_label(ui: &mut egui::Ui, total: usize) {
+    if total > MENU_PATHS {
+        ui.weak(trf("and {} more", &[&(total - MENU_PATHS)]));
+    }
+}
+
+/// Which of `paths` git's ignore rules match
+fn git_ignored(paths: &[String]) -> Vec<String> {
+    check_ignore(
+        command().args(["check-ignore", "--no-index", "--stdin"]),
+        paths,
+    )
+}
+
+/// Which of `paths` in the git working tree at `root` are ignored and not
+/// tracked
+pub fn untracked_ignored(root: &Path, paths: &[String]) -> Vec<String> {
+    let mut command = Command::new("git");
+    command.current_dir(root).args(["check-ignore", "--stdin"]);
+    check_ignore(&mut command, paths)
+}
+
+/// Runs a `git check-ignore --stdin` command over `paths`
+fn check_ignore(command: &mut Command, paths: &[String]) -> Vec<String> {
+    let child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else {
+        return Vec::new();
+    };
+    // Written from another thread so a full stdout pipe can't stall us
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = paths.join("\n");
+        thread::spawn(move || stdin.write_all(input.as_bytes()));
+    }
+    // Exits with 1 when nothing is ignored, which is no error here
+    match child.wait_with_output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn git_lines(args: &[&str]) -> Vec<String> {
+    match command().args(args).output() {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Clone URL of `origin`, or else of the first remote. Colocated repos ask
+/// git; others ask jj, which keeps its remotes in its own git store.
+pub fn remote_url() -> Option<String> {
+    if is_colocated() {
+        let url = git_lines(&["remote", "get-url", "origin"]).pop();
+        return url.or_else(|| {
+            let first = git_lines(&["remote"]).into_iter().next()?;
+            git_lines(&["remote", "get-url", &first]).pop()
+        });
+    }
+
+    let output = jj::output_with_retry(jj::command().args(["git", "remote", "list"])).ok()?;
+    let listed = String::from_utf8_lossy(&output.stdout);
+    // Lines are "<name> <url>"
+    let remotes: Vec<(&str, &str)> = listed
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .collect();
+    remotes
+        .iter()
+        .find(|(name, _)| *name == "origin")
+        .or(remotes.first())
+        .map(|(_, url)| url.trim().to_string())
+}
+
+/// Web page showing `path` at `commit` on the forge hosting `remote`, from
+/// an https, ssh or `user@host:path` clone URL
+pub fn forge_url(remote: &str, commit: &str, path: &str) -> Option<String> {
+    let remote = remote.trim().trim_end_matches('/');
+    let remote = remote.strip_suffix(".git").unwrap_or(remote);
+    let (host, repo) = match ["https://", "http://", "ssh://"]
+        .iter()
+        .find_map(|scheme| remote.strip_prefix(scheme))
+    {
+        Some(rest) => rest.split_once('/')?,
+        None => remote.split_once(':')?,
+    };
+    // Drop `git@` and any port
+    let host = host.rsplit('@').next()?;
+    let host = host.split(':').next()?;
+    let base = format!("https://{}/{}", host, repo.trim_start_matches('/'));
+
+    // GitHub's layout is shared by the Gitea family, like Forgejo and Codeberg
+    let (blob, item) = if host.contains("gitlab") {
+        ("-/blob", "")
+    } else if host.contains("bitbucket") {
+        ("src", "")
+    } else if host == "git.sr.ht" {
+        ("tree", "item/")
+    } else {
+        ("blob", "")
+    };
+    Some(format!("{}/{}/{}/{}{}", base, blob, commit, item, path))
+}
@@ -0,0 +1,169 @@
+use eframe::egui::{self, Color32, RichText};
+use similar::ChangeTag;
+
+use crate::changed_files::FileStatus;
+use crate::conflicts::{ConflictLine, ConflictPart};
+use crate::diff_viewer::change_tag_to_bg_color;
+use crate::i18n::tr;
+
+fn shortcuts() -> [(&'static str, &'static str); 12] {
+    [
+        ("?", tr("Show or hide this help")),
+        (
+            "Tab",
+            tr("Focus the next pane: files, diff, find bar and log; Shift+Tab goes back"),
+        ),
+        (
+            "↑ ↓",
+            tr("Step through the files, hunks or commits of the focused pane"),
+        ),
+        ("Space", tr("Mark the focused file as viewed, or unmark it")),
+        (
+            "Enter",
+            tr("In the log, diff the selected commit or the two selected"),
+        ),
+        (
+            "Ctrl-click",
+            tr("Add or remove a file or commit from the selection"),
+        ),
+        (
+            "Shift-click",
+            tr("Select a range of files, diff lines or hunks"),
+        ),
+        ("Ctrl+A", tr("Select every hunk in the file")),
+        (
+            "Ctrl+F",
+            tr("Find in the diff, with matches marked beside the scroll bar"),
+        ),
+        (
+            "Right-click",
+            tr("File and line actions, like copying or reverting"),
+        ),
+        ("Ctrl+S", tr("Save while editing a file in the diff view")),
+        ("F12", tr("Show how long loading and drawing the diff took")),
+    ]
+}
+
+/// View options and the tooltip explaining each, as in the diff header
+fn view_modes() -> [(&'static str, &'static str); 7] {
+    [
+        (tr("Side-by-side"), tr("Old and new version in two columns")),
+        (
+            tr("Inline"),
+            tr("Removed lines above the added lines that replace them"),
+        ),
+        ("⇄", tr("Swap sides, showing the diff from new to old")),
+        (
+            tr("Semantic"),
+            tr("Compare parsed keys, ignoring ordering and formatting"),
+        ),
+        (
+            tr("Structural"),
+            tr("Match syntax tokens instead of lines, so reformatting isn't a change"),
+        ),
+        (tr("Table"), tr("Align columns and highlight changed cells")),
+        (
+            tr("Plain text"),
+            tr("Show this file without syntax highlighting"),
+        ),
+    ]
+}
+
+/// Legend for status symbols and colors, plus shortcuts and view modes;
+/// toggled with `?`
+pub fn show(ctx: &egui::Context, open: &mut bool) {
+    egui::Window::new(tr("Help"))
+        .open(open)
+        .collapsible(false)
+        .default_width(560.0)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.heading(tr("File status"));
+                egui::Grid::new("help_status").show(ui, |ui| {
+                    for (status, meaning) in [
+                        (FileStatus::Added, tr("Added")),
+                        (FileStatus::Modified, tr("Modified")),
+                        (FileStatus::Deleted, tr("Deleted")),
+                        (FileStatus::Renamed, tr("Renamed")),
+                    ] {
+                        ui.label(
+                            RichText::new(status.symbol())
+                                .monospace()
+                                .strong()
+                                .color(status.color()),
+                        );
+                        ui.label(meaning);
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+
+                ui.heading(tr("Diff colors"));
+                let side = |side| ConflictLine {
+                    part: ConflictPart::Side { side, sides: 2 },
+                    depth: 0,
+                };
+                let base = ConflictLine {
+                    part: ConflictPart::Base,
+                    depth: 0,
+                };
+                egui::Grid::new("help_colors").show(ui, |ui| {
+                    for (color, meaning) in [
+                        (change_tag_to_bg_color(ChangeTag::Insert), tr("Added line")),
+                        (
+                            change_tag_to_bg_color(ChangeTag::Delete),
+                            tr("Removed line"),
+                        ),
+                        (side(1).color(), tr("Conflict, first side")),
+                        (side(2).color(), tr("Conflict, second side")),
+                        (base.color(), tr("Conflict base")),
+                    ] {
+                        swatch(ui, color);
+                        ui.label(meaning);
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+
+                ui.heading(tr("Keyboard and mouse"));
+                egui::Grid::new("help_shortcuts").show(ui, |ui| {
+                    for (keys, meaning) in shortcuts() {
+                        ui.label(RichText::new(keys).monospace().strong());
+                        ui.label(meaning);
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+
+                ui.heading(tr("View modes"));
+                egui::Grid::new("help_views").show(ui, |ui| {
+                    for (name, meaning) in view_modes() {
+                        ui.label(RichText::new(name).strong());
+                        ui.label(meaning);
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+}
+
+/// Whether `?` was typed outside a text field
+pub fn toggle_requested(ctx: &egui::Context) -> bool {
+    !ctx.wants_keyboard_input()
+        && ctx.input(|i| {
+            i.events
+                .iter()
+                .any(|e| matches!(e, egui::Event::Text(text) if text == "?"))
+        })
+}
+
+fn swatch(ui: &mut egui::Ui, color: Color32) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(48.0, 16.0), egui::Sense::hover());
+    ui.painter().rect_filled(rect, 2.0, color);
+    ui.painter().rect_stroke(
+        rect,
+        2.0,
+        ui.visuals().widgets.noninteractive.bg_stroke,
+        egui::StrokeKind::Inside,
+    );
+}
@@ -0,0 +1,118 @@
+/// Longest context shown after a hunk's line ranges, as git cuts it too
+const MAX_CHARS: usize = 80;
+
+const RUST_QUALIFIERS: &[&str] = &[
+    "pub",
+    "pub(crate)",
+    "pub(super)",
+    "pub(self)",
+    "async",
+    "unsafe",
+    "const",
+    "default",
+];
+const RUST_KEYWORDS: &[&str] = &[
+    "fn",
+    "impl",
+    "trait",
+    "struct",
+    "enum",
+    "union",
+    "mod",
+    "macro_rules!",
+];
+const JS_QUALIFIERS: &[&str] = &["export", "default", "async", "abstract", "declare"];
+const JS_KEYWORDS: &[&str] = &["function", "function*", "class", "interface"];
+
+/// The nearest line above 1-based `line` that opens a function, type or
+/// section, for the hunk header like git's `@@ ... fn foo()`. Known
+/// languages go by their keywords, the rest by git's default rule.
+pub fn enclosing(path: &str, lines: &[&str], line: usize) -> Option<String> {
+    let extension = path.rsplit_once('.').map_or("", |(_, ext)| ext);
+    let above = line.saturating_sub(1).min(lines.len());
+    let found = lines[..above]
+        .iter()
+        .rev()
+        .find(|l| is_section(extension, l))?;
+    let found = found.trim();
+    Some(match found.char_indices().nth(MAX_CHARS) {
+        Some((end, _)) => format!("{}…", &found[..end]),
+        None => found.to_string(),
+    })
+}
+
+fn is_section(extension: &str, line: &str) -> bool {
+    let trimmed = line.trim_start();
+    match extension {
+        "rs" => starts_with_keyword(strip_qualifiers(trimmed, RUST_QUALIFIERS), RUST_KEYWORDS),
+        "py" | "pyi" => {
+            starts_with_keyword(strip_qualifiers(trimmed, &["async"]), &["def", "class"])
+        }
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => {
+            starts_with_keyword(strip_qualifiers(trimmed, JS_QUALIFIERS), JS_KEYWORDS)
+        }
+        "go" => starts_with_keyword(line, &["func", "type"]),
+        "md" | "markdown" => line.starts_with('#'),
+        "toml" | "ini" | "cfg" => trimmed.starts_with('['),
+        // git's default: a letter, `_` or `$` in the first column
+        _ => line.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '$'),
+    }
+}
+
+/// `line` past any leading words from `qualifiers`, e.g. `pub async `
+fn strip_qualifiers<'a>(mut line: &'a str, qualifiers: &[&str]) -> &'a str {
+    while let Some(rest) = qualifiers.iter().find_map(|q| {
+        line.strip_prefix(q)
+            .filter(|rest| rest.starts_with(' '))
+            .map(str::trim_start)
+    }) {
+        line = rest;
+    }
+    line
+}
+
+fn starts_with_keyword(line: &str, keywords: &[&str]) -> bool {
+    keywords.iter().any(|keyword| {
+        line.strip_prefix(keyword)
+            .is_some_and(|rest| rest.starts_with([' ', '<', '(']))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_enclosing_section() {
+        let cases = [
+            (
+                "a.rs",
+                "pub(crate) async fn load() {\n    x\n}\n",
+                Some("pub(crate) async fn load() {"),
+            ),
+            (
+                "a.rs",
+                "impl<T> Foo<T> {\n    fn a() {}\n    let x;\n",
+                Some("fn a() {}"),
+            ),
+            ("a.rs", "// fn commented_out()\n    x\n", None),
+            (
+                "a.py",
+                "class A:\n    async def run(self):\n        x\n",
+                Some("async def run(self):"),
+            ),
+            (
+                "a.ts",
+                "export default async function main() {\n  x\n",
+                Some("export default async function main() {"),
+            ),
+            ("a.md", "# Title\ntext\n", Some("# Title")),
+            ("a.txt", "Section\n  indented\n", Some("Section")),
+        ];
+        for (path, content, expected) in cases {
+            let lines: Vec<&str> = content.lines().collect();
+            let found = enclosing(path, &lines, lines.len() + 1);
+            assert_eq!(found.as_deref(), expected, "{} {:?}", path, content);
+        }
+    }
+}
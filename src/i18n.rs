@@ -0,0 +1,768 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::{LazyLock, OnceLock};
+
+/// UI language. Strings are keyed by their English text, so English needs
+/// no bundle and an untranslated string falls back to it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Locale {
+    English,
+    German,
+}
+
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+impl Locale {
+    /// Accepts `de`, `de-AT`, `de_DE.UTF-8` and the like
+    pub fn from_code(code: &str) -> Option<Self> {
+        let language = code
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or(code)
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "en" | "c" | "posix" => Some(Locale::English),
+            "de" => Some(Locale::German),
+            _ => None,
+        }
+    }
+
+    /// From the POSIX locale variables, in the order libc checks them
+    pub fn detect() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::from_code(&value))
+            .unwrap_or(Locale::English)
+    }
+}
+
+/// Pick the locale once at startup; `setting` is the config's `language`
+pub fn init(setting: Option<&str>) {
+    let locale = setting
+        .filter(|s| *s != "auto")
+        .and_then(|s| {
+            let locale = Locale::from_code(s);
+            if locale.is_none() {
+                tracing::warn!("Unknown language {}, detecting from the environment", s);
+            }
+            locale
+        })
+        .unwrap_or_else(Locale::detect);
+    let _ = LOCALE.set(locale);
+}
+
+/// The UI text for `english` in the current locale
+pub fn tr(english: &'static str) -> &'static str {
+    match LOCALE.get() {
+        Some(Locale::German) => GERMAN.get(english).copied().unwrap_or(english),
+        _ => english,
+    }
+}
+
+/// `tr` with each `{}` replaced by the next argument
+pub fn trf(english: &'static str, args: &[&dyn Display]) -> String {
+    let mut parts = tr(english).split("{}");
+    let mut out = parts.next().unwrap_or_default().to_string();
+    for (part, arg) in parts.zip(args.iter().map(Some).chain(std::iter::repeat(None))) {
+        if let Some(arg) = arg {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+static GERMAN: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(|| {
+    HashMap::from([
+        // Title bar
+        ("Squash into parent", "In Elternänderung zusammenführen"),
+        ("Split change...", "Änderung aufteilen..."),
+        (
+            "Move some files of @ into a new change before it",
+            "Einige Dateien von @ in eine neue Änderung davor verschieben",
+        ),
+        ("Paste patch", "Patch einfügen"),
+        (
+            "Preview and apply a unified diff from the clipboard",
+            "Unified Diff aus der Zwischenablage ansehen und anwenden",
+        ),
+        ("Danger zone", "Gefahrenzone"),
+        ("Abandon current change", "Aktuelle Änderung verwerfen"),
+        ("Undo last operation", "Letzte Operation rückgängig machen"),
+        ("UI scale", "UI-Skalierung"),
+        (
+            "Relative to the display's own scale factor",
+            "Relativ zum Skalierungsfaktor des Bildschirms",
+        ),
+        ("Theme", "Farbschema"),
+        ("System", "System"),
+        ("Dark", "Dunkel"),
+        ("Light", "Hell"),
+        ("Fetch", "Abrufen"),
+        ("Push", "Hochladen"),
+        (
+            "jj git push (tracked bookmarks)",
+            "jj git push (verfolgte Bookmarks)",
+        ),
+        ("Split", "Teilen"),
+        (
+            "View two files at once; click a pane to bind the sidebar",
+            "Zwei Dateien gleichzeitig ansehen; Bereich anklicken, um die Seitenleiste zu binden",
+        ),
+        ("Info", "Info"),
+        ("Files", "Dateien"),
+        (
+            "Show or hide the changed files",
+            "Geänderte Dateien ein- oder ausblenden",
+        ),
+        (
+            "Show details of the revision being diffed",
+            "Details der verglichenen Revision anzeigen",
+        ),
+        (
+            "Edit the description of the working-copy change",
+            "Beschreibung der Arbeitskopie-Änderung bearbeiten",
+        ),
+        ("Only list files in {}/", "Nur Dateien in {}/ auflisten"),
+        (
+            "List files in every directory",
+            "Dateien in allen Verzeichnissen auflisten",
+        ),
+        ("Unsnapshotted edits", "Nicht erfasste Änderungen"),
+        (
+            "Compare the files on disk with what jj last snapshotted, without taking a new snapshot",
+            "Dateien auf der Festplatte mit dem letzten Snapshot von jj vergleichen, ohne einen neuen anzulegen",
+        ),
+        // Sidebar
+        ("last snapshot → disk", "letzter Snapshot → Festplatte"),
+        ("Changed Files", "Geänderte Dateien"),
+        ("Refresh file list", "Dateiliste aktualisieren"),
+        (
+            "Show dotfiles and files in dot-directories",
+            "Dotfiles und Dateien in Punkt-Verzeichnissen anzeigen",
+        ),
+        ("{} dotfiles hidden", "{} Dotfiles ausgeblendet"),
+        ("Back to working copy", "Zurück zur Arbeitskopie"),
+        ("Overview", "Übersicht"),
+        (
+            "{} hidden by exclude patterns",
+            "{} durch Ausschlussmuster ausgeblendet",
+        ),
+        ("Hide all {} files", "Alle {}-Dateien ausblenden"),
+        ("Hide this file", "Diese Datei ausblenden"),
+        ("Added", "Hinzugefügt"),
+        ("Modified", "Geändert"),
+        ("Deleted", "Gelöscht"),
+        ("Renamed", "Umbenannt"),
+        ("Renamed from {}", "Umbenannt von {}"),
+        ("{} untracked", "{} nicht verfolgt"),
+        ("Track", "Verfolgen"),
+        (
+            "Comparing the clipboard with the file on disk",
+            "Die Zwischenablage wird mit der Datei auf der Festplatte verglichen",
+        ),
+        ("Filtered through `{}`", "Gefiltert durch `{}`"),
+        (
+            "Filter failed, showing the raw files: {}",
+            "Filter fehlgeschlagen, die Dateien werden ungefiltert angezeigt: {}",
+        ),
+        ("Save UI scale: {}", "UI-Skalierung speichern: {}"),
+        ("Save theme: {}", "Farbschema speichern: {}"),
+        ("Switch workspace: {}", "Workspace wechseln: {}"),
+        ("Paste patch: {}", "Patch einfügen: {}"),
+        ("old line {}", "alte Zeile {}"),
+        ("new line {}", "neue Zeile {}"),
+        ("byte offset {}, {} bytes", "Byte-Offset {}, {} Bytes"),
+        (
+            "{} trailing whitespace characters",
+            "{} Leerzeichen am Zeilenende",
+        ),
+        (
+            "These files lose their changes in the working copy:",
+            "Diese Dateien verlieren ihre Änderungen in der Arbeitskopie:",
+        ),
+        ("Full context", "Ganzer Kontext"),
+        (
+            "Show every line up to the hunks before and after this one",
+            "Alle Zeilen bis zu den Hunks davor und danach zeigen",
+        ),
+        ("Less context", "Weniger Kontext"),
+        (
+            "Back to a few lines around the changes",
+            "Zurück zu wenigen Zeilen um die Änderungen",
+        ),
+        (
+            "Couldn't read the jj version from \"{}\"; file lists and diffs may be incomplete",
+            "Die jj-Version ließ sich aus \"{}\" nicht lesen; Dateilisten und Diffs sind womöglich unvollständig",
+        ),
+        (
+            "jj {} is older than {}, the oldest release supported. Some views may stay empty until jj is updated.",
+            "jj {} ist älter als {}, die älteste unterstützte Version. Manche Ansichten bleiben womöglich leer, bis jj aktualisiert ist.",
+        ),
+        (
+            "jj {} is newer than {}, the newest release tested. If the file list looks wrong, jj's output may have changed.",
+            "jj {} ist neuer als {}, die neueste getestete Version. Falls die Dateiliste falsch aussieht, hat sich womöglich jjs Ausgabe geändert.",
+        ),
+        ("Stack review", "Stack-Review"),
+        (
+            "Check off each commit between trunk and @, oldest first",
+            "Jeden Commit zwischen trunk und @ abhaken, den ältesten zuerst",
+        ),
+        ("Close stack review", "Stack-Review schließen"),
+        (
+            "No commits between trunk and @",
+            "Keine Commits zwischen trunk und @",
+        ),
+        ("{} of {} commits reviewed", "{} von {} Commits geprüft"),
+        ("◀ Previous", "◀ Zurück"),
+        ("Next ▶", "Weiter ▶"),
+        ("Reviewed, next", "Geprüft, weiter"),
+        (
+            "Check this commit off and go to the next one",
+            "Diesen Commit abhaken und zum nächsten gehen",
+        ),
+        ("Detected: {}", "Erkannt: {}"),
+        ("Detect automatically", "Automatisch erkennen"),
+        (
+            "Grammar for highlighting, remembered for {}",
+            "Grammatik für die Hervorhebung, gemerkt für {}",
+        ),
+        ("Copy change ID", "Change-ID kopieren"),
+        ("Copy commit SHA", "Commit-SHA kopieren"),
+        ("Open in new window", "In neuem Fenster öffnen"),
+        (
+            "Pop the diff out, e.g. onto another monitor",
+            "Das Diff herauslösen, z. B. auf einen anderen Bildschirm",
+        ),
+        ("Export patch", "Patch exportieren"),
+        ("Every changed file", "Alle geänderten Dateien"),
+        ("{} files", "{} Dateien"),
+        ("Context lines", "Kontextzeilen"),
+        ("Git headers", "Git-Header"),
+        (
+            "diff --git lines with file modes and renames, for git apply and git am",
+            "diff --git-Zeilen mit Dateimodi und Umbenennungen, für git apply und git am",
+        ),
+        (
+            "Plain unified diff for patch and similar tools; renames and mode changes are left out",
+            "Einfaches Unified-Diff für patch und ähnliche Werkzeuge; Umbenennungen und Modusänderungen fehlen",
+        ),
+        ("Track {} paths", "{} Pfade verfolgen"),
+        ("Untrack", "Nicht mehr verfolgen"),
+        ("Untrack {} files", "{} Dateien nicht mehr verfolgen"),
+        (
+            "Stop recording in jj. Only works for files .gitignore matches; others are tracked again on the next snapshot.",
+            "Nicht mehr in jj aufzeichnen. Geht nur für Dateien, auf die .gitignore passt; andere werden beim nächsten Snapshot wieder verfolgt.",
+        ),
+        ("Conflicted", "Konflikt"),
+        (
+            "Restore from revision...",
+            "Aus Revision wiederherstellen...",
+        ),
+        ("Mark {} files viewed", "{} Dateien als gesehen markieren"),
+        ("{}/{} viewed", "{}/{} gesehen"),
+        ("Mark all viewed", "Alle als gesehen markieren"),
+        ("Clear all", "Alle zurücksetzen"),
+        (
+            "Clear the review state of the listed files",
+            "Review-Status der aufgelisteten Dateien zurücksetzen",
+        ),
+        (
+            "Export patch for {} files...",
+            "Patch für {} Dateien exportieren...",
+        ),
+        (
+            "Squash {} files into parent",
+            "{} Dateien in Elternänderung zusammenführen",
+        ),
+        ("Revert {} files", "{} Dateien zurücksetzen"),
+        ("{} files selected", "{} Dateien ausgewählt"),
+        (
+            "Ctrl-click to add or remove files, Shift-click for a range",
+            "Strg-Klick fügt Dateien hinzu oder entfernt sie, Umschalt-Klick wählt einen Bereich",
+        ),
+        ("No textual changes", "Keine textuellen Änderungen"),
+        ("Back to the file header", "Zurück zum Dateikopf"),
+        // Log
+        ("Log", "Verlauf"),
+        ("Refresh log", "Verlauf aktualisieren"),
+        ("Diff selected", "Auswahl vergleichen"),
+        (
+            "Ctrl-click two commits to compare them",
+            "Zwei Commits mit Strg-Klick auswählen, um sie zu vergleichen",
+        ),
+        ("Loading changes...", "Änderungen werden geladen..."),
+        ("Compare to", "Vergleichen mit"),
+        (
+            "Everything on @ that isn't on this yet",
+            "Alles auf @, was hier noch nicht enthalten ist",
+        ),
+        ("and {} more", "und {} weitere"),
+        // Dashboard
+        ("Working copy", "Arbeitskopie"),
+        ("Revision range", "Revisionsbereich"),
+        (
+            "Edits jj hasn't snapshotted",
+            "Von jj noch nicht erfasste Änderungen",
+        ),
+        ("Description", "Beschreibung"),
+        ("Describe", "Beschreiben"),
+        ("New change", "Neue Änderung"),
+        ("{} conflicted files", "{} Dateien mit Konflikten"),
+        ("{} files changed", "{} Dateien geändert"),
+        // Diff viewer
+        ("Lines {}", "Zeilen {}"),
+        ("Old lines {}", "Alte Zeilen {}"),
+        ("No file selected", "Keine Datei ausgewählt"),
+        ("Inline", "Einzeilig"),
+        ("Side-by-side", "Nebeneinander"),
+        ("Ignore case", "Groß-/Kleinschreibung ignorieren"),
+        ("Ignore comments", "Kommentare ignorieren"),
+        (
+            "Hide hunks that only change comments or blank lines",
+            "Abschnitte ausblenden, die nur Kommentare oder Leerzeilen ändern",
+        ),
+        ("Ignore import order", "Import-Reihenfolge ignorieren"),
+        (
+            "Hide hunks that only reorder imports",
+            "Abschnitte ausblenden, die nur Importe umsortieren",
+        ),
+        (
+            "{} hunks hidden by the ignore options",
+            "{} Abschnitte durch die Ignorieren-Optionen ausgeblendet",
+        ),
+        ("Age", "Alter"),
+        (
+            "Color line numbers by how recently each line changed, newest brightest",
+            "Zeilennummern nach dem Alter der letzten Änderung färben, neueste am hellsten",
+        ),
+        (
+            "jj file annotate failed: {}",
+            "jj file annotate fehlgeschlagen: {}",
+        ),
+        (
+            "Compare with clipboard: the clipboard text as the old side, the file on disk as the new",
+            "Mit Zwischenablage vergleichen: Text der Zwischenablage als alte Seite, die Datei auf der Festplatte als neue",
+        ),
+        (
+            "Couldn't read the clipboard: {}",
+            "Zwischenablage konnte nicht gelesen werden: {}",
+        ),
+        ("Highlight", "Hervorheben"),
+        (
+            "Highlight changes within replaced lines by word or character",
+            "Änderungen in ersetzten Zeilen wort- oder zeichenweise hervorheben",
+        ),
+        ("Lines", "Zeilen"),
+        ("Words", "Wörter"),
+        ("Characters", "Zeichen"),
+        (
+            "Compare lines ignoring upper and lower case",
+            "Zeilen ohne Beachtung der Groß-/Kleinschreibung vergleichen",
+        ),
+        ("File changed on disk", "Datei auf der Festplatte geändert"),
+        ("Reload", "Neu laden"),
+        ("Copy as Markdown", "Als Markdown kopieren"),
+        ("New file", "Neue Datei"),
+        ("+{} lines", "+{} Zeilen"),
+        ("Show content", "Inhalt anzeigen"),
+        ("Fold", "Einklappen"),
+        (
+            "New files longer than `added_fold_lines` in config.toml start folded",
+            "Neue Dateien länger als `added_fold_lines` in config.toml starten eingeklappt",
+        ),
+        (
+            "Copy the selected lines as a ```diff block",
+            "Ausgewählte Zeilen als ```diff-Block kopieren",
+        ),
+        ("Conflict", "Konflikt"),
+        ("side #{} of {}", "Seite {} von {}"),
+        ("Side #{} of {}", "Seite {} von {}"),
+        (
+            "Side #{} of {}, as changes from the base",
+            "Seite {} von {}, als Änderungen gegenüber der Basis",
+        ),
+        ("Base", "Basis"),
+        ("End of conflict", "Konfliktende"),
+        (
+            "Swap sides, showing the diff from new to old",
+            "Seiten tauschen und den Diff von neu nach alt zeigen",
+        ),
+        ("fetch", "Abruf"),
+        ("diff", "Diff"),
+        ("highlight", "Hervorhebung"),
+        ("layout", "Layout"),
+        ("Gutter", "Zeilennummern"),
+        ("Absolute", "Absolut"),
+        ("Relative to cursor", "Relativ zum Cursor"),
+        ("Old only", "Nur alt"),
+        ("New only", "Nur neu"),
+        ("Hidden", "Ausgeblendet"),
+        ("Show outputs", "Ausgaben zeigen"),
+        (
+            "Include cell outputs and metadata in the diff",
+            "Zellausgaben und Metadaten in den Diff aufnehmen",
+        ),
+        ("Semantic", "Semantisch"),
+        (
+            "Compare parsed keys, ignoring ordering and formatting",
+            "Geparste Schlüssel vergleichen, Reihenfolge und Formatierung ignorieren",
+        ),
+        ("Structural", "Strukturell"),
+        (
+            "Match syntax tokens instead of lines, so reformatting isn't a change",
+            "Syntax-Token statt Zeilen vergleichen, damit Umformatieren keine Änderung ist",
+        ),
+        ("Plain text", "Nur Text"),
+        (
+            "Show this file without syntax highlighting",
+            "Diese Datei ohne Syntaxhervorhebung zeigen",
+        ),
+        ("Table", "Tabelle"),
+        (
+            "Align columns and highlight changed cells",
+            "Spalten ausrichten und geänderte Zellen hervorheben",
+        ),
+        ("Preview", "Vorschau"),
+        (
+            "Render the old and new versions side by side",
+            "Alte und neue Version gerendert nebeneinander zeigen",
+        ),
+        ("Edit", "Bearbeiten"),
+        (
+            "Edit the working copy file here; saving updates the diff",
+            "Datei der Arbeitskopie hier bearbeiten; Speichern aktualisiert den Diff",
+        ),
+        (
+            "Select a file from the sidebar to view its diff.",
+            "Eine Datei in der Seitenleiste wählen, um ihren Diff zu sehen.",
+        ),
+        (
+            "Generated or vendored file",
+            "Generierte oder eingebundene Datei",
+        ),
+        (
+            "File is marked @generated",
+            "Datei ist als @generated markiert",
+        ),
+        ("Load anyway", "Trotzdem laden"),
+        (
+            "Add patterns under `generated` in config.toml to collapse more files",
+            "Muster unter `generated` in config.toml eintragen, um mehr Dateien einzuklappen",
+        ),
+        ("Loading diff...", "Diff wird geladen..."),
+        ("Retry", "Erneut versuchen"),
+        ("Retry as plain text", "Als Text erneut versuchen"),
+        (
+            "Syntax highlighting skipped for {} lines",
+            "Syntaxhervorhebung für {} Zeilen übersprungen",
+        ),
+        ("Highlight anyway", "Trotzdem hervorheben"),
+        (
+            "Raise `highlight_max_lines` in config.toml to keep it",
+            "`highlight_max_lines` in config.toml erhöhen, um es beizubehalten",
+        ),
+        ("Save", "Speichern"),
+        (
+            "Write to disk (Ctrl+S)",
+            "Auf die Festplatte schreiben (Strg+S)",
+        ),
+        ("Discard", "Verwerfen"),
+        (
+            "Reload the file from disk",
+            "Datei von der Festplatte neu laden",
+        ),
+        ("unsaved", "ungespeichert"),
+        ("Before", "Vorher"),
+        ("After", "Nachher"),
+        ("{} unchanged rows", "{} unveränderte Zeilen"),
+        ("Click to copy", "Zum Kopieren klicken"),
+        ("This file was deleted", "Diese Datei wurde gelöscht"),
+        ("Restore file", "Datei wiederherstellen"),
+        ("Revert hunk", "Abschnitt zurücksetzen"),
+        (
+            "Function or section the hunk is in",
+            "Funktion oder Abschnitt, in dem der Hunk liegt",
+        ),
+        ("Find in diff", "Im Diff suchen"),
+        ("No matches", "Keine Treffer"),
+        (
+            "Previous match (Shift+Enter)",
+            "Vorheriger Treffer (Umschalt+Enter)",
+        ),
+        ("Next match (Enter)", "Nächster Treffer (Enter)"),
+        (
+            "Undo this hunk in the working copy",
+            "Diesen Abschnitt in der Arbeitskopie rückgängig machen",
+        ),
+        ("Copy as patch", "Als Patch kopieren"),
+        (
+            "Select for batch actions; Shift-click selects a range",
+            "Für Sammelaktionen auswählen; Umschalt-Klick wählt einen Bereich",
+        ),
+        ("{} hunks selected", "{} Abschnitte ausgewählt"),
+        ("Revert {} hunks", "{} Abschnitte zurücksetzen"),
+        ("Clear selection", "Auswahl aufheben"),
+        ("Ctrl+A selects every hunk", "Strg+A wählt alle Abschnitte"),
+        ("Copy link", "Link kopieren"),
+        (
+            "le-differ --goto link to this hunk",
+            "le-differ --goto-Link zu diesem Abschnitt",
+        ),
+        ("Copy new text", "Neuen Text kopieren"),
+        ("added", "hinzugefügt"),
+        ("modified", "geändert"),
+        ("deleted", "gelöscht"),
+        ("renamed", "umbenannt"),
+        ("from", "von"),
+        // Dialogs
+        ("Cancel", "Abbrechen"),
+        ("Restore from revision", "Aus Revision wiederherstellen"),
+        ("Restore {} from {}", "{} aus {} wiederherstellen"),
+        (
+            "change ID, bookmark or revset",
+            "Änderungs-ID, Bookmark oder Revset",
+        ),
+        ("Restore", "Wiederherstellen"),
+        ("Preview the restore first", "Zuerst eine Vorschau anzeigen"),
+        (
+            "The file is already the same there",
+            "Die Datei ist dort bereits identisch",
+        ),
+        (
+            "Pick a revision and preview what restoring it changes.",
+            "Revision wählen und in der Vorschau prüfen, was die Wiederherstellung ändert.",
+        ),
+        (
+            "The revision changed, preview again.",
+            "Die Revision hat sich geändert, bitte erneut Vorschau anzeigen.",
+        ),
+        ("Close", "Schließen"),
+        ("Apply", "Anwenden"),
+        ("Revert", "Zurücksetzen"),
+        ("Patch applied.", "Patch angewendet."),
+        (
+            "{} hunks don't apply cleanly",
+            "{} Abschnitte lassen sich nicht sauber anwenden",
+        ),
+        ("conflict", "Konflikt"),
+        ("Afterwards:", "Danach:"),
+        (
+            "This reverts the most recent operation:",
+            "Dies macht die letzte Operation rückgängig:",
+        ),
+        (
+            "The working-copy change and everything in it will be dropped:",
+            "Die Änderung der Arbeitskopie und ihr gesamter Inhalt werden verworfen:",
+        ),
+        (
+            "It has no file changes.",
+            "Sie enthält keine Dateiänderungen.",
+        ),
+        ("Split change", "Änderung aufteilen"),
+        (
+            "Description of the first change",
+            "Beschreibung der ersten Änderung",
+        ),
+        (
+            "Both changes need at least one file",
+            "Beide Änderungen brauchen mindestens eine Datei",
+        ),
+        ("First change", "Erste Änderung"),
+        ("Remains in @", "Bleibt in @"),
+        (
+            "Move to the other change",
+            "In die andere Änderung verschieben",
+        ),
+        // Review
+        ("Viewed", "Angesehen"),
+        ("Needs work", "Braucht Arbeit"),
+        ("Comment on file...", "Datei kommentieren..."),
+        ("Comment on this line...", "Diese Zeile kommentieren..."),
+        ("Review summary", "Review-Zusammenfassung"),
+        ("Review summary...", "Review-Zusammenfassung..."),
+        (
+            "File states, comments and notes as Markdown",
+            "Dateistatus, Kommentare und Notizen als Markdown",
+        ),
+        ("Overall notes", "Allgemeine Notizen"),
+        (
+            "{} of {} files reviewed, {} comments",
+            "{} von {} Dateien geprüft, {} Kommentare",
+        ),
+        ("Review time: {}", "Review-Zeit: {}"),
+        ("Copy Markdown", "Markdown kopieren"),
+        ("Copied to clipboard", "In die Zwischenablage kopiert"),
+        ("Save...", "Speichern..."),
+        ("Save review summary", "Review-Zusammenfassung speichern"),
+        ("Saved {}", "{} gespeichert"),
+        ("Comment", "Kommentar"),
+        ("Add comment", "Kommentar hinzufügen"),
+        // File dialogs
+        ("Open repository...", "Repository öffnen..."),
+        ("Open repository", "Repository öffnen"),
+        ("Save patch...", "Patch speichern..."),
+        ("Save patch", "Patch speichern"),
+        (
+            "Write the whole diff as a git patch",
+            "Den gesamten Diff als Git-Patch schreiben",
+        ),
+        ("Save patch: {}", "Patch speichern: {}"),
+        ("{} is not in a repository", "{} liegt in keinem Repository"),
+        // Metadata panel
+        ("Revision", "Revision"),
+        ("Change", "Änderung"),
+        ("Commit", "Commit"),
+        ("Author", "Autor"),
+        ("Authored", "Verfasst"),
+        ("Committed", "Committet"),
+        ("Parents", "Eltern"),
+        (
+            "Show this parent's changes",
+            "Änderungen dieses Elternteils zeigen",
+        ),
+        ("Bookmarks", "Bookmarks"),
+        ("(no description)", "(keine Beschreibung)"),
+        // Help
+        ("Help", "Hilfe"),
+        ("File status", "Dateistatus"),
+        ("Diff colors", "Diff-Farben"),
+        ("Added line", "Hinzugefügte Zeile"),
+        ("Removed line", "Entfernte Zeile"),
+        ("Conflict, first side", "Konflikt, erste Seite"),
+        ("Conflict, second side", "Konflikt, zweite Seite"),
+        ("Conflict base", "Konfliktbasis"),
+        ("Keyboard and mouse", "Tastatur und Maus"),
+        ("Show or hide this help", "Diese Hilfe ein- oder ausblenden"),
+        (
+            "Add or remove a file or commit from the selection",
+            "Datei oder Commit zur Auswahl hinzufügen oder entfernen",
+        ),
+        (
+            "Select a range of files, diff lines or hunks",
+            "Einen Bereich von Dateien, Diff-Zeilen oder Abschnitten auswählen",
+        ),
+        (
+            "Select every hunk in the file",
+            "Alle Abschnitte der Datei auswählen",
+        ),
+        (
+            "Focus the next pane: files, diff, find bar and log; Shift+Tab goes back",
+            "Nächsten Bereich fokussieren: Dateien, Diff, Suchleiste und Log; Umschalt+Tab geht zurück",
+        ),
+        (
+            "Step through the files, hunks or commits of the focused pane",
+            "Durch die Dateien, Abschnitte oder Commits des fokussierten Bereichs gehen",
+        ),
+        (
+            "Mark the focused file as viewed, or unmark it",
+            "Die fokussierte Datei als angesehen markieren oder die Markierung aufheben",
+        ),
+        (
+            "In the log, diff the selected commit or the two selected",
+            "Im Log den ausgewählten Commit oder die zwei ausgewählten vergleichen",
+        ),
+        (
+            "Find in the diff, with matches marked beside the scroll bar",
+            "Im Diff suchen, Treffer werden neben der Bildlaufleiste markiert",
+        ),
+        (
+            "File and line actions, like copying or reverting",
+            "Datei- und Zeilenaktionen wie Kopieren oder Zurücksetzen",
+        ),
+        (
+            "Save while editing a file in the diff view",
+            "Beim Bearbeiten einer Datei in der Diff-Ansicht speichern",
+        ),
+        (
+            "Show how long loading and drawing the diff took",
+            "Anzeigen, wie lange Laden und Zeichnen des Diffs gedauert haben",
+        ),
+        ("View modes", "Ansichten"),
+        (
+            "Old and new version in two columns",
+            "Alte und neue Version in zwei Spalten",
+        ),
+        (
+            "Removed lines above the added lines that replace them",
+            "Entfernte Zeilen über den hinzugefügten Zeilen, die sie ersetzen",
+        ),
+        // Status bar and close dialog
+        ("Stop this jj command", "Diesen jj-Befehl abbrechen"),
+        ("Output", "Ausgabe"),
+        ("Open in the diff view", "In der Diff-Ansicht öffnen"),
+        ("Dismiss", "Verwerfen"),
+        ("Warnings from jj", "Warnungen von jj"),
+        (
+            "Colocated with git: both share this working copy, and jj imports git's changes on every command",
+            "Mit git kolokiert: beide teilen diese Arbeitskopie, und jj übernimmt Änderungen von git bei jedem Befehl",
+        ),
+        (
+            "Comparing jj's files with git's ignore rules...",
+            "Vergleiche die Dateien von jj mit den Ignorier-Regeln von git...",
+        ),
+        (
+            "jj and git agree on which files are tracked",
+            "jj und git sind sich einig, welche Dateien verfolgt werden",
+        ),
+        (
+            "Tracked by jj but ignored by git:",
+            "Von jj verfolgt, aber von git ignoriert:",
+        ),
+        (
+            "Seen by git but not in jj's last snapshot:",
+            "Von git gesehen, aber nicht im letzten Snapshot von jj:",
+        ),
+        ("Check again", "Erneut prüfen"),
+        ("Open on forge", "Auf der Forge öffnen"),
+        (
+            "Open the file in the browser at the newest commit the git remote has",
+            "Die Datei im Browser beim neuesten Commit öffnen, den das git-Remote hat",
+        ),
+        (
+            "No git remote to open the file on",
+            "Kein git-Remote, auf dem die Datei geöffnet werden kann",
+        ),
+        (
+            "No commit of this change is on a remote yet",
+            "Noch kein Commit dieser Änderung ist auf einem Remote",
+        ),
+        (
+            "Can't tell the web address of remote {}",
+            "Die Webadresse des Remotes {} ist nicht erkennbar",
+        ),
+        (
+            "Operation in progress; refreshes show the last snapshot until it finishes",
+            "Vorgang läuft; Aktualisierungen zeigen bis zum Ende den letzten Schnappschuss",
+        ),
+        ("{} queued", "{} in Warteschlange"),
+        (
+            "Review time this session\nThis change: {}\nThis file: {}",
+            "Review-Zeit dieser Sitzung\nDiese Änderung: {}\nDiese Datei: {}",
+        ),
+        ("Update", "Aktualisieren"),
+        ("Working copy is stale", "Arbeitskopie ist veraltet"),
+        (
+            "Another workspace rewrote this one's commit; update it to continue",
+            "Ein anderer Workspace hat den Commit dieses Workspaces umgeschrieben; zum Fortfahren aktualisieren",
+        ),
+        (
+            "Update stale working copy",
+            "Veraltete Arbeitskopie aktualisieren",
+        ),
+        ("Operation output", "Ausgabe der Operation"),
+        ("Read-only", "Schreibgeschützt"),
+        (
+            "No repository found above the working directory",
+            "Kein Repository über dem Arbeitsverzeichnis gefunden",
+        ),
+        ("Operation in progress", "Operation läuft"),
+        ("Close when finished", "Nach Abschluss schließen"),
+        ("Keep open", "Offen lassen"),
+        ("Restore {}", "{} wiederherstellen"),
+        ("{} has no changes", "{} hat keine Änderungen"),
+        ("{} changed files", "{} geänderte Dateien"),
+    ])
+});
@@ -0,0 +1,189 @@
+use std::path::Path;
+use tree_sitter::Node;
+
+use crate::structural_diff;
+
+/// Line comment markers by extension, for files without a grammar or
+/// whose grammar gave up
+fn comment_prefixes(extension: &str) -> &'static [&'static str] {
+    match extension {
+        "rs" | "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" | "go" | "c" | "h" | "cc" | "cpp"
+        | "hpp" | "java" | "kt" | "kts" | "scala" | "swift" | "cs" | "dart" | "zig" => {
+            &["//", "/*", "*", "*/"]
+        }
+        "py" | "pyi" | "sh" | "bash" | "zsh" | "fish" | "rb" | "pl" | "r" | "toml" | "yaml"
+        | "yml" | "nix" | "ps1" | "mk" => &["#"],
+        "sql" | "lua" | "hs" | "elm" => &["--"],
+        "ini" | "cfg" | "el" | "clj" | "lisp" => &[";", "#"],
+        "html" | "htm" | "xml" | "svg" | "vue" => &["<!--"],
+        _ => &[],
+    }
+}
+
+/// Line starts of import statements by extension. Go's block imports are
+/// bare quoted paths, matched separately.
+fn import_prefixes(extension: &str) -> &'static [&'static str] {
+    match extension {
+        "rs" => &["use ", "pub use ", "pub(crate) use ", "extern crate "],
+        "py" | "pyi" => &["import ", "from "],
+        "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" | "vue" => &["import "],
+        "go" => &["import "],
+        "java" | "kt" | "kts" | "scala" | "dart" => &["import "],
+        "c" | "h" | "cc" | "cpp" | "hpp" => &["#include "],
+        "cs" => &["using "],
+        "swift" => &["import "],
+        _ => &[],
+    }
+}
+
+fn extension(path: &str) -> &str {
+    Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+}
+
+pub fn supports_comments(path: &str) -> bool {
+    !comment_prefixes(extension(path)).is_empty()
+}
+
+pub fn supports_imports(path: &str) -> bool {
+    !import_prefixes(extension(path)).is_empty()
+}
+
+/// Per 0-based line of `content`, whether it holds only comments. Files
+/// with a grammar go by their syntax tree, so `//` inside a string isn't
+/// taken for one; the rest by their line comment markers.
+pub fn comment_lines(path: &str, content: &str) -> Vec<bool> {
+    let Some(tree) = structural_diff::syntax_tree(path, content) else {
+        let prefixes = comment_prefixes(extension(path));
+        return content
+            .lines()
+            .map(|line| {
+                let line = line.trim_start();
+                prefixes.iter().any(|p| line.starts_with(p))
+            })
+            .collect();
+    };
+
+    let mut commented = vec![false; content.len()];
+    mark_comments(tree.root_node(), &mut commented);
+    let mut start = 0;
+    content
+        .split('\n')
+        .map(|line| {
+            let bytes = start..start + line.len();
+            start = bytes.end + 1;
+            let mut text = line
+                .bytes()
+                .zip(&commented[bytes])
+                .filter(|(b, _)| !b.is_ascii_whitespace())
+                .peekable();
+            text.peek().is_some() && text.all(|(_, &comment)| comment)
+        })
+        .take(content.lines().count())
+        .collect()
+}
+
+fn mark_comments(node: Node, commented: &mut [bool]) {
+    if node.kind().contains("comment") {
+        commented[node.byte_range()].fill(true);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        mark_comments(child, commented);
+    }
+}
+
+/// Whether the trimmed `line` imports something
+fn is_import(extension: &str, line: &str) -> bool {
+    if import_prefixes(extension)
+        .iter()
+        .any(|p| line.starts_with(p))
+    {
+        return true;
+    }
+    // Inside Go's `import (...)`: `"fmt"` or `alias "path"`
+    extension == "go"
+        && line.ends_with('"')
+        && line.split_once('"').is_some_and(|(alias, _)| {
+            alias
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == ' ')
+        })
+}
+
+/// Whether `removed` and `added` are the same imports in another order.
+/// Lines are trimmed, and blank ones don't count.
+pub fn reorders_imports(path: &str, removed: &[&str], added: &[&str]) -> bool {
+    let extension = extension(path);
+    match (
+        sorted_imports(extension, removed),
+        sorted_imports(extension, added),
+    ) {
+        (Some(removed), Some(added)) => !removed.is_empty() && removed == added,
+        _ => false,
+    }
+}
+
+/// The non-blank `lines` trimmed and sorted, or None if any isn't an import
+fn sorted_imports<'a>(extension: &str, lines: &[&'a str]) -> Option<Vec<&'a str>> {
+    let mut imports = Vec::new();
+    for line in lines.iter().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        if !is_import(extension, line) {
+            return None;
+        }
+        imports.push(line);
+    }
+    imports.sort_unstable();
+    Some(imports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_comment_only_lines() {
+        let cases: [(&str, &str, &[bool]); 3] = [
+            // A chunk mixing a comment change with code
+            (
+                "a.rs",
+                "// note\nlet a = 1; // trailing\nlet s = \"// not one\";\n/* block\n   more */\n",
+                &[true, false, false, true, true],
+            ),
+            ("a.sh", "# note\necho hi # trailing\n", &[true, false]),
+            ("a.unknown", "// note\n", &[false]),
+        ];
+        for (path, content, expected) in cases {
+            assert_eq!(comment_lines(path, content), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn detects_reordered_imports() {
+        let cases: [(&str, &[&str], &[&str], bool); 5] = [
+            ("a.rs", &["use b;", "use a;"], &["use a;", "use b;"], true),
+            ("a.rs", &["use a;", ""], &["  use a;"], true),
+            ("a.rs", &["use a;"], &["use b;"], false),
+            // Code in the chunk keeps it visible
+            (
+                "a.rs",
+                &["use b;", "use a;"],
+                &["use a;", "let x = 1;", "use b;"],
+                false,
+            ),
+            ("a.go", &["\"os\"", "\"fmt\""], &["\"fmt\"", "\"os\""], true),
+        ];
+        for (path, removed, added, expected) in cases {
+            assert_eq!(
+                reorders_imports(path, removed, added),
+                expected,
+                "{:?} -> {:?}",
+                removed,
+                added
+            );
+        }
+    }
+}
@@ -0,0 +1,1056 @@
+use eframe::egui;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError, channel};
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, warn};
+
+/// Root of the workspace commands run in; None uses the process CWD
+static WORKSPACE_ROOT: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+pub fn set_workspace_root(root: Option<PathBuf>) {
+    *WORKSPACE_ROOT.write().unwrap_or_else(|e| e.into_inner()) = root;
+}
+
+pub fn workspace_root() -> Option<PathBuf> {
+    WORKSPACE_ROOT
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Nearest directory at or above `start` holding a `.jj` repo, falling
+/// back to a `.git` one for colocated or not-yet-initialized repos
+pub fn find_repo_root(start: &Path) -> Option<PathBuf> {
+    let find = |marker: &str| {
+        start
+            .ancestors()
+            .find(|dir| dir.join(marker).is_dir())
+            .map(Path::to_path_buf)
+    };
+    find(".jj").or_else(|| find(".git"))
+}
+
+/// Held by a mutating command for as long as it runs, so they go one at a
+/// time
+static OPERATION_LOCK: Mutex<()> = Mutex::new(());
+
+/// Set while a mutating command runs
+static OPERATION_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Whether a mutating command is running right now
+pub fn operation_running() -> bool {
+    OPERATION_RUNNING.load(Ordering::Acquire)
+}
+
+/// Set while comparing against the last snapshot, so reads don't take a new
+/// one and record the very edits being looked at
+static SNAPSHOTS_PAUSED: AtomicBool = AtomicBool::new(false);
+
+pub fn pause_snapshots(paused: bool) {
+    SNAPSHOTS_PAUSED.store(paused, Ordering::Release);
+}
+
+/// A `jj` command running in the selected workspace. While an operation is
+/// running it reads the last snapshot instead of taking a new one, which
+/// would catch the working copy halfway through the operation. The same
+/// goes while snapshots are paused.
+pub fn command() -> Command {
+    let mut command = workspace_command();
+    if operation_running() || SNAPSHOTS_PAUSED.load(Ordering::Acquire) {
+        command.arg("--ignore-working-copy");
+    }
+    command
+}
+
+/// stderr of commands that failed only because another process held a lock
+/// for a moment
+const LOCK_ERRORS: &[&str] = &[
+    "Failed to lock",
+    "index.lock",
+    "Resource temporarily unavailable",
+    "Resource busy",
+];
+
+/// Waits before each retry of a command that hit a lock
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_millis(100),
+    Duration::from_millis(300),
+    Duration::from_millis(900),
+];
+
+/// Set when the last command failed on a stale working copy
+static STALE: AtomicBool = AtomicBool::new(false);
+
+/// Whether jj last reported the working copy as stale, which needs
+/// `jj workspace update-stale` before anything else works
+pub fn working_copy_stale() -> bool {
+    STALE.load(Ordering::Acquire)
+}
+
+/// A `Warning:` jj printed on stderr, e.g. about files it refused to
+/// snapshot, with the paths it names
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    pub message: String,
+    pub paths: Vec<String>,
+}
+
+/// Warnings seen since they were last dismissed, without repeats
+static WARNINGS: Mutex<Vec<Warning>> = Mutex::new(Vec::new());
+
+pub fn warnings() -> Vec<Warning> {
+    WARNINGS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Forget the shown warnings; ones that still apply come back with the
+/// next command that prints them
+pub fn dismiss_warnings() {
+    WARNINGS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+fn record_warnings(stderr: &str) {
+    if !stderr.contains("Warning: ") {
+        return;
+    }
+    let mut warnings = WARNINGS.lock().unwrap_or_else(|e| e.into_inner());
+    for warning in parse_warnings(stderr) {
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+}
+
+/// Each `Warning:` line with the indented lines and hints under it. The
+/// indented ones read `path: reason` when listing files, so existing
+/// paths are picked out.
+fn parse_warnings(stderr: &str) -> Vec<Warning> {
+    let mut warnings: Vec<Warning> = Vec::new();
+    let mut in_warning = false;
+    for line in stderr.lines() {
+        if let Some(message) = line.strip_prefix("Warning: ") {
+            warnings.push(Warning {
+                message: message.to_string(),
+                paths: Vec::new(),
+            });
+            in_warning = true;
+        } else if in_warning
+            && line.starts_with(char::is_whitespace)
+            && let Some(warning) = warnings.last_mut()
+        {
+            let detail = line.trim();
+            warning.message.push('\n');
+            warning.message.push_str(detail);
+            if let Some((path, _)) = detail.split_once(": ")
+                && workspace_path(path).exists()
+            {
+                warning.paths.push(path.to_string());
+            }
+        } else if in_warning
+            && line.starts_with("Hint: ")
+            && let Some(warning) = warnings.last_mut()
+        {
+            warning.message.push('\n');
+            warning.message.push_str(line);
+        } else {
+            in_warning = false;
+        }
+    }
+    warnings
+}
+
+/// Reads give up after this; a jj that hangs this long is stuck, not slow
+pub const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Operations may fetch or push over the network, so they get longer
+const OPERATION_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How often a running command checks for cancellation and its deadline
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Stops a running command when cancelled; clones share the flag
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// Kills and reaps the child if it's still running when dropped, so an
+/// early return or panic doesn't leave a zombie behind
+struct ChildGuard(Child);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Ok(None) = self.0.try_wait() {
+            let _ = self.0.kill();
+        }
+        let _ = self.0.wait();
+    }
+}
+
+/// Run `command`, handing each line of stdout to `on_line` as it arrives.
+/// The child is killed once `timeout` passes or `cancel` is set.
+pub fn stream(
+    command: &mut Command,
+    timeout: Duration,
+    cancel: Option<&CancelToken>,
+    mut on_line: impl FnMut(&str),
+) -> std::io::Result<Output> {
+    let mut child = ChildGuard(
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?,
+    );
+    let stdout = child.0.stdout.take().expect("stdout is piped");
+    let mut stderr = child.0.stderr.take().expect("stderr is piped");
+
+    // Both pipes drain on their own threads so a full one can't block jj
+    let stderr_reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stderr.read_to_end(&mut buf);
+        buf
+    });
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        loop {
+            let mut line = Vec::new();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) if sender.send(line).is_err() => break,
+                Ok(_) => {}
+            }
+        }
+    });
+
+    let deadline = Instant::now() + timeout;
+    let mut stdout = Vec::new();
+    loop {
+        match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(line) => {
+                on_line(String::from_utf8_lossy(&line).trim_end_matches('\n'));
+                stdout.extend_from_slice(&line);
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        if cancel.is_some_and(CancelToken::is_cancelled) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                "cancelled",
+            ));
+        }
+        if Instant::now() > deadline {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("timed out after {}s", timeout.as_secs()),
+            ));
+        }
+    }
+
+    let status = child.0.wait()?;
+    let stderr = stderr_reader.join().unwrap_or_default();
+    record_warnings(&String::from_utf8_lossy(&stderr));
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+/// Like `Command::output`, killed after `COMMAND_TIMEOUT`
+pub fn output_with_retry(command: &mut Command) -> std::io::Result<Output> {
+    retrying(command, COMMAND_TIMEOUT, None)
+}
+
+/// Runs `command`, retried with backoff while jj reports lock contention.
+/// Also notes whether the working copy is stale.
+fn retrying(
+    command: &mut Command,
+    timeout: Duration,
+    cancel: Option<&CancelToken>,
+) -> std::io::Result<Output> {
+    let mut delays = RETRY_DELAYS.iter();
+    loop {
+        let output = stream(command, timeout, cancel, |_| {})?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.success() {
+            STALE.store(false, Ordering::Release);
+        } else if stderr.contains("working copy is stale") {
+            STALE.store(true, Ordering::Release);
+        }
+        let locked = !output.status.success() && LOCK_ERRORS.iter().any(|e| stderr.contains(e));
+        match delays.next() {
+            Some(delay) if locked => {
+                warn!("jj hit a lock, retrying in {:?}: {}", delay, stderr.trim());
+                thread::sleep(*delay);
+            }
+            _ => return Ok(output),
+        }
+    }
+}
+
+fn workspace_command() -> Command {
+    let mut command = Command::new("jj");
+    if let Some(root) = workspace_root() {
+        command.current_dir(root);
+    }
+    command
+}
+
+/// A jj release, as printed by `jj --version`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// "jj 0.45.1", or "jj 0.46.0-1f2e3d..." for builds from source
+    fn parse(output: &str) -> Option<Self> {
+        let version = output.trim().strip_prefix("jj ")?;
+        let version = version.split(['-', '+', ' ']).next()?;
+        let mut parts = version.split('.').map(|part| part.parse().ok());
+        Some(Self::new(parts.next()??, parts.next()??, parts.next()??))
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Oldest release whose output the parsers are known to read, and the one
+/// that moved `cat`, `files` and `untrack` under `jj file`
+pub const OLDEST_SUPPORTED: Version = Version::new(0, 19, 0);
+
+/// Newest release checked against. Later ones usually work, but `jj st`'s
+/// layout is not a stable interface and has changed between releases.
+pub const NEWEST_TESTED: Version = Version::new(0, 45, 1);
+
+/// What `jj --version` said
+enum Installed {
+    Missing,
+    Unrecognized(String),
+    Found(Version),
+}
+
+/// Asked once, the first time anything needs it
+static INSTALLED: LazyLock<Installed> = LazyLock::new(|| {
+    let output = match Command::new("jj").arg("--version").output() {
+        Ok(output) if output.status.success() => output,
+        _ => return Installed::Missing,
+    };
+    let printed = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    match Version::parse(&printed) {
+        Some(version) => {
+            debug!("Found jj {}", version);
+            Installed::Found(version)
+        }
+        None => {
+            warn!("Couldn't read jj's version from {:?}", printed);
+            Installed::Unrecognized(printed)
+        }
+    }
+});
+
+/// The installed jj's version, None when it's missing or printed
+/// something unexpected
+pub fn version() -> Option<Version> {
+    match *INSTALLED {
+        Installed::Found(version) => Some(version),
+        _ => None,
+    }
+}
+
+/// How the installed jj compares to the releases the parsers were written for
+pub enum Compatibility {
+    Supported,
+    /// Not on PATH; failing commands already say so
+    Missing,
+    /// `jj --version` printed this instead of a version
+    Unrecognized(String),
+    TooOld(Version),
+    /// A newer minor release than `NEWEST_TESTED`
+    Untested(Version),
+}
+
+pub fn compatibility() -> Compatibility {
+    match &*INSTALLED {
+        Installed::Missing => Compatibility::Missing,
+        Installed::Unrecognized(printed) => Compatibility::Unrecognized(printed.clone()),
+        Installed::Found(version) if *version < OLDEST_SUPPORTED => Compatibility::TooOld(*version),
+        Installed::Found(version)
+            if (version.major, version.minor) > (NEWEST_TESTED.major, NEWEST_TESTED.minor) =>
+        {
+            Compatibility::Untested(*version)
+        }
+        Installed::Found(_) => Compatibility::Supported,
+    }
+}
+
+/// `jj file <name>` as the installed jj spells it. Before 0.19 these were
+/// top-level, with `show` named `cat` and `list` named `files`.
+pub fn file_command(name: &'static str) -> Vec<&'static str> {
+    if version().is_none_or(|version| version >= OLDEST_SUPPORTED) {
+        return vec!["file", name];
+    }
+    vec![match name {
+        "show" => "cat",
+        "list" => "files",
+        name => name,
+    }]
+}
+
+/// Resolve a repo-relative path against the selected workspace
+pub fn workspace_path(path: &str) -> PathBuf {
+    match workspace_root() {
+        Some(root) => root.join(path),
+        None => PathBuf::from(path),
+    }
+}
+
+/// `path` as a fileset naming exactly that file, so glob characters,
+/// spaces or quotes in it aren't parsed as an expression. jj before 0.20
+/// takes plain paths.
+pub fn root_file(path: &str) -> String {
+    if version().is_some_and(|version| version < Version::new(0, 20, 0)) {
+        return path.to_string();
+    }
+    let escaped = path.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("root-file:\"{}\"", escaped)
+}
+
+/// Names of all workspaces in the repository
+pub fn workspaces() -> Vec<String> {
+    let output = output_with_retry(command().args(["workspace", "list"]));
+
+    // Lines are "<name>: <change id> <description>"
+    match output {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout)
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, _)| name.trim().to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Name of the workspace whose working copy is `@`
+pub fn current_workspace() -> Option<String> {
+    let output =
+        output_with_retry(command().args(["log", "--no-graph", "-r", "@", "-T", "working_copies"]))
+            .ok()
+            .filter(|o| o.status.success())?;
+
+    // Rendered as "name@", space separated when several share the commit
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .find_map(|wc| wc.strip_suffix('@'))
+        .map(str::to_string)
+}
+
+pub fn workspace_root_for(name: &str) -> Result<PathBuf, String> {
+    let output = output_with_retry(command().args(["workspace", "root", "--name", name]))
+        .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(PathBuf::from(
+            String::from_utf8_lossy(&output.stdout).trim(),
+        ))
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Which two states the sidebar and diff viewer compare
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum DiffSource {
+    /// The working copy on disk against its parent (`@-`)
+    #[default]
+    WorkingCopy,
+    /// Two arbitrary revisions, like `jj diff --from X --to Y`
+    Range { from: String, to: String },
+    /// Files on disk against `@` as jj last snapshotted it, showing edits
+    /// it hasn't recorded yet
+    Unsnapshotted,
+}
+
+impl DiffSource {
+    /// Revision holding the old side of each file
+    pub fn old_revision(&self) -> &str {
+        match self {
+            DiffSource::WorkingCopy => "@-",
+            DiffSource::Range { from, .. } => from,
+            DiffSource::Unsnapshotted => "@",
+        }
+    }
+
+    /// Revision holding the new side, or None to read from disk
+    pub fn new_revision(&self) -> Option<&str> {
+        match self {
+            DiffSource::WorkingCopy | DiffSource::Unsnapshotted => None,
+            DiffSource::Range { to, .. } => Some(to),
+        }
+    }
+
+    /// Revision arguments for `jj diff`; None when jj can't diff it, since
+    /// it only diffs what it has recorded
+    pub fn diff_args(&self) -> Option<Vec<String>> {
+        match self {
+            DiffSource::WorkingCopy => Some(vec!["-r".to_string(), "@".to_string()]),
+            DiffSource::Range { from, to } => Some(vec![
+                "--from".to_string(),
+                from.clone(),
+                "--to".to_string(),
+                to.clone(),
+            ]),
+            DiffSource::Unsnapshotted => None,
+        }
+    }
+}
+
+/// Added/removed line counts for one file
+#[derive(Clone, Debug, Default)]
+pub struct FileStat {
+    pub path: String,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Exact per-file line counts, taken from the git-format diff since
+/// `--stat` bars are scaled
+pub fn diff_stats(source: &DiffSource) -> Vec<FileStat> {
+    git_diff(source)
+        .map(|diff| parse_git_diff_stats(&diff))
+        .unwrap_or_default()
+}
+
+/// Line counts for what one revision changed against its parents
+pub fn revision_stats(revision: &str) -> Result<Vec<FileStat>, String> {
+    let output = output_with_retry(command().args(["diff", "-r", revision, "--git"]))
+        .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(parse_git_diff_stats(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// The whole diff in git's format, which `git apply` and `patch` accept
+pub fn git_diff(source: &DiffSource) -> Result<String, String> {
+    git_diff_paths(source, &[])
+}
+
+/// Like `git_diff`, limited to `paths`; empty means every file
+pub fn git_diff_paths(source: &DiffSource, paths: &[String]) -> Result<String, String> {
+    git_diff_with(source, paths, &[])
+}
+
+fn git_diff_with(
+    source: &DiffSource,
+    paths: &[String],
+    extra: &[String],
+) -> Result<String, String> {
+    let Some(args) = source.diff_args() else {
+        return Err("jj can only diff edits it has snapshotted".to_string());
+    };
+    let output = output_with_retry(
+        command()
+            .arg("diff")
+            .args(args)
+            .arg("--git")
+            .args(extra)
+            .args(paths.iter().map(|path| root_file(path))),
+    )
+    .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// How exported patches are written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PatchFormat {
+    /// Unchanged lines around each hunk
+    pub context: usize,
+    /// Keep `diff --git` headers with file modes and renames, which
+    /// `git apply` and `git am` read; off leaves a plain unified diff
+    pub git_headers: bool,
+}
+
+/// `paths` of the diff, or all files when empty, as a patch in `format`
+pub fn export_patch(
+    source: &DiffSource,
+    paths: &[String],
+    format: PatchFormat,
+) -> Result<String, String> {
+    let context = ["--context".to_string(), format.context.to_string()];
+    let patch = git_diff_with(source, paths, &context)?;
+    Ok(match format.git_headers {
+        true => patch,
+        false => strip_git_headers(&patch),
+    })
+}
+
+/// Git's extended header lines dropped, leaving the `---`/`+++` headers
+/// and hunks `patch` and other tools expect. Hunk lines start with a
+/// space, `+`, `-` or `\`, so none of them can be mistaken for one.
+fn strip_git_headers(patch: &str) -> String {
+    const EXTENDED: [&str; 12] = [
+        "diff --git ",
+        "index ",
+        "old mode ",
+        "new mode ",
+        "new file mode ",
+        "deleted file mode ",
+        "similarity index ",
+        "dissimilarity index ",
+        "rename from ",
+        "rename to ",
+        "copy from ",
+        "copy to ",
+    ];
+    patch
+        .split_inclusive('\n')
+        .filter(|line| !EXTENDED.iter().any(|header| line.starts_with(header)))
+        .collect()
+}
+
+fn parse_git_diff_stats(diff: &str) -> Vec<FileStat> {
+    let mut stats: Vec<FileStat> = Vec::new();
+    let mut in_hunk = false;
+
+    for line in diff.lines() {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            let path = header
+                .split_once(" b/")
+                .map(|(_, new)| new)
+                .unwrap_or(header);
+            stats.push(FileStat {
+                path: path.to_string(),
+                ..Default::default()
+            });
+            in_hunk = false;
+            continue;
+        }
+
+        let Some(stat) = stats.last_mut() else {
+            continue;
+        };
+
+        // `+++`/`---` file headers only appear before the first hunk
+        if line.starts_with("@@") {
+            in_hunk = true;
+        } else if in_hunk && line.starts_with('+') {
+            stat.added += 1;
+        } else if in_hunk && line.starts_with('-') {
+            stat.removed += 1;
+        }
+    }
+
+    stats
+}
+
+/// Prints when each line's change was authored, as seconds since the epoch
+const ANNOTATE_TEMPLATE: &str = r#"commit.author().timestamp().format("%s") ++ "\n""#;
+
+/// When the change that last touched each line of `path` at `revision`
+/// was authored, in seconds since the epoch
+pub fn annotate_times(revision: &str, path: &str) -> Result<Vec<i64>, String> {
+    // annotate takes a plain path, not a fileset, so no `root-file:` here;
+    // the command runs in the workspace root, which anchors it the same way
+    let output = output_with_retry(command().args(file_command("annotate")).args([
+        "-r",
+        revision,
+        "-T",
+        ANNOTATE_TEMPLATE,
+        path,
+    ]))
+    .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim().parse().unwrap_or(0))
+            .collect())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// When jj last wrote the working copy state, i.e. the last snapshot
+pub fn last_snapshot() -> Option<SystemTime> {
+    std::fs::metadata(
+        workspace_path(".jj")
+            .join("working_copy")
+            .join("tree_state"),
+    )
+    .and_then(|meta| meta.modified())
+    .ok()
+}
+
+/// Every file in `@` as last snapshotted
+pub fn recorded_files() -> Vec<String> {
+    let output = output_with_retry(
+        workspace_command()
+            .arg("--ignore-working-copy")
+            .args(file_command("list"))
+            .args(["-r", "@"]),
+    );
+
+    match output {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Full description of a revision, empty if undescribed
+pub fn description(revision: &str) -> String {
+    let output = output_with_retry(command().args([
+        "log",
+        "--no-graph",
+        "-r",
+        revision,
+        "-T",
+        "description",
+    ]));
+
+    match output {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).trim_end().to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Fields are NUL separated; the description goes last so it can hold anything
+const METADATA_TEMPLATE: &str = r#"change_id ++ "\0" ++ commit_id ++ "\0" ++ author.name() ++ "\0" ++ author.email() ++ "\0" ++ author.timestamp() ++ "\0" ++ committer.timestamp() ++ "\0" ++ parents.map(|c| c.commit_id().short()).join(" ") ++ "\0" ++ local_bookmarks.map(|b| b.name()).join(" ") ++ "\0" ++ description"#;
+
+#[derive(Clone, Debug, Default)]
+pub struct RevisionMetadata {
+    /// Full IDs, shortened for display with `short_id`
+    pub change_id: String,
+    pub commit_id: String,
+    pub author: String,
+    pub email: String,
+    pub authored: String,
+    pub committed: String,
+    /// Short commit IDs
+    pub parents: Vec<String>,
+    pub bookmarks: Vec<String>,
+    pub description: String,
+}
+
+pub fn revision_metadata(revision: &str) -> Result<RevisionMetadata, String> {
+    let output = output_with_retry(command().args([
+        "log",
+        "--no-graph",
+        "-r",
+        revision,
+        "-T",
+        METADATA_TEMPLATE,
+    ]))
+    .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut fields = stdout.splitn(9, '\0');
+    let mut next = || fields.next().unwrap_or("").to_string();
+    let words = |s: String| s.split_whitespace().map(str::to_string).collect();
+    Ok(RevisionMetadata {
+        change_id: next(),
+        commit_id: next(),
+        author: next(),
+        email: next(),
+        authored: next(),
+        committed: next(),
+        parents: words(next()),
+        bookmarks: words(next()),
+        description: next().trim_end().to_string(),
+    })
+}
+
+/// The first `len` characters of a change or commit ID, which are ASCII
+pub fn short_id(id: &str, len: usize) -> &str {
+    &id[..id.len().min(len)]
+}
+
+/// Description of the latest entry in the operation log
+pub fn last_operation() -> String {
+    let output = output_with_retry(command().args([
+        "op",
+        "log",
+        "--no-graph",
+        "-n",
+        "1",
+        "-T",
+        "description",
+    ]));
+
+    match output {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).trim_end().to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Paths with unresolved conflicts in a revision
+pub fn conflicted_files(revision: &str) -> Vec<String> {
+    let output = output_with_retry(command().args(["resolve", "--list", "-r", revision]));
+
+    // `jj resolve --list` fails when there is nothing to resolve
+    match output {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout)
+            .lines()
+            // Lines are "<path>    <n>-sided conflict"
+            .filter_map(|line| line.split("  ").next())
+            .map(|path| path.trim().to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// File contents by (revision, path), valid until the operation log
+/// moves. Under the same op heads a revision always names the same commit,
+/// so it's a key without asking jj for an ID.
+#[derive(Default)]
+struct ContentCache {
+    /// Workspace and op heads the entries were read at
+    key: Option<(Option<PathBuf>, String)>,
+    contents: HashMap<(String, String), String>,
+}
+
+static CONTENT_CACHE: LazyLock<Mutex<ContentCache>> = LazyLock::new(Mutex::default);
+
+impl ContentCache {
+    /// Drop everything when the workspace or op heads differ from `key`
+    fn validate(&mut self, key: &(Option<PathBuf>, String)) {
+        if self.key.as_ref() != Some(key) {
+            self.contents.clear();
+            self.key = Some(key.clone());
+        }
+    }
+}
+
+/// Contents of `path` at `revision`, empty if it doesn't exist there.
+/// Cached so re-opening a diff doesn't re-run `jj file show`.
+pub fn file_show(revision: &str, path: &str) -> String {
+    let Some(key) = cache_key() else {
+        return fetch_file(revision, path);
+    };
+
+    let cached = {
+        let mut cache = CONTENT_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        cache.validate(&key);
+        cache
+            .contents
+            .get(&(revision.to_string(), path.to_string()))
+            .cloned()
+    };
+    if let Some(content) = cached {
+        return content;
+    }
+
+    let content = fetch_file(revision, path);
+
+    // Running jj may have snapshotted the working copy and moved the op
+    // log, so file the result under the heads as they are now
+    if let Some(key) = cache_key() {
+        let mut cache = CONTENT_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        cache.validate(&key);
+        cache
+            .contents
+            .insert((revision.to_string(), path.to_string()), content.clone());
+    }
+    content
+}
+
+fn fetch_file(revision: &str, path: &str) -> String {
+    let output = output_with_retry(
+        command()
+            .args(file_command("show"))
+            .args(["-r", revision])
+            .arg(root_file(path)),
+    );
+
+    match output {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Full commit ID of the newest ancestor of `revision` that a remote
+/// bookmark points at or past, i.e. one the forge already has
+pub fn pushed_commit(revision: &str) -> Option<String> {
+    let revset = format!("latest(::({}) & ::remote_bookmarks())", revision);
+    let output =
+        output_with_retry(command().args(["log", "--no-graph", "-r", &revset, "-T", "commit_id"]))
+            .ok()
+            .filter(|o| o.status.success())?;
+    let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!id.is_empty()).then_some(id)
+}
+
+fn cache_key() -> Option<(Option<PathBuf>, String)> {
+    Some((workspace_root(), op_heads()?))
+}
+
+/// Current operation ids, read from the repo on disk so checking them
+/// doesn't cost a jj invocation
+fn op_heads() -> Option<String> {
+    let jj_dir = workspace_path(".jj");
+    let mut repo = jj_dir.join("repo");
+    // Secondary workspaces store the path to the shared repo instead
+    if repo.is_file() {
+        let target = std::fs::read_to_string(&repo).ok()?;
+        repo = jj_dir.join(target.trim());
+    }
+
+    let mut heads: Vec<String> = std::fs::read_dir(repo.join("op_heads").join("heads"))
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .collect();
+    if heads.is_empty() {
+        return None;
+    }
+    heads.sort();
+    Some(heads.join(","))
+}
+
+/// A mutating jj command running on a background thread
+pub struct Operation {
+    label: String,
+    receiver: Receiver<Result<String, String>>,
+    cancel: CancelToken,
+}
+
+impl Operation {
+    pub fn spawn(label: &str, args: Vec<String>, ctx: &egui::Context) -> Self {
+        let (sender, receiver) = channel();
+        let ctx = ctx.clone();
+        let cancel = CancelToken::default();
+
+        debug!("Starting operation: jj {}", args.join(" "));
+        let token = cancel.clone();
+        thread::spawn(move || {
+            let _ = sender.send(run(&args, &token));
+            ctx.request_repaint();
+        });
+
+        Self {
+            label: label.to_string(),
+            receiver,
+            cancel,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Kill the jj process; `poll` then reports it as cancelled
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Returns the outcome once the command has finished
+    pub fn poll(&self) -> Option<Result<String, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("operation thread exited".to_string())),
+        }
+    }
+
+    /// Block until the command has finished
+    pub fn wait(self) -> Result<String, String> {
+        self.receiver
+            .recv()
+            .unwrap_or_else(|_| Err("operation thread exited".to_string()))
+    }
+}
+
+fn run(args: &[String], cancel: &CancelToken) -> Result<String, String> {
+    let _lock = OPERATION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if cancel.is_cancelled() {
+        return Err("cancelled".to_string());
+    }
+    OPERATION_RUNNING.store(true, Ordering::Release);
+    let output = retrying(
+        workspace_command().args(args),
+        OPERATION_TIMEOUT,
+        Some(cancel),
+    );
+    OPERATION_RUNNING.store(false, Ordering::Release);
+    let output = output.map_err(|e| e.to_string())?;
+
+    // jj reports most of its progress on stderr, even on success
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if output.status.success() {
+        Ok(stderr)
+    } else {
+        Err(stderr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(Version::parse("jj 0.45.1\n"), Some(Version::new(0, 45, 1)));
+        assert_eq!(
+            Version::parse("jj 0.46.0-1f2e3d4c5b6a"),
+            Some(Version::new(0, 46, 0))
+        );
+        assert_eq!(Version::parse("jj 1.2"), None);
+        assert_eq!(Version::parse("jujutsu"), None);
+        assert!(Version::new(0, 9, 0) < OLDEST_SUPPORTED);
+    }
+
+    #[test]
+    fn quotes_root_file_patterns() {
+        if version().is_some_and(|version| version < Version::new(0, 20, 0)) {
+            return;
+        }
+        assert_eq!(root_file("src/main.rs"), r#"root-file:"src/main.rs""#);
+        assert_eq!(
+            root_file(r#"odd "name" [1]\*.txt"#),
+            r#"root-file:"odd \"name\" [1]\\*.txt""#
+        );
+    }
+}
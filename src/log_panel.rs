@@ -0,0 +1,412 @@
+use eframe::egui::{self, RichText, Sense};
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, TryRecvError, channel};
+use std::thread;
+use tracing::debug;
+
+use crate::changed_files::FileStatus;
+use crate::i18n::{tr, trf};
+use crate::jj::{self, CancelToken, DiffSource, FileStat};
+use crate::metadata_panel;
+use crate::theme;
+
+/// Fields are tab separated; the description goes last so tabs in it survive
+const LOG_TEMPLATE: &str = r#"change_id ++ "\t" ++ commit_id ++ "\t" ++ author.name() ++ "\t" ++ description.first_line() ++ "\n""#;
+
+const LOG_LIMIT: &str = "100";
+
+/// Bases for "Compare to", diffed from where `@` branched off them so
+/// the result is the whole branch without what landed there since
+const COMPARE_PRESETS: &[&str] = &["trunk()", "main@origin"];
+
+/// Files listed in the hover tooltip before the rest are summed up
+const TOOLTIP_FILES: usize = 8;
+
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    /// Full IDs, shortened for display
+    pub change_id: String,
+    pub commit_id: String,
+    pub author: String,
+    pub description: String,
+}
+
+/// Revision log with Ctrl-click selection of two commits to compare
+#[derive(Default)]
+pub struct LogPanel {
+    entries: Vec<LogEntry>,
+    loaded: bool,
+    /// The `jj log` still streaming in, if any
+    loading: Option<LogLoad>,
+    /// Selected entry indices, at most two, in click order
+    selected: Vec<usize>,
+    /// Per commit ID, fetched on first hover; commits never change, so this
+    /// outlives refreshes
+    stats: HashMap<String, CommitStats>,
+}
+
+enum CommitStats {
+    Loading(Receiver<Result<Vec<FileStat>, String>>),
+    Loaded(Result<Vec<FileStat>, String>),
+}
+
+/// Entries arrive one by one as `jj log` prints them; dropping this kills
+/// the process
+struct LogLoad {
+    receiver: Receiver<LogEntry>,
+    cancel: CancelToken,
+}
+
+impl Drop for LogLoad {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+impl LogPanel {
+    /// Reloads the next time the log is shown, cancelling any load in flight
+    pub fn refresh(&mut self) {
+        self.loading = None;
+        self.loaded = false;
+    }
+
+    fn start_loading(&mut self, ctx: &egui::Context) {
+        let (sender, receiver) = channel();
+        let cancel = CancelToken::default();
+        let (ctx, token) = (ctx.clone(), cancel.clone());
+        thread::spawn(move || {
+            stream_log(&token, |entry| {
+                let _ = sender.send(entry);
+                ctx.request_repaint();
+            });
+            ctx.request_repaint();
+        });
+        self.loading = Some(LogLoad { receiver, cancel });
+        self.entries.clear();
+        self.selected.clear();
+        self.loaded = true;
+    }
+
+    fn poll_loading(&mut self) {
+        let Some(loading) = &self.loading else {
+            return;
+        };
+        loop {
+            match loading.receiver.try_recv() {
+                Ok(entry) => self.entries.push(entry),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+        self.loading = None;
+    }
+
+    /// Renders the log and returns a new diff source when "Diff selected" is
+    /// clicked. `focused` takes the arrow keys and Enter.
+    pub fn show(&mut self, ui: &mut egui::Ui, focused: bool) -> Option<DiffSource> {
+        if !self.loaded {
+            self.start_loading(ui.ctx());
+        }
+        self.poll_loading();
+
+        let mut source = None;
+        let mut stepped = false;
+        if focused && !ui.ctx().wants_keyboard_input() {
+            (source, stepped) = self.keyboard_step(ui);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(tr("Log"));
+            if self.loading.is_some() {
+                ui.spinner();
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("↻").on_hover_text(tr("Refresh log")).clicked() {
+                    self.refresh();
+                }
+                ui.menu_button(tr("Compare to"), |ui| {
+                    for base in COMPARE_PRESETS {
+                        if ui
+                            .button(RichText::new(*base).monospace())
+                            .on_hover_text(tr("Everything on @ that isn't on this yet"))
+                            .clicked()
+                        {
+                            source = Some(compare_to(base));
+                        }
+                    }
+                });
+                let diffable = self.selected.len() == 2;
+                if ui
+                    .add_enabled(diffable, egui::Button::new(tr("Diff selected")))
+                    .on_hover_text(tr("Ctrl-click two commits to compare them"))
+                    .clicked()
+                {
+                    source = self.selected_range();
+                }
+            });
+        });
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for i in 0..self.entries.len() {
+                    let is_selected = self.selected.contains(&i);
+                    let response = log_entry_item(ui, &self.entries[i], is_selected);
+                    let response = if response.hovered() {
+                        let commit_id = self.entries[i].commit_id.clone();
+                        let stats = self.stats_for(ui.ctx(), &commit_id);
+                        response.on_hover_ui(|ui| stats_tooltip(ui, stats))
+                    } else {
+                        response
+                    };
+
+                    if stepped && self.selected.last() == Some(&i) {
+                        response.scroll_to_me(None);
+                    }
+                    response.context_menu(|ui| {
+                        let entry = &self.entries[i];
+                        if ui.button(tr("Copy change ID")).clicked() {
+                            ui.ctx().copy_text(entry.change_id.clone());
+                        }
+                        if ui.button(tr("Copy commit SHA")).clicked() {
+                            ui.ctx().copy_text(entry.commit_id.clone());
+                        }
+                    });
+                    if response.clicked() {
+                        if ui.input(|input| input.modifiers.command) {
+                            self.toggle(i);
+                        } else {
+                            self.selected = vec![i];
+                        }
+                    }
+                }
+            });
+
+        source
+    }
+
+    /// The commit's stats, starting a background fetch the first time
+    fn stats_for(&mut self, ctx: &egui::Context, commit_id: &str) -> Option<&StatsResult> {
+        let stats = self.stats.entry(commit_id.to_string()).or_insert_with(|| {
+            let (sender, receiver) = channel();
+            let (ctx, commit_id) = (ctx.clone(), commit_id.to_string());
+            thread::spawn(move || {
+                let _ = sender.send(jj::revision_stats(&commit_id));
+                ctx.request_repaint();
+            });
+            CommitStats::Loading(receiver)
+        });
+        if let CommitStats::Loading(receiver) = stats
+            && let Ok(result) = receiver.try_recv()
+        {
+            *stats = CommitStats::Loaded(result);
+        }
+        match stats {
+            CommitStats::Loaded(result) => Some(result),
+            CommitStats::Loading(_) => None,
+        }
+    }
+
+    /// Up and Down move a single selection through the log. Enter diffs the
+    /// two selected commits, or one against its parents. Returns the source
+    /// to show and whether the selection moved.
+    fn keyboard_step(&mut self, ui: &mut egui::Ui) -> (Option<DiffSource>, bool) {
+        if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter)) {
+            let source = match self.selected[..] {
+                [i] => Some(DiffSource::Range {
+                    from: format!("{}-", self.entries[i].commit_id),
+                    to: self.entries[i].commit_id.clone(),
+                }),
+                _ => self.selected_range(),
+            };
+            return (source, false);
+        }
+
+        let down = ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown));
+        let up = ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp));
+        let Some(last) = self.entries.len().checked_sub(1) else {
+            return (None, false);
+        };
+        let row = match self.selected.last() {
+            Some(&row) if down => (row + 1).min(last),
+            Some(&row) if up => row.saturating_sub(1),
+            None if down || up => 0,
+            _ => return (None, false),
+        };
+        self.selected = vec![row];
+        (None, true)
+    }
+
+    fn toggle(&mut self, idx: usize) {
+        if let Some(pos) = self.selected.iter().position(|&i| i == idx) {
+            self.selected.remove(pos);
+        } else {
+            // Keep the most recent pick when a third commit is added
+            if self.selected.len() == 2 {
+                self.selected.remove(0);
+            }
+            self.selected.push(idx);
+        }
+    }
+
+    /// The log is newest first, so the lower entry is the older `from` side
+    fn selected_range(&self) -> Option<DiffSource> {
+        let (&a, &b) = (self.selected.first()?, self.selected.get(1)?);
+        let (from, to) = (a.max(b), a.min(b));
+        Some(DiffSource::Range {
+            from: self.entries[from].commit_id.clone(),
+            to: self.entries[to].commit_id.clone(),
+        })
+    }
+}
+
+/// Everything on @ that isn't on `base` yet
+pub fn compare_to(base: &str) -> DiffSource {
+    DiffSource::Range {
+        from: format!("fork_point({} | @)", base),
+        to: "@".to_string(),
+    }
+}
+
+/// The base of a source made by `compare_to`
+pub fn compared_base(source: &DiffSource) -> Option<&str> {
+    match source {
+        DiffSource::Range { from, to } if to == "@" => {
+            from.strip_prefix("fork_point(")?.strip_suffix(" | @)")
+        }
+        _ => None,
+    }
+}
+
+type StatsResult = Result<Vec<FileStat>, String>;
+
+/// Files changed and line counts, or a loading note while they're fetched
+fn stats_tooltip(ui: &mut egui::Ui, stats: Option<&StatsResult>) {
+    let stats = match stats {
+        None => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(tr("Loading changes..."));
+            });
+            return;
+        }
+        Some(Err(e)) => {
+            ui.label(RichText::new(e).color(FileStatus::Deleted.color()));
+            return;
+        }
+        Some(Ok(stats)) => stats,
+    };
+
+    let added: usize = stats.iter().map(|s| s.added).sum();
+    let removed: usize = stats.iter().map(|s| s.removed).sum();
+    ui.horizontal(|ui| {
+        ui.label(trf("{} files changed", &[&stats.len()]));
+        counts(ui, added, removed);
+    });
+    if stats.is_empty() {
+        return;
+    }
+    ui.separator();
+    egui::Grid::new("log_stats_tooltip").show(ui, |ui| {
+        for stat in stats.iter().take(TOOLTIP_FILES) {
+            ui.label(RichText::new(&stat.path).monospace());
+            ui.horizontal(|ui| counts(ui, stat.added, stat.removed));
+            ui.end_row();
+        }
+    });
+    if stats.len() > TOOLTIP_FILES {
+        ui.label(trf("and {} more", &[&(stats.len() - TOOLTIP_FILES)]));
+    }
+}
+
+fn counts(ui: &mut egui::Ui, added: usize, removed: usize) {
+    ui.label(
+        RichText::new(format!("+{}", added))
+            .monospace()
+            .color(FileStatus::Added.color()),
+    );
+    ui.label(
+        RichText::new(format!("-{}", removed))
+            .monospace()
+            .color(FileStatus::Deleted.color()),
+    );
+}
+
+fn log_entry_item(ui: &mut egui::Ui, entry: &LogEntry, selected: bool) -> egui::Response {
+    let height = 24.0;
+
+    let (rect, response) =
+        ui.allocate_exact_size(egui::vec2(ui.available_width(), height), Sense::click());
+
+    if ui.is_rect_visible(rect) {
+        let bg_color = theme::row_fill(ui.visuals(), selected, response.hovered());
+        ui.painter().rect_filled(rect, 4.0, bg_color);
+
+        let font = egui::TextStyle::Monospace.resolve(ui.style());
+        let id_width = ui.fonts_mut(|f| f.glyph_width(&font, '0')) * 9.0;
+        let mut x = 8.0;
+        for (id, color) in [
+            (&entry.change_id, metadata_panel::CHANGE_ID_COLOR),
+            (&entry.commit_id, metadata_panel::COMMIT_ID_COLOR),
+        ] {
+            ui.painter().text(
+                rect.left_center() + egui::vec2(x, 0.0),
+                egui::Align2::LEFT_CENTER,
+                jj::short_id(id, 8),
+                font.clone(),
+                color,
+            );
+            x += id_width;
+        }
+
+        let description = if entry.description.is_empty() {
+            tr("(no description)")
+        } else {
+            &entry.description
+        };
+        ui.painter().text(
+            rect.left_center() + egui::vec2(x + 8.0, 0.0),
+            egui::Align2::LEFT_CENTER,
+            format!("{}  {}", description, entry.author),
+            font,
+            ui.style().visuals.text_color(),
+        );
+    }
+
+    response
+}
+
+/// Hands each entry to `on_entry` as soon as jj prints it
+fn stream_log(cancel: &CancelToken, mut on_entry: impl FnMut(LogEntry)) {
+    let result = jj::stream(
+        jj::command().args([
+            "log",
+            "--no-graph",
+            "--limit",
+            LOG_LIMIT,
+            "-T",
+            LOG_TEMPLATE,
+        ]),
+        jj::COMMAND_TIMEOUT,
+        Some(cancel),
+        |line| {
+            if let Some(entry) = parse_log_line(line) {
+                on_entry(entry);
+            }
+        },
+    );
+    if let Err(e) = result {
+        debug!("jj log stopped: {}", e);
+    }
+}
+
+fn parse_log_line(line: &str) -> Option<LogEntry> {
+    let mut fields = line.splitn(4, '\t');
+    Some(LogEntry {
+        change_id: fields.next()?.to_string(),
+        commit_id: fields.next()?.to_string(),
+        author: fields.next()?.to_string(),
+        description: fields.next().unwrap_or("").to_string(),
+    })
+}
@@ -1,100 +1,1357 @@
 use eframe::egui;
-use tracing::debug;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tracing::{debug, warn};
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::fmt;
+use tracing_subscriber::fmt::format::FmtSpan;
 
-use crate::changed_files::{ChangedFile, ChangedFilesCache};
-use crate::diff_viewer::{DiffViewMode, DiffViewer};
-use crate::theme::set_rusty_theme;
+use crate::changed_files::{ChangedFile, ChangedFilesCache, FileAction};
+use crate::cli::Location;
+use crate::combined_diff::CombinedDiff;
+use crate::config::{Config, RefreshMode};
+use crate::danger_zone::{DangerAction, DangerDialog};
+use crate::dashboard::{Dashboard, DashboardAction};
+use crate::dialogs::DialogKind;
+use crate::diff_viewer::{DiffViewMode, DiffViewer, DiffViewerAction};
+use crate::export_dialog::ExportDialog;
+use crate::file_colors::FileColors;
+use crate::focus::Focus;
+use crate::git::Colocation;
+use crate::i18n::{tr, trf};
+use crate::log_panel::LogPanel;
+use crate::metadata_panel::MetadataPanel;
+use crate::patch::PatchDialog;
+use crate::popout::Popouts;
+use crate::repo_settings::RepoSettings;
+use crate::restore_dialog::RestoreDialog;
+use crate::review::Review;
+use crate::split_dialog::SplitDialog;
+use crate::stack_review::StackReview;
+use crate::theme::{Appearance, set_rusty_theme};
+use crate::title_bar::{Title, TitleBarAction};
+use crate::watcher::RepoWatcher;
 
 mod changed_files;
+mod cli;
+mod combined_diff;
+mod config;
+mod conflicts;
+mod content_filter;
+mod danger_zone;
+mod dashboard;
+mod dialogs;
 mod diff_viewer;
+mod export_dialog;
+mod file_colors;
+mod focus;
+mod git;
+mod help;
+mod hunk_context;
+mod i18n;
+mod ignore_rules;
+mod jj;
+mod log_panel;
+mod metadata_panel;
+mod notebook;
+mod patch;
+mod popout;
+mod repo_settings;
+mod restore_dialog;
+mod review;
+mod semantic_diff;
+mod split_dialog;
+mod stack_review;
+mod structural_diff;
+mod table_diff;
 mod theme;
 mod title_bar;
+#[cfg(test)]
+mod ui_tests;
+mod watcher;
 
-struct MyApp {
-    selected_file_idx: usize,
+/// Something a pane asked for that needs the app to act on
+enum PaneAction {
+    Dashboard(DashboardAction),
+    Diff(DiffViewerAction),
+}
+
+/// One diff view in the central panel, bound to its own sidebar selection
+struct Pane {
+    selected_file_idx: Option<usize>,
     selected_changed_file: Option<ChangedFile>,
-    changed_files_cache: ChangedFilesCache,
     diff_viewer: DiffViewer,
     diff_view_mode: DiffViewMode,
 }
 
-impl Default for MyApp {
-    fn default() -> Self {
+impl Pane {
+    fn new(config: Arc<Config>) -> Self {
         Self {
-            selected_file_idx: 0,
+            selected_file_idx: None,
             selected_changed_file: None,
-            changed_files_cache: ChangedFilesCache::new(),
-            diff_viewer: DiffViewer::new(),
+            diff_viewer: DiffViewer::new(config),
             diff_view_mode: DiffViewMode::SideBySide,
         }
     }
 }
 
+struct MyApp {
+    config: Arc<Config>,
+    /// One pane, or two when the central panel is split
+    panes: Vec<Pane>,
+    /// Pane the sidebar selection is bound to
+    active_pane: usize,
+    changed_files_cache: ChangedFilesCache,
+    workspaces: Vec<String>,
+    current_workspace: Option<String>,
+    log_panel: LogPanel,
+    /// First line of @'s description for the title bar, fetched on demand
+    working_copy_title: Option<String>,
+    dashboard: Dashboard,
+    /// Shown in the active pane while several files are selected
+    combined_diff: CombinedDiff,
+    metadata_panel: MetadataPanel,
+    show_metadata: bool,
+    review: Review,
+    stack_review: StackReview,
+    /// What was last saved for the current workspace root
+    repo_settings: RepoSettings,
+    /// Mutating jj command currently in flight, if any
+    operation: Option<jj::Operation>,
+    /// (label, args) of operations started while another one was running
+    queued_operations: VecDeque<(String, Vec<String>)>,
+    /// Outcome of the last finished operation, shown in the status bar
+    operation_result: Option<Result<String, String>>,
+    show_operation_output: bool,
+    patch_dialog: Option<PatchDialog>,
+    danger_dialog: Option<DangerDialog>,
+    split_dialog: Option<SplitDialog>,
+    restore_dialog: Option<RestoreDialog>,
+    export_dialog: Option<ExportDialog>,
+    /// Last export's choices, preset from `config.toml`
+    patch_format: jj::PatchFormat,
+    /// The `?` legend and shortcut overlay
+    show_help: bool,
+    confirm_close: bool,
+    close_after_operation: bool,
+    /// UI scale and theme, adjustable from the menu
+    appearance: Appearance,
+    /// `--goto` target, opened once the changed files are known
+    goto: Option<Location>,
+    /// Collapsed when started on a single file
+    show_sidebar: bool,
+    /// Window focus last frame, for `refresh = "focus"`
+    was_focused: bool,
+    /// For `refresh = "watch"`, following the current workspace root
+    watcher: Option<RepoWatcher>,
+    /// Whether the current workspace shares its working copy with git
+    colocation: Option<Colocation>,
+    /// Diffs popped out into their own windows
+    popouts: Popouts,
+    /// Pane the arrow keys and other keyboard actions apply to
+    keyboard_focus: Focus,
+    /// Tab press taken from egui's input, true with Shift
+    pending_tab: Option<bool>,
+}
+
+impl MyApp {
+    fn new(config: Config, goto: Option<Location>, focus: bool) -> Self {
+        let config = Arc::new(config);
+        let appearance = Appearance {
+            ui_scale: config.ui_scale.clamp(0.75, 2.0),
+            theme: config.theme,
+        };
+        let patch_format = jj::PatchFormat {
+            context: config.patch_context_lines,
+            git_headers: config.patch_git_headers,
+        };
+        let mut app = Self {
+            panes: vec![Pane::new(config.clone())],
+            active_pane: 0,
+            changed_files_cache: ChangedFilesCache::new(
+                config.exclude.clone(),
+                config.show_dotfiles,
+                FileColors::new(&config.file_colors),
+            ),
+            config,
+            workspaces: jj::workspaces(),
+            current_workspace: jj::current_workspace(),
+            log_panel: LogPanel::default(),
+            working_copy_title: None,
+            dashboard: Dashboard::default(),
+            combined_diff: CombinedDiff::default(),
+            metadata_panel: MetadataPanel::default(),
+            show_metadata: false,
+            review: Review::default(),
+            stack_review: StackReview::default(),
+            repo_settings: RepoSettings::default(),
+            operation: None,
+            queued_operations: VecDeque::new(),
+            operation_result: None,
+            show_operation_output: false,
+            patch_dialog: None,
+            danger_dialog: None,
+            split_dialog: None,
+            restore_dialog: None,
+            export_dialog: None,
+            patch_format,
+            show_help: false,
+            confirm_close: false,
+            close_after_operation: false,
+            appearance,
+            goto,
+            show_sidebar: !focus,
+            was_focused: true,
+            watcher: None,
+            colocation: None,
+            popouts: Popouts::default(),
+            keyboard_focus: Focus::default(),
+            pending_tab: None,
+        };
+        app.load_repo_settings();
+        app
+    }
+}
+
 impl eframe::App for MyApp {
+    /// Tab cycles panes rather than widgets, except while typing in a text
+    /// field other than the find bar
+    fn raw_input_hook(&mut self, ctx: &egui::Context, raw_input: &mut egui::RawInput) {
+        let typing = ctx
+            .memory(|m| m.focused())
+            .is_some_and(|id| egui::text_edit::TextEditState::load(ctx, id).is_some());
+        if (!typing || self.keyboard_focus == Focus::Search)
+            && let Some(backwards) = focus::take_tab(raw_input)
+        {
+            self.pending_tab = Some(backwards);
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        set_rusty_theme(ctx);
+        set_rusty_theme(ctx, self.appearance.theme);
+        self.apply_ui_scale(ctx);
+
+        self.poll_operation(ctx);
+        self.auto_refresh(ctx);
+
+        // Closing mid-operation would leave jj's working copy half-updated
+        if ctx.input(|i| i.viewport().close_requested()) && self.operation.is_some() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.confirm_close = true;
+        }
 
         // Top title bar
-        egui::TopBottomPanel::top("title_bar").show(ctx, |ui| {
-            title_bar::show(ui, "le diff");
-        });
+        let title_action = egui::TopBottomPanel::top("title_bar")
+            .show(ctx, |ui| {
+                let description = self.working_copy_title.get_or_insert_with(|| {
+                    jj::description("@")
+                        .lines()
+                        .next()
+                        .unwrap_or("")
+                        .to_string()
+                });
+                let repo = jj::workspace_root()
+                    .and_then(|root| Some(root.file_name()?.to_string_lossy().into_owned()))
+                    .unwrap_or_default();
+                let selected = self.panes[self.active_pane].selected_changed_file.as_ref();
+                title_bar::show(
+                    ui,
+                    &Title {
+                        name: "le diff",
+                        description,
+                        repo: &repo,
+                        directory: self.changed_files_cache.directory(),
+                        path: selected.map(|file| file.path.as_str()),
+                    },
+                    self.operation.is_some(),
+                    &self.workspaces,
+                    self.current_workspace.as_deref(),
+                    &mut self.appearance,
+                    self.config.read_only,
+                )
+            })
+            .inner;
+
+        let mut title_source = None;
+        match title_action {
+            Some(TitleBarAction::Fetch) => {
+                let args = vec!["git".to_string(), "fetch".to_string()];
+                self.start_operation(ctx, tr("Fetch"), args);
+            }
+            Some(TitleBarAction::Push) => {
+                // Pushes tracked bookmarks between the remote and @
+                let args = vec!["git".to_string(), "push".to_string()];
+                self.start_operation(ctx, tr("Push"), args);
+            }
+            Some(TitleBarAction::PastePatch) => self.open_patch_from_clipboard(),
+            Some(TitleBarAction::Split) => self.split_dialog = Some(SplitDialog::new()),
+            Some(TitleBarAction::Danger(action)) => {
+                self.danger_dialog = Some(DangerDialog::new(action));
+            }
+            Some(TitleBarAction::ToggleSplit) => self.toggle_split(),
+            Some(TitleBarAction::ToggleMetadata) => self.show_metadata = !self.show_metadata,
+            Some(TitleBarAction::ToggleSidebar) => self.show_sidebar = !self.show_sidebar,
+            Some(TitleBarAction::ReviewSummary) => self.review.open = true,
+            Some(TitleBarAction::StackReview) => self.stack_review.start(),
+            Some(TitleBarAction::CompareSnapshot) => {
+                title_source = Some(jj::DiffSource::Unsnapshotted);
+            }
+            Some(TitleBarAction::Help) => self.show_help = true,
+            // Clicking the title's description opens the dashboard's editor for @
+            Some(TitleBarAction::EditDescription) => {
+                title_source = Some(jj::DiffSource::WorkingCopy)
+                    .filter(|source| source != self.changed_files_cache.source());
+                self.panes[0].selected_file_idx = None;
+                self.active_pane = 0;
+                self.dashboard.focus_description();
+            }
+            Some(TitleBarAction::FilterDirectory(directory)) => {
+                self.changed_files_cache.set_directory(directory);
+                self.show_sidebar = true;
+            }
+            Some(TitleBarAction::OpenRepository) => self.open_repository(),
+            Some(TitleBarAction::SavePatch) => {
+                self.export_dialog = Some(ExportDialog::new(Vec::new(), self.patch_format));
+            }
+            Some(TitleBarAction::SwitchWorkspace(name)) => self.switch_workspace(name),
+            Some(TitleBarAction::SaveUiScale) => {
+                if let Err(e) = config::save_ui_scale(self.appearance.ui_scale) {
+                    self.operation_result = Some(Err(trf("Save UI scale: {}", &[&e])));
+                }
+            }
+            Some(TitleBarAction::SaveTheme) => {
+                if let Err(e) = config::save_theme(self.appearance.theme) {
+                    self.operation_result = Some(Err(trf("Save theme: {}", &[&e])));
+                }
+            }
+            Some(TitleBarAction::Close) => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            None => {}
+        }
+
+        self.show_status_bar(ctx);
+        self.show_close_dialog(ctx);
+        self.show_patch_dialog(ctx);
+        self.show_danger_dialog(ctx);
+        self.show_split_dialog(ctx);
+        self.show_restore_dialog(ctx);
+        self.show_export_dialog(ctx);
+        self.show_review(ctx);
+        if help::toggle_requested(ctx) {
+            self.show_help = !self.show_help;
+        }
+        help::show(ctx, &mut self.show_help);
+        self.move_focus(ctx);
 
-        // Track previous selection to detect changes
-        let prev_selection = self.selected_file_idx;
+        // Track previous selections to detect changes
+        let prev_selections: Vec<_> = self.panes.iter().map(|p| p.selected_file_idx).collect();
         let mut refresh_requested = false;
 
+        // Revision log (BOTTOM)
+        let log_panel = egui::TopBottomPanel::bottom("log_panel")
+            .resizable(true)
+            .default_height(180.0)
+            .show(ctx, |ui| {
+                let focused = self.focus_pane(ui, Focus::Log);
+                self.log_panel.show(ui, focused)
+            });
+        self.focus_ring(ctx, Focus::Log, log_panel.response.rect);
+        let log_source = log_panel.inner;
+
+        // Revision details (RIGHT)
+        let metadata_source = egui::SidePanel::right("metadata_panel")
+            .resizable(true)
+            .default_width(260.0)
+            .show_animated(ctx, self.show_metadata, |ui| {
+                let source = self.changed_files_cache.source().clone();
+                self.metadata_panel.show(ui, &source)
+            })
+            .and_then(|response| response.inner);
+
+        // Stack review (LEFT of the sidebar)
+        let stack_source = egui::SidePanel::left("stack_review")
+            .resizable(true)
+            .default_width(280.0)
+            .show_animated(ctx, self.stack_review.open, |ui| {
+                let source = self.changed_files_cache.source().clone();
+                self.stack_review.show(ui, &source)
+            })
+            .and_then(|response| response.inner);
+
+        if let Some(source) = log_source
+            .or(metadata_source)
+            .or(stack_source)
+            .or(title_source)
+        {
+            self.changed_files_cache.set_source(source);
+            for pane in &mut self.panes {
+                pane.selected_file_idx = None;
+            }
+            refresh_requested = true;
+        }
+
         // Sidebar (LEFT)
-        egui::SidePanel::left("sidebar")
+        let sidebar = egui::SidePanel::left("sidebar")
             .resizable(false)
             .default_width(220.0)
             .frame(
                 egui::Frame::new()
                     // slightly lighter than central panel
-                    .fill(egui::Color32::from_rgb(36, 38, 43))
+                    .fill(theme::sidebar_fill(&ctx.style().visuals))
                     .inner_margin(egui::Margin::symmetric(16, 20)),
             )
-            .show(ctx, |ui| {
-                let (selected_file, refreshed) = changed_files::show(
+            .show_animated(ctx, self.show_sidebar, |ui| {
+                let focused = self.focus_pane(ui, Focus::Sidebar);
+                let (_, refreshed, file_action) = changed_files::show(
                     ui,
                     &mut self.changed_files_cache,
-                    &mut self.selected_file_idx,
+                    &mut self.panes[self.active_pane].selected_file_idx,
+                    &mut self.review,
+                    self.config.read_only,
+                    focused,
                 );
-                self.selected_changed_file = selected_file;
-                refresh_requested = refreshed;
+                refresh_requested |= refreshed;
+                file_action
             });
+        if let Some(sidebar) = &sidebar {
+            self.focus_ring(ctx, Focus::Sidebar, sidebar.response.rect);
+        }
+        let file_action = sidebar.and_then(|response| response.inner);
 
-        // Invalidate diff cache if selection changed or refresh requested
-        if prev_selection != self.selected_file_idx || refresh_requested {
-            self.diff_viewer.invalidate_cache();
+        if let Some(action) = file_action {
+            self.handle_file_action(ctx, action);
         }
+        self.open_goto();
+
+        let source = self.changed_files_cache.source().clone();
+        let visible = self.changed_files_cache.visible().to_vec();
+        let files = self.changed_files_cache.get_files();
+        for (pane, prev_selection) in self.panes.iter_mut().zip(prev_selections) {
+            // Indices may point elsewhere after a refresh, so re-resolve every pane
+            pane.selected_changed_file = pane.selected_file_idx.and_then(|i| files.get(i)).cloned();
+
+            // The sidebar decides what is being compared; the viewer follows it
+            pane.diff_viewer.set_source(source.clone());
+
+            // A refresh drops every cached diff; another file only clears the view
+            if refresh_requested {
+                pane.diff_viewer.invalidate_cache();
+            } else if prev_selection != pane.selected_file_idx {
+                pane.diff_viewer.reset();
+            }
+            if let Some(i) = pane.selected_file_idx {
+                pane.diff_viewer.prefetch_around(files, &visible, i, ctx);
+            }
+        }
+        if refresh_requested {
+            self.working_copy_title = None;
+            self.popouts.invalidate();
+            self.stack_review.invalidate();
+            self.dashboard.invalidate();
+            self.combined_diff.invalidate();
+            self.metadata_panel.invalidate();
+        }
+
+        let path = self.panes[self.active_pane]
+            .selected_changed_file
+            .as_ref()
+            .map(|f| f.path.as_str());
+        self.review.tick(ctx, &source, path);
 
         // Main content
-        egui::CentralPanel::default()
+        let mut pane_actions = Vec::new();
+        let central = egui::CentralPanel::default()
             .frame(
                 egui::Frame::new()
-                    .fill(egui::Color32::from_rgb(30, 32, 36))
+                    .fill(ctx.style().visuals.window_fill)
                     .inner_margin(egui::Margin::symmetric(24, 20)),
             )
             .show(ctx, |ui| {
-                self.diff_viewer.show(
+                self.focus_pane(ui, Focus::Diff);
+                if self.panes.len() == 1 {
+                    pane_actions.extend(self.show_pane(ui, 0));
+                    return;
+                }
+
+                egui::TopBottomPanel::top("split_top_pane")
+                    .resizable(true)
+                    .default_height(ui.available_height() / 2.0)
+                    .frame(egui::Frame::new().inner_margin(egui::Margin {
+                        bottom: 12,
+                        ..Default::default()
+                    }))
+                    .show_inside(ui, |ui| {
+                        pane_actions.extend(self.show_pane(ui, 0));
+                    });
+                ui.add_space(12.0);
+                pane_actions.extend(self.show_pane(ui, 1));
+            });
+        self.focus_ring(ctx, Focus::Diff, central.response.rect);
+        pane_actions.extend(self.popouts.show(ctx).into_iter().map(PaneAction::Diff));
+
+        for action in pane_actions {
+            self.handle_pane_action(ctx, action);
+        }
+        self.sync_focus();
+        self.save_repo_settings();
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // Never leave jj mid-operation, even if the window was torn down anyway
+        if let Some(op) = self.operation.take() {
+            debug!("Waiting for \"{}\" before exiting", op.label());
+            let _ = op.wait();
+        }
+    }
+}
+
+impl MyApp {
+    /// Run a mutating jj command, or queue it behind the one running
+    fn start_operation(&mut self, ctx: &egui::Context, label: &str, args: Vec<String>) {
+        if self.operation.is_some() {
+            self.queued_operations.push_back((label.to_string(), args));
+        } else {
+            self.operation = Some(jj::Operation::spawn(label, args, ctx));
+            self.operation_result = None;
+        }
+    }
+
+    /// Tab and Shift+Tab step through the panes, and Ctrl+F jumps to the
+    /// find bar
+    fn move_focus(&mut self, ctx: &egui::Context) {
+        let has_file = self.panes[self.active_pane].selected_changed_file.is_some();
+        let mut next = self.pending_tab.take().map(|backwards| {
+            self.keyboard_focus.cycle(backwards, |focus| match focus {
+                Focus::Sidebar => self.show_sidebar,
+                Focus::Search => has_file,
+                Focus::Diff | Focus::Log => true,
+            })
+        });
+        if has_file && ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::F)) {
+            next = Some(Focus::Search);
+        }
+        let Some(next) = next else {
+            return;
+        };
+
+        // Leaving the find bar takes the cursor out of its field
+        if self.keyboard_focus == Focus::Search && next != Focus::Search {
+            ctx.memory_mut(|m| {
+                if let Some(id) = m.focused() {
+                    m.surrender_focus(id);
+                }
+            });
+        }
+        if next == Focus::Search {
+            self.panes[self.active_pane].diff_viewer.open_find();
+        }
+        self.keyboard_focus = next;
+    }
+
+    /// Clicking into a pane focuses it. Returns whether `focus` is what has
+    /// focus now.
+    fn focus_pane(&mut self, ui: &egui::Ui, focus: Focus) -> bool {
+        if focus::clicked_in(ui, ui.clip_rect()) {
+            self.keyboard_focus = focus;
+        }
+        self.keyboard_focus == focus
+    }
+
+    /// Outline the panel at `rect` if `pane` has focus
+    fn focus_ring(&self, ctx: &egui::Context, pane: Focus, rect: egui::Rect) {
+        if self.keyboard_focus.pane() == pane {
+            focus::ring(ctx, rect);
+        }
+    }
+
+    /// Follow the find field gaining or losing focus, and leave panes that
+    /// were hidden
+    fn sync_focus(&mut self) {
+        let viewer = &self.panes[self.active_pane].diff_viewer;
+        if viewer.find_focused() {
+            self.keyboard_focus = Focus::Search;
+        } else if (self.keyboard_focus == Focus::Search && !viewer.find_open())
+            || (self.keyboard_focus == Focus::Sidebar && !self.show_sidebar)
+        {
+            self.keyboard_focus = Focus::Diff;
+        }
+    }
+
+    /// Select the `--goto` file in the active pane and scroll to its line
+    fn open_goto(&mut self) {
+        let Some(goto) = self.goto.take() else {
+            return;
+        };
+        let files = self.changed_files_cache.get_files();
+        match files.iter().position(|f| f.path == goto.path) {
+            Some(i) => {
+                let pane = &mut self.panes[self.active_pane];
+                pane.selected_file_idx = Some(i);
+                if let Some(line) = goto.line {
+                    pane.diff_viewer.goto_line(line);
+                }
+            }
+            None => {
+                self.operation_result = Some(Err(trf("{} has no changes", &[&goto.path])));
+            }
+        }
+    }
+
+    /// The OS scale factor is read every frame so moving the window to
+    /// another monitor keeps the same relative size
+    fn apply_ui_scale(&self, ctx: &egui::Context) {
+        let native = ctx.native_pixels_per_point().unwrap_or(1.0);
+        let target = native * self.appearance.ui_scale;
+        if (ctx.pixels_per_point() - target).abs() > f32::EPSILON {
+            ctx.set_pixels_per_point(target);
+        }
+    }
+
+    fn toggle_split(&mut self) {
+        if self.panes.len() > 1 {
+            self.panes.truncate(1);
+        } else {
+            self.panes.push(Pane::new(self.config.clone()));
+        }
+        self.active_pane = self.panes.len() - 1;
+    }
+
+    /// Draws a pane, making it active when clicked. Only the first pane
+    /// hosts the dashboard; others just prompt for a file.
+    fn show_pane(&mut self, ui: &mut egui::Ui, idx: usize) -> Option<PaneAction> {
+        let split = self.panes.len() > 1;
+        let rect = ui.max_rect();
+
+        if split && ui.input(|i| i.pointer.primary_pressed()) && ui.rect_contains_pointer(rect) {
+            self.active_pane = idx;
+        }
+
+        let multi_selection = self.changed_files_cache.multi_selection();
+        let pane = &mut self.panes[idx];
+        let action = match (&pane.selected_changed_file, idx) {
+            _ if idx == self.active_pane && !multi_selection.is_empty() => {
+                let source = self.changed_files_cache.source();
+                self.combined_diff.show(ui, source, &multi_selection);
+                None
+            }
+            (None, 0) => {
+                let source = self.changed_files_cache.source();
+                let busy = self.operation.is_some();
+                self.dashboard
+                    .show(ui, source, busy, self.config.read_only)
+                    .map(PaneAction::Dashboard)
+            }
+            (file, _) => pane
+                .diff_viewer
+                .show(
                     ui,
-                    self.selected_changed_file.as_ref(),
-                    &mut self.diff_view_mode,
-                );
+                    file.as_ref(),
+                    &mut pane.diff_view_mode,
+                    idx == self.active_pane && self.keyboard_focus == Focus::Diff,
+                )
+                .map(PaneAction::Diff),
+        };
+
+        if split && self.active_pane == idx {
+            ui.painter().rect_stroke(
+                rect.expand(4.0),
+                4.0,
+                ui.visuals().selection.stroke,
+                egui::StrokeKind::Outside,
+            );
+        }
+
+        action
+    }
+
+    fn handle_pane_action(&mut self, ctx: &egui::Context, action: PaneAction) {
+        match action {
+            PaneAction::Dashboard(DashboardAction::Describe(message)) => {
+                let args = vec!["describe".to_string(), "-m".to_string(), message];
+                self.start_operation(ctx, tr("Describe"), args);
+            }
+            PaneAction::Dashboard(DashboardAction::Squash) => {
+                self.start_operation(ctx, tr("Squash into parent"), vec!["squash".to_string()]);
+            }
+            PaneAction::Dashboard(DashboardAction::New) => {
+                self.start_operation(ctx, tr("New change"), vec!["new".to_string()]);
+            }
+            PaneAction::Diff(DiffViewerAction::Restore(path)) => {
+                let label = trf("Restore {}", &[&path]);
+                let args = vec!["restore".to_string(), jj::root_file(&path)];
+                self.start_operation(ctx, &label, args);
+            }
+            PaneAction::Diff(DiffViewerAction::Comment { path, line }) => {
+                self.review.start_comment(&path, Some(line));
+            }
+            PaneAction::Diff(DiffViewerAction::RevertHunk(patch)) => {
+                // Edits the file directly once confirmed; jj snapshots it on the next command
+                self.patch_dialog = Some(PatchDialog::revert(patch));
+            }
+        }
+    }
+
+    fn handle_file_action(&mut self, ctx: &egui::Context, action: FileAction) {
+        match action {
+            FileAction::Revert(paths) => {
+                self.danger_dialog = Some(DangerDialog::new(DangerAction::Revert(paths)));
+            }
+            FileAction::Squash(paths) => {
+                let label = trf("Squash {} files into parent", &[&paths.len()]);
+                let args = std::iter::once("squash".to_string())
+                    .chain(paths.iter().map(|path| jj::root_file(path)))
+                    .collect();
+                self.start_operation(ctx, &label, args);
+            }
+            FileAction::ExportPatch(paths) => {
+                self.export_dialog = Some(ExportDialog::new(paths, self.patch_format));
+            }
+            FileAction::RestoreFrom(path) => self.restore_dialog = Some(RestoreDialog::new(path)),
+            FileAction::OpenOnForge(path) => self.open_on_forge(ctx, &path),
+            FileAction::PopOut(path) => {
+                let cache = &mut self.changed_files_cache;
+                let source = cache.source().clone();
+                if let Some(file) = cache.get_files().iter().find(|f| f.path == path) {
+                    self.popouts.open(self.config.clone(), file.clone(), source);
+                }
+            }
+            FileAction::Track(paths) => {
+                let label = trf("Track {} paths", &[&paths.len()]);
+                let args = jj::file_command("track")
+                    .into_iter()
+                    .map(str::to_string)
+                    .chain(paths.iter().map(|path| jj::root_file(path)));
+                self.start_operation(ctx, &label, args.collect());
+            }
+            FileAction::Untrack(paths) => {
+                let label = trf("Untrack {} files", &[&paths.len()]);
+                let args = jj::file_command("untrack")
+                    .into_iter()
+                    .map(str::to_string)
+                    .chain(paths.iter().map(|path| jj::root_file(path)));
+                self.start_operation(ctx, &label, args.collect());
+            }
+        }
+    }
+
+    /// Open `path` on the forge at the newest commit it has from the
+    /// revision being diffed; unpushed commits wouldn't be found there
+    fn open_on_forge(&mut self, ctx: &egui::Context, path: &str) {
+        let revision = self
+            .changed_files_cache
+            .source()
+            .new_revision()
+            .unwrap_or("@");
+        let url = git::remote_url()
+            .ok_or_else(|| tr("No git remote to open the file on").to_string())
+            .and_then(|remote| {
+                let commit = jj::pushed_commit(revision)
+                    .ok_or_else(|| tr("No commit of this change is on a remote yet").to_string())?;
+                git::forge_url(&remote, &commit, path)
+                    .ok_or_else(|| trf("Can't tell the web address of remote {}", &[&remote]))
             });
+        match url {
+            Ok(url) => ctx.open_url(egui::OpenUrl::new_tab(url)),
+            Err(e) => self.operation_result = Some(Err(e)),
+        }
+    }
+
+    /// Refresh on regaining focus or after file writes, per `refresh`.
+    /// Skipped mid-operation, which refreshes when it finishes anyway.
+    fn auto_refresh(&mut self, ctx: &egui::Context) {
+        let changed = match self.config.refresh {
+            RefreshMode::Manual => false,
+            RefreshMode::Focus => {
+                let focused = ctx.input(|i| i.viewport().focused.unwrap_or(true));
+                let regained = focused && !self.was_focused;
+                self.was_focused = focused;
+                regained
+            }
+            RefreshMode::Watch => {
+                let root = jj::workspace_root();
+                if self.watcher.as_ref().map(|w| w.root()) != root.as_deref() {
+                    self.watcher = root.map(|root| RepoWatcher::new(&root, ctx));
+                }
+                self.watcher.as_mut().is_some_and(|w| w.poll(ctx))
+            }
+        };
+        if changed && self.operation.is_none() {
+            self.refresh_all();
+        }
+    }
+
+    fn poll_operation(&mut self, ctx: &egui::Context) {
+        let Some(result) = self.operation.as_ref().and_then(|op| op.poll()) else {
+            return;
+        };
+
+        self.operation = None;
+        // Queued commands were meant for the state before a failure, so
+        // they're dropped rather than run against something unexpected
+        if result.is_err() {
+            self.queued_operations.clear();
+        }
+        self.operation_result = Some(result);
+        self.refresh_all();
+
+        if let Some((label, args)) = self.queued_operations.pop_front() {
+            self.start_operation(ctx, &label, args);
+        } else if self.close_after_operation {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+    }
+
+    /// Point every jj command and file read at another workspace's root
+    fn switch_workspace(&mut self, name: String) {
+        match jj::workspace_root_for(&name) {
+            Ok(root) => {
+                jj::set_workspace_root(Some(root));
+                self.current_workspace = Some(name);
+                self.load_repo_settings();
+                for pane in &mut self.panes {
+                    pane.selected_file_idx = None;
+                }
+                self.refresh_all();
+            }
+            Err(e) => self.operation_result = Some(Err(trf("Switch workspace: {}", &[&e]))),
+        }
+    }
+
+    fn open_repository(&mut self) {
+        let Some(dir) = dialogs::pick_folder(DialogKind::OpenRepository, tr("Open repository"))
+        else {
+            return;
+        };
+        let Some(root) = jj::find_repo_root(&dir) else {
+            self.operation_result = Some(Err(trf("{} is not in a repository", &[&dir.display()])));
+            return;
+        };
+        jj::set_workspace_root(Some(root));
+        self.workspaces = jj::workspaces();
+        self.current_workspace = jj::current_workspace();
+        self.load_repo_settings();
+        for pane in &mut self.panes {
+            pane.selected_file_idx = None;
+        }
+        self.refresh_all();
+    }
+
+    /// Apply the settings saved for the current workspace root, or the
+    /// defaults from `config.toml`, and go to the remembered base
+    fn load_repo_settings(&mut self) {
+        let settings = jj::workspace_root()
+            .map(|root| RepoSettings::load(&root))
+            .unwrap_or_default();
+        let exclude = self
+            .config
+            .exclude
+            .iter()
+            .chain(&settings.exclude)
+            .cloned()
+            .collect();
+        let show_dotfiles = settings.show_dotfiles.unwrap_or(self.config.show_dotfiles);
+        self.changed_files_cache
+            .set_preferences(exclude, show_dotfiles);
+        self.review.set_states(settings.review.clone());
+        self.stack_review
+            .set_reviewed(settings.reviewed_commits.clone());
+        let source = settings
+            .base
+            .as_deref()
+            .map(log_panel::compare_to)
+            .unwrap_or(jj::DiffSource::WorkingCopy);
+        self.changed_files_cache.set_source(source);
+        self.repo_settings = settings;
     }
+
+    /// Save the per-repo settings when something in them changed
+    fn save_repo_settings(&mut self) {
+        let Some(root) = jj::workspace_root() else {
+            return;
+        };
+        let cache = &self.changed_files_cache;
+        // Other ranges and snapshots keep the base; the working copy drops it
+        let base = match cache.source() {
+            jj::DiffSource::WorkingCopy => None,
+            source => log_panel::compared_base(source)
+                .map(str::to_string)
+                .or_else(|| self.repo_settings.base.clone()),
+        };
+        let exclude: Vec<String> = cache
+            .exclude_patterns()
+            .iter()
+            .filter(|pattern| !self.config.exclude.contains(pattern))
+            .cloned()
+            .collect();
+        let show_dotfiles =
+            Some(cache.show_dotfiles()).filter(|&shown| shown != self.config.show_dotfiles);
+        let settings = &self.repo_settings;
+        if settings.base == base
+            && settings.exclude == exclude
+            && settings.show_dotfiles == show_dotfiles
+            && &settings.review == self.review.states()
+            && &settings.reviewed_commits == self.stack_review.reviewed()
+        {
+            return;
+        }
+
+        self.repo_settings = RepoSettings {
+            exclude,
+            base,
+            show_dotfiles,
+            review: self.review.states().clone(),
+            reviewed_commits: self.stack_review.reviewed().clone(),
+        };
+        if let Err(e) = self.repo_settings.save(&root) {
+            warn!("Couldn't save settings for {}: {}", root.display(), e);
+        }
+    }
+
+    /// Only `paths` when given, otherwise every changed file
+    fn save_patch(&mut self, paths: &[String]) {
+        let source = self.changed_files_cache.source().clone();
+        let patch = match jj::export_patch(&source, paths, self.patch_format) {
+            Ok(patch) => patch,
+            Err(e) => {
+                self.operation_result = Some(Err(trf("Save patch: {}", &[&e])));
+                return;
+            }
+        };
+        let Some(path) = dialogs::save_file(
+            DialogKind::SavePatch,
+            tr("Save patch"),
+            "changes.patch",
+            ("Patch", &["patch", "diff"]),
+        ) else {
+            return;
+        };
+        self.operation_result = Some(
+            std::fs::write(&path, patch)
+                .map(|()| trf("Saved {}", &[&path.display()]))
+                .map_err(|e| trf("Save patch: {}", &[&e])),
+        );
+    }
+
+    /// Refetch everything after the repository was changed
+    fn refresh_all(&mut self) {
+        self.changed_files_cache.refresh();
+        for pane in &mut self.panes {
+            pane.diff_viewer.invalidate_cache();
+        }
+        self.log_panel.refresh();
+        self.working_copy_title = None;
+        self.popouts.invalidate();
+        self.stack_review.invalidate();
+        self.dashboard.invalidate();
+        self.combined_diff.invalidate();
+        self.metadata_panel.invalidate();
+    }
+
+    fn open_patch_from_clipboard(&mut self) {
+        let text = arboard::Clipboard::new().and_then(|mut c| c.get_text());
+        match text
+            .map_err(|e| e.to_string())
+            .and_then(|t| patch::parse(&t))
+        {
+            Ok(patch) => self.patch_dialog = Some(PatchDialog::new(patch)),
+            Err(e) => self.operation_result = Some(Err(trf("Paste patch: {}", &[&e]))),
+        }
+    }
+
+    fn show_patch_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.patch_dialog else {
+            return;
+        };
+
+        let (keep_open, applied) = dialog.show(ctx);
+        if !keep_open {
+            self.patch_dialog = None;
+        }
+        if applied {
+            self.refresh_all();
+        }
+    }
+
+    /// Confirmed actions run as an operation, which refreshes everything
+    fn show_danger_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &self.danger_dialog else {
+            return;
+        };
+
+        match dialog.show(ctx) {
+            Some(true) => {
+                let (label, args) = (dialog.label(), dialog.args());
+                self.danger_dialog = None;
+                self.start_operation(ctx, &label, args);
+            }
+            Some(false) => self.danger_dialog = None,
+            None => {}
+        }
+    }
+
+    fn show_split_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.split_dialog else {
+            return;
+        };
+
+        match dialog.show(ctx) {
+            Some(true) => {
+                let args = dialog.args();
+                self.split_dialog = None;
+                self.start_operation(ctx, tr("Split change"), args);
+            }
+            Some(false) => self.split_dialog = None,
+            None => {}
+        }
+    }
+
+    fn show_restore_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.restore_dialog else {
+            return;
+        };
+
+        match dialog.show(ctx) {
+            Some(true) => {
+                let (label, args) = (dialog.label(), dialog.args());
+                self.restore_dialog = None;
+                self.start_operation(ctx, &label, args);
+            }
+            Some(false) => self.restore_dialog = None,
+            None => {}
+        }
+    }
+
+    fn show_export_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.export_dialog else {
+            return;
+        };
+
+        match dialog.show(ctx) {
+            Some(true) => {
+                let dialog = self.export_dialog.take().unwrap();
+                self.patch_format = dialog.format;
+                self.save_patch(&dialog.paths);
+            }
+            Some(false) => self.export_dialog = None,
+            None => {}
+        }
+    }
+
+    fn show_review(&mut self, ctx: &egui::Context) {
+        if !self.review.open {
+            return;
+        }
+        let source = self.changed_files_cache.source().clone();
+        let files = self.changed_files_cache.get_files();
+        self.review.show(ctx, &source, files);
+    }
+
+    fn show_status_bar(&mut self, ctx: &egui::Context) {
+        let mut update_stale = false;
+        let mut open_path = None;
+        let root = jj::workspace_root();
+        if self.colocation.as_ref().map(Colocation::root) != Some(root.as_deref()) {
+            self.colocation = Some(Colocation::detect(ctx));
+        }
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if let Some(op) = &self.operation {
+                    ui.spinner().on_hover_text(tr(
+                        "Operation in progress; refreshes show the last snapshot until it finishes",
+                    ));
+                    ui.label(format!("{}...", op.label()));
+                    if ui
+                        .small_button("✕")
+                        .on_hover_text(tr("Stop this jj command"))
+                        .clicked()
+                    {
+                        op.cancel();
+                    }
+                    if !self.queued_operations.is_empty() {
+                        let queued: Vec<&str> = self
+                            .queued_operations
+                            .iter()
+                            .map(|(label, _)| label.as_str())
+                            .collect();
+                        ui.label(
+                            egui::RichText::new(trf("{} queued", &[&queued.len()]))
+                                .color(theme::dim_text(ui.visuals())),
+                        )
+                        .on_hover_text(queued.join("\n"));
+                    }
+                } else if let Some(result) = &self.operation_result {
+                    let (output, color) = match result {
+                        Ok(output) => (output, ui.visuals().text_color()),
+                        Err(output) => (output, egui::Color32::from_rgb(220, 80, 80)),
+                    };
+
+                    // Only the last line fits here; the rest is behind "Output"
+                    ui.colored_label(color, output.lines().last().unwrap_or(""));
+                    if output.lines().count() > 1
+                        && ui
+                            .selectable_label(self.show_operation_output, tr("Output"))
+                            .clicked()
+                    {
+                        self.show_operation_output = !self.show_operation_output;
+                    }
+                }
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let source = self.changed_files_cache.source();
+                    let path = self.panes[self.active_pane]
+                        .selected_changed_file
+                        .as_ref()
+                        .map(|f| f.path.as_str());
+                    let [session, change, file] = self.review.elapsed(source, path);
+                    ui.label(
+                        egui::RichText::new(format!("⏱ {}", review::format_duration(session)))
+                            .monospace()
+                            .color(theme::dim_text(ui.visuals())),
+                    )
+                    .on_hover_text(trf(
+                        "Review time this session\nThis change: {}\nThis file: {}",
+                        &[
+                            &review::format_duration(change),
+                            &review::format_duration(file),
+                        ],
+                    ));
+                    if jj::working_copy_stale() && self.operation.is_none() {
+                        if !self.config.read_only {
+                            update_stale = ui
+                                .small_button(tr("Update"))
+                                .on_hover_text("jj workspace update-stale")
+                                .clicked();
+                        }
+                        ui.label(
+                            egui::RichText::new(tr("Working copy is stale"))
+                                .color(egui::Color32::from_rgb(220, 180, 80)),
+                        )
+                        .on_hover_text(tr(
+                            "Another workspace rewrote this one's commit; update it to continue",
+                        ));
+                    }
+                    open_path = warnings_menu(ui);
+                    version_warning(ui);
+                    if let Some(path) = self.colocation.as_mut().and_then(|c| c.show(ui)) {
+                        open_path = Some(path);
+                    }
+                    if self.config.read_only {
+                        ui.label(
+                            egui::RichText::new(tr("Read-only"))
+                                .color(egui::Color32::from_rgb(220, 180, 80)),
+                        );
+                    }
+                    if let Ok(metadata) = self
+                        .metadata_panel
+                        .metadata(self.changed_files_cache.source())
+                    {
+                        metadata_panel::commit_id(ui, &metadata.commit_id);
+                        metadata_panel::change_id(ui, &metadata.change_id);
+                    }
+                    match jj::workspace_root() {
+                        Some(root) => ui.label(
+                            egui::RichText::new(root.display().to_string())
+                                .monospace()
+                                .color(theme::dim_text(ui.visuals())),
+                        ),
+                        None => ui.colored_label(
+                            egui::Color32::from_rgb(220, 80, 80),
+                            tr("No repository found above the working directory"),
+                        ),
+                    };
+                });
+            });
+        });
+
+        if let Some(path) = open_path {
+            self.goto = Some(Location { path, line: None });
+        }
+        if update_stale {
+            let args = vec!["workspace".to_string(), "update-stale".to_string()];
+            self.start_operation(ctx, tr("Update stale working copy"), args);
+        }
+
+        if let Some(Ok(output) | Err(output)) = &self.operation_result {
+            egui::Window::new(tr("Operation output"))
+                .open(&mut self.show_operation_output)
+                .default_width(520.0)
+                .show(ctx, |ui| {
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        ui.label(egui::RichText::new(output).monospace());
+                    });
+                });
+        }
+    }
+
+    fn show_close_dialog(&mut self, ctx: &egui::Context) {
+        if !self.confirm_close {
+            return;
+        }
+        let Some(op) = &self.operation else {
+            self.confirm_close = false;
+            return;
+        };
+
+        let label = op.label().to_string();
+        egui::Modal::new(egui::Id::new("confirm_close")).show(ctx, |ui| {
+            ui.heading(tr("Operation in progress"));
+            ui.label(format!("\"{}\" is still running.", label));
+            ui.horizontal(|ui| {
+                if ui.button(tr("Close when finished")).clicked() {
+                    self.close_after_operation = true;
+                    self.confirm_close = false;
+                }
+                if ui.button(tr("Keep open")).clicked() {
+                    self.confirm_close = false;
+                }
+            });
+        });
+    }
+}
+
+/// `--check`: 0 for a clean working copy, 1 with changes and 2 when jj
+/// can't tell, for shell prompts and hooks
+fn check(summary: bool) -> i32 {
+    let files = match changed_files::try_fetch_changed_files() {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("le-differ: {}", e);
+            return 2;
+        }
+    };
+    if summary {
+        for file in &files {
+            println!("{} {}", file.status.symbol(), file.path);
+        }
+        println!("{}", trf("{} changed files", &[&files.len()]));
+    }
+    i32::from(!files.is_empty())
+}
+
+/// "⚠ 2" in the status bar while jj has printed warnings, listing them with
+/// links to the paths they name. Returns the clicked path.
+fn warnings_menu(ui: &mut egui::Ui) -> Option<String> {
+    let warnings = jj::warnings();
+    if warnings.is_empty() {
+        return None;
+    }
+
+    let mut open = None;
+    let label = egui::RichText::new(format!("⚠ {}", warnings.len()))
+        .color(egui::Color32::from_rgb(220, 180, 80));
+    ui.menu_button(label, |ui| {
+        ui.set_max_width(480.0);
+        for warning in &warnings {
+            ui.label(&warning.message);
+            for path in &warning.paths {
+                if ui
+                    .link(egui::RichText::new(path).monospace())
+                    .on_hover_text(tr("Open in the diff view"))
+                    .clicked()
+                {
+                    open = Some(path.clone());
+                    ui.close();
+                }
+            }
+            ui.separator();
+        }
+        if ui.button(tr("Dismiss")).clicked() {
+            jj::dismiss_warnings();
+            ui.close();
+        }
+    })
+    .response
+    .on_hover_text(tr("Warnings from jj"));
+    open
+}
+
+/// "jj 0.50.0 ⚠" in the status bar when the installed jj is outside the
+/// releases whose output is known to parse
+fn version_warning(ui: &mut egui::Ui) {
+    let (label, hover) = match jj::compatibility() {
+        jj::Compatibility::Supported | jj::Compatibility::Missing => return,
+        jj::Compatibility::Unrecognized(printed) => (
+            "jj ? ⚠".to_string(),
+            trf(
+                "Couldn't read the jj version from \"{}\"; file lists and diffs may be incomplete",
+                &[&printed],
+            ),
+        ),
+        jj::Compatibility::TooOld(version) => (
+            format!("jj {} ⚠", version),
+            trf(
+                "jj {} is older than {}, the oldest release supported. Some views may stay empty until jj is updated.",
+                &[&version, &jj::OLDEST_SUPPORTED],
+            ),
+        ),
+        jj::Compatibility::Untested(version) => (
+            format!("jj {} ⚠", version),
+            trf(
+                "jj {} is newer than {}, the newest release tested. If the file list looks wrong, jj's output may have changed.",
+                &[&version, &jj::NEWEST_TESTED],
+            ),
+        ),
+    };
+    ui.label(egui::RichText::new(label).color(egui::Color32::from_rgb(220, 180, 80)))
+        .on_hover_text(hover);
 }
 
 fn main() -> eframe::Result<()> {
-    fmt().with_env_filter(EnvFilter::from_default_env()).init();
+    // Span close events carry their duration, e.g. RUST_LOG=le_differ=debug
+    // logs how long each diff stage took
+    fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_span_events(FmtSpan::CLOSE)
+        .init();
     debug!("Starting application in debug mode...");
 
+    let args = match cli::Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!(
+                "le-differ: {}\nTry 'le-differ --help' for more information.",
+                e
+            );
+            std::process::exit(2);
+        }
+    };
+    if let Some(info) = args.info {
+        info.print();
+        return Ok(());
+    }
+
+    // Launchers often start us in $HOME or /, so pin every command to the repo
+    let repo_root = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| jj::find_repo_root(&cwd));
+    debug!("Repository root: {:?}", repo_root);
+    jj::set_workspace_root(repo_root);
+
+    let mut config = Config::load();
+    config.read_only |= args.read_only;
+    config.profile |= args.profile;
+    i18n::init(config.language.as_deref());
+    let goto = match (args.goto, std::env::current_dir(), jj::workspace_root()) {
+        (Some(goto), Ok(cwd), Some(root)) => Some(goto.relative_to(&cwd, &root)),
+        (goto, _, _) => goto,
+    };
+    if args.check {
+        std::process::exit(check(args.summary));
+    }
+    // As a per-file check from the shell, no changes means no window
+    if args.focus
+        && let Some(goto) = &goto
+        && !changed_files::fetch_changed_files()
+            .iter()
+            .any(|f| f.path == goto.path)
+    {
+        eprintln!("{}", trf("{} has no changes", &[&goto.path]));
+        std::process::exit(1);
+    }
+
     let native_options = eframe::NativeOptions::default();
     eframe::run_native(
         "Counter App",
         native_options,
-        Box::new(|_cc| Ok(Box::<MyApp>::default())),
+        Box::new(|_cc| Ok(Box::new(MyApp::new(config, goto, args.focus)))),
     )
 }
@@ -0,0 +1,151 @@
+use eframe::egui::{self, Color32, RichText};
+
+use crate::changed_files::FileStatus;
+use crate::i18n::tr;
+use crate::jj::{self, DiffSource, RevisionMetadata};
+use crate::theme;
+
+/// Details of the revision on the new side of the diff, for the right panel
+pub struct MetadataPanel {
+    loaded_for: Option<String>,
+    metadata: Result<RevisionMetadata, String>,
+}
+
+impl Default for MetadataPanel {
+    fn default() -> Self {
+        Self {
+            loaded_for: None,
+            metadata: Ok(RevisionMetadata::default()),
+        }
+    }
+}
+
+impl MetadataPanel {
+    /// Drop cached data so the next `show` refetches it
+    pub fn invalidate(&mut self) {
+        self.loaded_for = None;
+    }
+
+    /// What is known about the new side of `source`, fetched when it changed
+    pub fn metadata(&mut self, source: &DiffSource) -> &Result<RevisionMetadata, String> {
+        let revision = source.new_revision().unwrap_or("@");
+        if self.loaded_for.as_deref() != Some(revision) {
+            self.metadata = jj::revision_metadata(revision);
+            self.loaded_for = Some(revision.to_string());
+        }
+        &self.metadata
+    }
+
+    /// Returns a new diff source when a parent is clicked
+    pub fn show(&mut self, ui: &mut egui::Ui, source: &DiffSource) -> Option<DiffSource> {
+        self.metadata(source);
+        let mut new_source = None;
+
+        ui.heading(tr("Revision"));
+        ui.separator();
+
+        let metadata = match &self.metadata {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                ui.label(RichText::new(e).color(FileStatus::Deleted.color()));
+                return None;
+            }
+        };
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                egui::Grid::new("revision_metadata")
+                    .num_columns(2)
+                    .spacing([12.0, 6.0])
+                    .show(ui, |ui| {
+                        field(ui, tr("Change"));
+                        change_id(ui, &metadata.change_id);
+                        ui.end_row();
+
+                        field(ui, tr("Commit"));
+                        commit_id(ui, &metadata.commit_id);
+                        ui.end_row();
+
+                        field(ui, tr("Author"));
+                        ui.label(&metadata.author).on_hover_text(&metadata.email);
+                        ui.end_row();
+
+                        field(ui, tr("Authored"));
+                        ui.label(&metadata.authored);
+                        ui.end_row();
+
+                        field(ui, tr("Committed"));
+                        ui.label(&metadata.committed);
+                        ui.end_row();
+
+                        field(ui, tr("Parents"));
+                        ui.vertical(|ui| {
+                            for parent in &metadata.parents {
+                                if ui
+                                    .link(RichText::new(parent).monospace())
+                                    .on_hover_text(tr("Show this parent's changes"))
+                                    .clicked()
+                                {
+                                    new_source = Some(DiffSource::Range {
+                                        from: format!("{}-", parent),
+                                        to: parent.clone(),
+                                    });
+                                }
+                            }
+                        });
+                        ui.end_row();
+
+                        if !metadata.bookmarks.is_empty() {
+                            field(ui, tr("Bookmarks"));
+                            ui.label(
+                                RichText::new(metadata.bookmarks.join(", "))
+                                    .color(FileStatus::Added.color()),
+                            );
+                            ui.end_row();
+                        }
+                    });
+
+                ui.add_space(12.0);
+                if metadata.description.is_empty() {
+                    ui.label(RichText::new(tr("(no description)")).italics());
+                } else {
+                    ui.label(&metadata.description);
+                }
+            });
+
+        new_source
+    }
+}
+
+fn field(ui: &mut egui::Ui, name: &str) {
+    ui.label(RichText::new(name).color(theme::dim_text(ui.visuals())));
+}
+
+/// A revision's change ID, shortened, with a button copying all of it
+pub fn change_id(ui: &mut egui::Ui, id: &str) {
+    copyable_id(ui, id, CHANGE_ID_COLOR, tr("Copy change ID"));
+}
+
+/// A revision's git commit SHA, shortened, with a button copying all of
+/// it for CI and forge links
+pub fn commit_id(ui: &mut egui::Ui, id: &str) {
+    copyable_id(ui, id, COMMIT_ID_COLOR, tr("Copy commit SHA"));
+}
+
+pub const CHANGE_ID_COLOR: Color32 = Color32::from_rgb(190, 80, 20);
+pub const COMMIT_ID_COLOR: Color32 = Color32::from_rgb(80, 140, 200);
+
+fn copyable_id(ui: &mut egui::Ui, id: &str, color: Color32, copy: &str) {
+    ui.horizontal(|ui| {
+        ui.spacing_mut().item_spacing.x = 2.0;
+        let short = RichText::new(jj::short_id(id, 12)).monospace().color(color);
+        let label = ui
+            .add(egui::Label::new(short).sense(egui::Sense::click()))
+            .on_hover_text(id);
+        let button = ui.small_button("📋").on_hover_text(copy);
+        if label.clicked() || button.clicked() {
+            ui.ctx().copy_text(id.to_string());
+        }
+    });
+}
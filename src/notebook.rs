@@ -0,0 +1,107 @@
+use serde_json::Value;
+use std::path::Path;
+
+/// Marks a line as a cell boundary in the flattened notebook text.
+/// A private-use codepoint so it never collides with real cell source.
+pub const CELL_MARKER: char = '\u{E000}';
+
+pub fn is_notebook(path: &str) -> bool {
+    Path::new(path).extension().and_then(|e| e.to_str()) == Some("ipynb")
+}
+
+/// Flatten a notebook into line-diffable text: a marker line per cell
+/// followed by its source. Outputs and metadata are only included when
+/// `show_outputs` is set. Returns None if the content isn't notebook JSON.
+pub fn to_text(content: &str, show_outputs: bool) -> Option<String> {
+    if content.trim().is_empty() {
+        return Some(String::new());
+    }
+
+    let notebook: Value = serde_json::from_str(content).ok()?;
+    let cells = notebook.get("cells")?.as_array()?;
+    let mut out = String::new();
+
+    for cell in cells {
+        let cell_type = cell
+            .get("cell_type")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown");
+
+        // Cell ids are stable across reorders, unlike positional indices
+        match cell.get("id").and_then(Value::as_str) {
+            Some(id) => push_marker(&mut out, &format!("{} cell · {}", cell_type, id)),
+            None => push_marker(&mut out, &format!("{} cell", cell_type)),
+        }
+
+        push_text(&mut out, cell.get("source"));
+
+        if !show_outputs {
+            continue;
+        }
+
+        if let Some(count) = cell.get("execution_count").and_then(Value::as_u64) {
+            push_marker(&mut out, &format!("execution count {}", count));
+        }
+
+        if let Some(metadata) = cell.get("metadata").filter(|m| !is_empty_object(m)) {
+            push_marker(&mut out, "metadata");
+            out.push_str(&metadata.to_string());
+            out.push('\n');
+        }
+
+        for output in cell
+            .get("outputs")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let output_type = output
+                .get("output_type")
+                .and_then(Value::as_str)
+                .unwrap_or("output");
+            push_marker(&mut out, &format!("output · {}", output_type));
+
+            match output_type {
+                "stream" => push_text(&mut out, output.get("text")),
+                "error" => {
+                    let name = output.get("ename").and_then(Value::as_str).unwrap_or("");
+                    let value = output.get("evalue").and_then(Value::as_str).unwrap_or("");
+                    out.push_str(&format!("{}: {}\n", name, value));
+                }
+                _ => push_text(
+                    &mut out,
+                    output.get("data").and_then(|d| d.get("text/plain")),
+                ),
+            }
+        }
+    }
+
+    Some(out)
+}
+
+fn push_marker(out: &mut String, label: &str) {
+    out.push(CELL_MARKER);
+    out.push_str(label);
+    out.push('\n');
+}
+
+/// Notebook text fields are either a string or an array of line strings
+fn push_text(out: &mut String, value: Option<&Value>) {
+    let start = out.len();
+    match value {
+        Some(Value::String(s)) => out.push_str(s),
+        Some(Value::Array(parts)) => {
+            for part in parts.iter().filter_map(Value::as_str) {
+                out.push_str(part);
+            }
+        }
+        _ => {}
+    }
+    if out.len() > start && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+fn is_empty_object(value: &Value) -> bool {
+    value.as_object().is_some_and(|o| o.is_empty())
+}
@@ -0,0 +1,634 @@
+use eframe::egui::{self, RichText};
+use std::path::{Component, Path};
+
+use crate::changed_files::FileStatus;
+use crate::i18n::{tr, trf};
+use crate::jj;
+use crate::theme;
+
+/// A parsed unified diff, possibly touching several files
+pub struct Patch {
+    pub files: Vec<FilePatch>,
+}
+
+pub struct FilePatch {
+    pub path: String,
+    pub is_new: bool,
+    pub is_delete: bool,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    pub fn status(&self) -> FileStatus {
+        if self.is_new {
+            FileStatus::Added
+        } else if self.is_delete {
+            FileStatus::Deleted
+        } else {
+            FileStatus::Modified
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Hunk {
+    /// 1-based line in the old file where the hunk starts
+    pub old_start: usize,
+    pub new_start: usize,
+    pub header: String,
+    /// (' ' | '-' | '+', text) pairs
+    pub lines: Vec<(char, String)>,
+    /// The old side's last line ends the file without a newline, per
+    /// `\ No newline at end of file`
+    pub old_no_newline: bool,
+    pub new_no_newline: bool,
+}
+
+impl Hunk {
+    /// Build a hunk from tagged lines, generating its `@@` header
+    pub fn new(old_start: usize, new_start: usize, lines: Vec<(char, String)>) -> Self {
+        let old_count = lines.iter().filter(|(tag, _)| *tag != '+').count();
+        let new_count = lines.iter().filter(|(tag, _)| *tag != '-').count();
+        Self {
+            old_start,
+            new_start,
+            header: format!(
+                "@@ -{},{} +{},{} @@",
+                old_start, old_count, new_start, new_count
+            ),
+            lines,
+            old_no_newline: false,
+            new_no_newline: false,
+        }
+    }
+
+    /// Append the enclosing function or section to the header, like git
+    pub fn with_context(mut self, context: &str) -> Self {
+        self.header.push(' ');
+        self.header.push_str(context);
+        self
+    }
+
+    /// Whatever follows the header's closing `@@`, usually a function name
+    pub fn context(&self) -> Option<&str> {
+        let (_, rest) = self.header.strip_prefix("@@")?.split_once("@@")?;
+        Some(rest.trim()).filter(|rest| !rest.is_empty())
+    }
+
+    /// The hunk that undoes this one
+    pub fn reversed(&self) -> Self {
+        let lines = self
+            .lines
+            .iter()
+            .map(|(tag, text)| {
+                let tag = match tag {
+                    '+' => '-',
+                    '-' => '+',
+                    other => *other,
+                };
+                (tag, text.clone())
+            })
+            .collect();
+        let reversed = Self {
+            old_no_newline: self.new_no_newline,
+            new_no_newline: self.old_no_newline,
+            ..Self::new(self.new_start, self.old_start, lines)
+        };
+        match self.context() {
+            Some(context) => reversed.with_context(context),
+            None => reversed,
+        }
+    }
+
+    /// Lines without a `\r` left from CRLF files; the file's own line
+    /// ending goes back on when it's written
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter(|(tag, _)| *tag != '+')
+            .map(|(_, text)| text.trim_end_matches('\r'))
+            .collect()
+    }
+
+    fn new_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter(|(tag, _)| *tag != '-')
+            .map(|(_, text)| text.trim_end_matches('\r'))
+            .collect()
+    }
+}
+
+pub fn parse(text: &str) -> Result<Patch, String> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut old_path: Option<String> = None;
+    // Lines left in the current hunk body, from its header counts. Body
+    // lines can look like headers (a removed "-- comment"), so count them.
+    let (mut old_left, mut new_left) = (0usize, 0usize);
+
+    for line in text.lines() {
+        if old_left > 0 || new_left > 0 {
+            let hunk = files
+                .last_mut()
+                .and_then(|f| f.hunks.last_mut())
+                .ok_or("hunk body without header")?;
+            // Some tools drop the space on empty context lines
+            let (tag, body) = match line.chars().next() {
+                Some(tag @ (' ' | '-' | '+')) => (tag, &line[1..]),
+                None => (' ', ""),
+                Some('\\') => {
+                    mark_no_newline(hunk);
+                    continue;
+                }
+                _ => return Err(format!("unexpected line in hunk: {}", line)),
+            };
+            match tag {
+                '-' => old_left = old_left.saturating_sub(1),
+                '+' => new_left = new_left.saturating_sub(1),
+                _ => {
+                    old_left = old_left.saturating_sub(1);
+                    new_left = new_left.saturating_sub(1);
+                }
+            }
+            hunk.lines.push((tag, body.to_string()));
+        } else if line.starts_with('\\') {
+            // The marker after a hunk's last line
+            if let Some(hunk) = files.last_mut().and_then(|f| f.hunks.last_mut()) {
+                mark_no_newline(hunk);
+            }
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            old_path = Some(strip_prefix_dir(path));
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            let old = old_path.take().unwrap_or_default();
+            let new = strip_prefix_dir(path);
+            let is_new = old == "/dev/null";
+            let is_delete = new == "/dev/null";
+            let path = if is_delete { old } else { new };
+            if !is_inside_workspace(&path) {
+                return Err(format!("patch reaches outside the workspace: {}", path));
+            }
+            files.push(FilePatch {
+                path,
+                is_new,
+                is_delete,
+                hunks: Vec::new(),
+            });
+        } else if line.starts_with("@@") {
+            let file = files.last_mut().ok_or("hunk before any file header")?;
+            let (old_start, old_count, new_start, new_count) =
+                parse_hunk_header(line).ok_or("malformed hunk header")?;
+            (old_left, new_left) = (old_count, new_count);
+            file.hunks.push(Hunk {
+                old_start,
+                new_start,
+                header: line.to_string(),
+                lines: Vec::new(),
+                old_no_newline: false,
+                new_no_newline: false,
+            });
+        }
+    }
+
+    if files.iter().all(|f| f.hunks.is_empty()) {
+        return Err("clipboard doesn't contain a unified diff".to_string());
+    }
+    Ok(Patch { files })
+}
+
+/// `\ No newline at end of file` applies to the line above it: the old
+/// side's for a removed line, the new side's for an added one and both
+/// for context
+fn mark_no_newline(hunk: &mut Hunk) {
+    match hunk.lines.last() {
+        Some(('-', _)) => hunk.old_no_newline = true,
+        Some(('+', _)) => hunk.new_no_newline = true,
+        Some(_) => {
+            hunk.old_no_newline = true;
+            hunk.new_no_newline = true;
+        }
+        None => {}
+    }
+}
+
+/// A relative path that stays below the workspace root, so a pasted patch
+/// can't write elsewhere
+fn is_inside_workspace(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// Drop the `a/`/`b/` prefix and any trailing timestamp
+fn strip_prefix_dir(path: &str) -> String {
+    let path = path.split('\t').next().unwrap_or(path).trim();
+    path.strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path)
+        .to_string()
+}
+
+/// "@@ -12,5 +14,6 @@" -> (12, 5, 14, 6); omitted counts default to 1
+fn parse_hunk_header(header: &str) -> Option<(usize, usize, usize, usize)> {
+    let mut parts = header.split_whitespace().skip(1);
+    let old = parts.next()?.strip_prefix('-')?;
+    let new = parts.next()?.strip_prefix('+')?;
+
+    let range = |spec: &str| -> Option<(usize, usize)> {
+        match spec.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((spec.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = range(old)?;
+    let (new_start, new_count) = range(new)?;
+    Some((old_start, old_count, new_start, new_count))
+}
+
+/// Unified diff text for one file, as `git apply` expects it
+pub fn format(file: &FilePatch) -> String {
+    let old = if file.is_new {
+        "/dev/null".to_string()
+    } else {
+        format!("a/{}", file.path)
+    };
+    let new = if file.is_delete {
+        "/dev/null".to_string()
+    } else {
+        format!("b/{}", file.path)
+    };
+
+    let mut text = format!("--- {}\n+++ {}\n", old, new);
+    for hunk in &file.hunks {
+        text.push_str(&hunk.header);
+        text.push('\n');
+        let last_old = hunk.lines.iter().rposition(|(tag, _)| *tag != '+');
+        let last_new = hunk.lines.iter().rposition(|(tag, _)| *tag != '-');
+        for (i, (tag, line)) in hunk.lines.iter().enumerate() {
+            text.push(*tag);
+            text.push_str(line);
+            text.push('\n');
+            if (hunk.old_no_newline && last_old == Some(i))
+                || (hunk.new_no_newline && last_new == Some(i))
+            {
+                text.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    text
+}
+
+/// Per-file, per-hunk outcome of applying a patch
+pub type ApplyReport = Vec<Vec<bool>>;
+
+/// Apply hunks to the working copy, searching outward from the expected
+/// position so hunks still land when earlier lines have moved. Hunks
+/// that don't match are skipped and reported as conflicts. With
+/// `dry_run` nothing is written.
+pub fn apply(patch: &Patch, dry_run: bool) -> ApplyReport {
+    patch
+        .files
+        .iter()
+        .map(|file| apply_file(file, dry_run))
+        .collect()
+}
+
+fn apply_file(file: &FilePatch, dry_run: bool) -> Vec<bool> {
+    if !is_inside_workspace(&file.path) {
+        return vec![false; file.hunks.len()];
+    }
+    let path = jj::workspace_path(&file.path);
+    let original = if file.is_new {
+        String::new()
+    } else {
+        match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => return vec![false; file.hunks.len()],
+        }
+    };
+
+    let (content, results) = apply_to(&original, file);
+
+    if !dry_run && results.iter().any(|&ok| ok) {
+        let result = if file.is_delete && content.is_empty() {
+            std::fs::remove_file(&path)
+        } else {
+            if let Some(parent) = path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            std::fs::write(&path, content)
+        };
+        if result.is_err() {
+            return vec![false; file.hunks.len()];
+        }
+    }
+
+    results
+}
+
+/// `original` with the hunks that still fit applied, keeping its line
+/// ending and whether it ends in a newline unless a hunk says otherwise
+fn apply_to(original: &str, file: &FilePatch) -> (String, Vec<bool>) {
+    let eol = if original.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut final_newline = original.is_empty() || original.ends_with('\n');
+    let mut lines: Vec<String> = original.lines().map(str::to_string).collect();
+    let mut offset: isize = 0;
+    let mut results = Vec::with_capacity(file.hunks.len());
+
+    for hunk in &file.hunks {
+        let old = hunk.old_lines();
+        let expected = (hunk.old_start.saturating_sub(1) as isize + offset).max(0) as usize;
+
+        match find_hunk(&lines, &old, expected) {
+            Some(at) => {
+                let new: Vec<String> = hunk.new_lines().into_iter().map(str::to_string).collect();
+                offset += new.len() as isize - old.len() as isize;
+                if at + old.len() == lines.len() {
+                    if hunk.new_no_newline {
+                        final_newline = false;
+                    } else if hunk.old_no_newline {
+                        final_newline = true;
+                    }
+                }
+                lines.splice(at..at + old.len(), new);
+                results.push(true);
+            }
+            None => results.push(false),
+        }
+    }
+
+    let mut content = lines.join(eol);
+    if !lines.is_empty() && final_newline {
+        content.push_str(eol);
+    }
+    (content, results)
+}
+
+fn find_hunk(lines: &[String], old: &[&str], expected: usize) -> Option<usize> {
+    let matches_at = |at: usize| {
+        at + old.len() <= lines.len()
+            && lines[at..at + old.len()]
+                .iter()
+                .zip(old)
+                .all(|(a, b)| a == b)
+    };
+
+    (0..=lines.len()).find_map(|distance| {
+        let after = expected + distance;
+        let before = expected.checked_sub(distance);
+        if matches_at(after) {
+            Some(after)
+        } else {
+            before.filter(|&at| matches_at(at))
+        }
+    })
+}
+
+/// The hunk's body with added and removed lines tinted
+pub fn show_hunk_lines(ui: &mut egui::Ui, hunk: &Hunk) {
+    for (tag, text) in &hunk.lines {
+        let color = match tag {
+            '+' => FileStatus::Added.color(),
+            '-' => FileStatus::Deleted.color(),
+            _ => ui.visuals().text_color(),
+        };
+        ui.label(
+            RichText::new(format!("{}{}", tag, text))
+                .monospace()
+                .color(color),
+        );
+    }
+}
+
+/// The hunk's region once applied, with line numbers and the lines it
+/// brings in tinted
+fn show_result(ui: &mut egui::Ui, hunk: &Hunk) {
+    let dim = theme::dim_text(ui.visuals());
+    egui::Frame::new()
+        .fill(theme::sidebar_fill(ui.visuals()))
+        .inner_margin(egui::Margin::same(6))
+        .show(ui, |ui| {
+            let kept = hunk.lines.iter().filter(|(tag, _)| *tag != '-');
+            for (n, (tag, text)) in (hunk.new_start..).zip(kept) {
+                let color = if *tag == '+' {
+                    FileStatus::Added.color()
+                } else {
+                    ui.visuals().text_color()
+                };
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(format!("{:>5}", n)).monospace().color(dim));
+                    ui.label(RichText::new(text).monospace().color(color));
+                });
+            }
+        });
+}
+
+/// Preview of a patch before it's applied, with per-hunk conflict markers
+pub struct PatchDialog {
+    patch: Patch,
+    /// Dry-run results until applied, then the real outcome
+    report: ApplyReport,
+    applied: bool,
+    title: &'static str,
+    apply_label: &'static str,
+    /// Also render each hunk's region as it will read afterwards
+    show_result: bool,
+}
+
+impl PatchDialog {
+    /// For a patch pasted from the clipboard
+    pub fn new(patch: Patch) -> Self {
+        let report = apply(&patch, true);
+        Self {
+            patch,
+            report,
+            applied: false,
+            title: tr("Paste patch"),
+            apply_label: tr("Apply"),
+            show_result: false,
+        }
+    }
+
+    /// For undoing hunks of the working copy; `patch` is already reversed
+    pub fn revert(patch: Patch) -> Self {
+        Self {
+            title: tr("Revert hunk"),
+            apply_label: tr("Revert"),
+            show_result: true,
+            ..Self::new(patch)
+        }
+    }
+
+    /// Returns (keep_open, applied_now)
+    pub fn show(&mut self, ctx: &egui::Context) -> (bool, bool) {
+        let mut open = true;
+        let mut close = false;
+        let mut applied_now = false;
+
+        egui::Window::new(self.title)
+            .open(&mut open)
+            .default_size([720.0, 520.0])
+            .show(ctx, |ui| {
+                let conflicts = self.report.iter().flatten().filter(|&&ok| !ok).count();
+                ui.horizontal(|ui| {
+                    if self.applied {
+                        ui.label(tr("Patch applied."));
+                    }
+                    if conflicts > 0 {
+                        ui.label(
+                            RichText::new(trf("{} hunks don't apply cleanly", &[&conflicts]))
+                                .color(FileStatus::Deleted.color()),
+                        );
+                    }
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if self.applied {
+                            close = ui.button(tr("Close")).clicked();
+                        } else {
+                            if ui.button(tr("Cancel")).clicked() {
+                                close = true;
+                            }
+                            if ui.button(self.apply_label).clicked() {
+                                self.report = apply(&self.patch, false);
+                                self.applied = true;
+                                applied_now = true;
+                            }
+                        }
+                    });
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| self.show_preview(ui));
+            });
+
+        (open && !close, applied_now)
+    }
+
+    fn show_preview(&self, ui: &mut egui::Ui) {
+        for (file, results) in self.patch.files.iter().zip(&self.report) {
+            ui.label(
+                RichText::new(&file.path)
+                    .strong()
+                    .color(file.status().color()),
+            );
+
+            for (hunk, ok) in file.hunks.iter().zip(results) {
+                ui.horizontal(|ui| {
+                    ui.label(
+                        RichText::new(&hunk.header)
+                            .monospace()
+                            .color(theme::dim_text(ui.visuals())),
+                    );
+                    if !ok {
+                        ui.label(RichText::new(tr("conflict")).color(FileStatus::Deleted.color()));
+                    }
+                });
+
+                show_hunk_lines(ui, hunk);
+
+                if self.show_result {
+                    ui.add_space(4.0);
+                    ui.label(RichText::new(tr("Afterwards:")).small());
+                    show_result(ui, hunk);
+                }
+            }
+            ui.add_space(8.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -2,3 +2,3 @@ fn main() {
+ two
+-three
++THREE
+ four
+";
+
+    fn file(patch: &str) -> FilePatch {
+        parse(patch).unwrap().files.remove(0)
+    }
+
+    #[test]
+    fn parses_a_unified_diff() {
+        let file = file(PATCH);
+        assert_eq!(file.path, "src/lib.rs");
+        assert!(!file.is_new && !file.is_delete);
+        let hunk = &file.hunks[0];
+        assert_eq!((hunk.old_start, hunk.new_start), (2, 2));
+        assert_eq!(hunk.context(), Some("fn main() {"));
+        assert_eq!(hunk.old_lines(), ["two", "three", "four"]);
+        assert_eq!(hunk.new_lines(), ["two", "THREE", "four"]);
+        assert_eq!(format(&file), PATCH);
+    }
+
+    #[test]
+    fn applies_at_an_offset() {
+        let (content, results) = apply_to("zero\none\ntwo\nthree\nfour\n", &file(PATCH));
+        assert_eq!(results, [true]);
+        assert_eq!(content, "zero\none\ntwo\nTHREE\nfour\n");
+    }
+
+    #[test]
+    fn skips_a_conflicting_hunk() {
+        let original = "one\ntwo\n3\nfour\n";
+        let (content, results) = apply_to(original, &file(PATCH));
+        assert_eq!(results, [false]);
+        assert_eq!(content, original);
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        let (content, results) = apply_to("one\r\ntwo\r\nthree\r\nfour\r\n", &file(PATCH));
+        assert_eq!(results, [true]);
+        assert_eq!(content, "one\r\ntwo\r\nTHREE\r\nfour\r\n");
+    }
+
+    #[test]
+    fn keeps_a_missing_final_newline() {
+        let (content, _) = apply_to("one\ntwo\nthree\nfour", &file(PATCH));
+        assert_eq!(content, "one\ntwo\nTHREE\nfour");
+    }
+
+    #[test]
+    fn honors_no_newline_markers() {
+        let patch = "\
+--- a/notes.txt
++++ b/notes.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++TWO
+\\ No newline at end of file
+";
+        let file = file(patch);
+        assert!(!file.hunks[0].old_no_newline && file.hunks[0].new_no_newline);
+        assert_eq!(format(&file), patch);
+        assert_eq!(apply_to("one\ntwo\n", &file).0, "one\nTWO");
+
+        let reverted = FilePatch {
+            hunks: vec![file.hunks[0].reversed()],
+            ..file
+        };
+        assert_eq!(apply_to("one\nTWO", &reverted).0, "one\ntwo\n");
+    }
+
+    #[test]
+    fn rejects_paths_outside_the_workspace() {
+        for path in ["/etc/passwd", "../outside", "src/../../outside"] {
+            let patch = PATCH.replace("src/lib.rs", path);
+            assert!(parse(&patch).is_err(), "{} was accepted", path);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use eframe::egui;
+use std::sync::Arc;
+
+use crate::changed_files::ChangedFile;
+use crate::config::Config;
+use crate::diff_viewer::{DiffViewMode, DiffViewer, DiffViewerAction};
+use crate::jj::DiffSource;
+
+/// A file's diff in a window of its own, e.g. to keep on a second monitor
+/// while reviewing on in the main one. It stays on the file and revisions
+/// it was opened with.
+struct Popout {
+    id: egui::ViewportId,
+    file: ChangedFile,
+    diff_viewer: DiffViewer,
+    view_mode: DiffViewMode,
+    open: bool,
+}
+
+/// The popped-out windows, and where the last one was placed so the next
+/// opens on the same monitor
+#[derive(Default)]
+pub struct Popouts {
+    windows: Vec<Popout>,
+    placement: Option<egui::Rect>,
+    /// Ids handed out so far, to keep them unique as windows close
+    opened: usize,
+}
+
+impl Popouts {
+    /// Open `file` as it differs in `source` in a new window
+    pub fn open(&mut self, config: Arc<Config>, file: ChangedFile, source: DiffSource) {
+        self.opened += 1;
+        let mut diff_viewer = DiffViewer::new(config);
+        diff_viewer.set_source(source);
+        self.windows.push(Popout {
+            id: egui::ViewportId::from_hash_of(("popout", self.opened)),
+            file,
+            diff_viewer,
+            view_mode: DiffViewMode::SideBySide,
+            open: true,
+        });
+    }
+
+    /// Reload every window's diff after the repository changed
+    pub fn invalidate(&mut self) {
+        for window in &mut self.windows {
+            window.diff_viewer.invalidate_cache();
+        }
+    }
+
+    /// Draws every window. Returns what their viewers asked for.
+    pub fn show(&mut self, ctx: &egui::Context) -> Vec<DiffViewerAction> {
+        let mut actions = Vec::new();
+        for window in &mut self.windows {
+            let mut builder = egui::ViewportBuilder::default()
+                .with_title(format!("{} — le-differ", window.file.path))
+                .with_inner_size([960.0, 720.0]);
+            if let Some(placement) = self.placement {
+                builder = builder.with_position(placement.min);
+            }
+
+            ctx.show_viewport_immediate(window.id, builder, |ctx, class| {
+                // Backends without multiple windows nest it in the main one
+                if class == egui::ViewportClass::Embedded {
+                    egui::Window::new(&window.file.path)
+                        .id(egui::Id::new(window.id))
+                        .open(&mut window.open)
+                        .default_size([720.0, 540.0])
+                        .show(ctx, |ui| {
+                            actions.extend(window.diff_viewer.show(
+                                ui,
+                                Some(&window.file),
+                                &mut window.view_mode,
+                                true,
+                            ));
+                        });
+                    return;
+                }
+
+                let (closing, focused, outer) = ctx.input(|i| {
+                    let viewport = i.viewport();
+                    (
+                        viewport.close_requested(),
+                        viewport.focused.unwrap_or(false),
+                        viewport.outer_rect,
+                    )
+                });
+                if closing {
+                    window.open = false;
+                }
+                // The window focused last decides where the next one opens
+                if focused && outer.is_some() {
+                    self.placement = outer;
+                }
+                if focused
+                    && ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::F))
+                {
+                    window.diff_viewer.open_find();
+                }
+
+                egui::CentralPanel::default()
+                    .frame(
+                        egui::Frame::new()
+                            .fill(ctx.style().visuals.window_fill)
+                            .inner_margin(egui::Margin::symmetric(24, 20)),
+                    )
+                    .show(ctx, |ui| {
+                        actions.extend(window.diff_viewer.show(
+                            ui,
+                            Some(&window.file),
+                            &mut window.view_mode,
+                            focused,
+                        ));
+                    });
+            });
+        }
+        self.windows.retain(|window| window.open);
+        actions
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::review::FileReview;
+
+/// Preferences remembered per repository, keyed by its root and stored in
+/// the app data directory rather than `config.toml`
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct RepoSettings {
+    /// Globs hidden from the sidebar here, on top of the configured `exclude`
+    pub exclude: Vec<String>,
+    /// Last "Compare to" base, compared against again on the next start
+    pub base: Option<String>,
+    /// The sidebar's dotfiles toggle; None follows `show_dotfiles`
+    pub show_dotfiles: Option<bool>,
+    /// Viewed and needs-work marks by path
+    pub review: BTreeMap<String, FileReview>,
+    /// Commit IDs checked off in stack review
+    pub reviewed_commits: BTreeSet<String>,
+}
+
+impl RepoSettings {
+    /// The stored settings for `root`, or the defaults for a new repo
+    pub fn load(root: &Path) -> Self {
+        let Some(path) = settings_path(root) else {
+            return Self::default();
+        };
+        // Settings saved before the name carried a hash
+        let legacy = path.with_file_name(format!("{}.toml", readable_name(root)));
+        let Some((path, content)) = [path, legacy]
+            .into_iter()
+            .find_map(|path| Some((path.clone(), std::fs::read_to_string(path).ok()?)))
+        else {
+            return Self::default();
+        };
+
+        toml::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignoring invalid repo settings {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, root: &Path) -> Result<(), String> {
+        let path = settings_path(root).ok_or("no data directory")?;
+        let content = toml::to_string(self).map_err(|e| e.to_string())?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(&path, content).map_err(|e| e.to_string())
+    }
+}
+
+/// `repos/home_me_src_project-<hash>.toml` under the data directory for
+/// `/home/me/src/project`. The hash of the whole path tells apart roots
+/// that only differ in punctuation.
+fn settings_path(root: &Path) -> Option<PathBuf> {
+    let name = format!(
+        "{}-{:016x}",
+        readable_name(root),
+        fnv1a(root.to_string_lossy().as_bytes())
+    );
+    dirs::data_dir().map(|dir| {
+        dir.join("le-differ")
+            .join("repos")
+            .join(format!("{}.toml", name))
+    })
+}
+
+/// The root with everything but letters, digits, `-` and `.` replaced
+fn readable_name(root: &Path) -> String {
+    let name: String = root
+        .to_string_lossy()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    name.trim_matches('_').to_string()
+}
+
+/// 64-bit FNV-1a, stable across Rust releases unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_roots_apart_that_differ_in_punctuation() {
+        let (Some(a), Some(b)) = (
+            settings_path(Path::new("/src/my project")),
+            settings_path(Path::new("/src/my_project")),
+        ) else {
+            return;
+        };
+        assert_ne!(a, b);
+        assert_eq!(settings_path(Path::new("/src/my project")), Some(a.clone()));
+        assert!(a.to_string_lossy().contains("src_my_project-"));
+    }
+}
@@ -0,0 +1,137 @@
+use eframe::egui::{self, RichText};
+
+use crate::changed_files::FileStatus;
+use crate::i18n::{tr, trf};
+use crate::jj::{self, DiffSource};
+use crate::patch::{self, Patch};
+use crate::theme;
+
+/// Replaces one working-copy file with its content at another revision,
+/// showing what that changes first
+pub struct RestoreDialog {
+    path: String,
+    revision: String,
+    /// Revision the preview was made for, and the diff from `@` to it
+    preview: Option<(String, Result<Patch, String>)>,
+}
+
+impl RestoreDialog {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            revision: "@-".to_string(),
+            preview: None,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        trf("Restore {} from {}", &[&self.path, &self.revision.trim()])
+    }
+
+    /// `jj restore` arguments for the chosen revision
+    pub fn args(&self) -> Vec<String> {
+        vec![
+            "restore".to_string(),
+            "--from".to_string(),
+            self.revision.trim().to_string(),
+            jj::root_file(&self.path),
+        ]
+    }
+
+    /// Returns Some(true) when confirmed, Some(false) when cancelled
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<bool> {
+        let mut open = true;
+        let mut choice = None;
+
+        egui::Window::new(tr("Restore from revision"))
+            .open(&mut open)
+            .default_size([720.0, 480.0])
+            .show(ctx, |ui| {
+                ui.label(RichText::new(&self.path).monospace().strong());
+                ui.horizontal(|ui| {
+                    ui.label(tr("Revision"));
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.revision)
+                            .hint_text(tr("change ID, bookmark or revset"))
+                            .desired_width(240.0),
+                    );
+                    let submitted =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui.button(tr("Preview")).clicked() || submitted {
+                        self.load_preview();
+                    }
+                });
+                ui.separator();
+
+                let previewed = self.show_preview(ui);
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(previewed, egui::Button::new(tr("Restore")))
+                        .on_disabled_hover_text(tr("Preview the restore first"))
+                        .clicked()
+                    {
+                        choice = Some(true);
+                    }
+                    if ui.button(tr("Cancel")).clicked() {
+                        choice = Some(false);
+                    }
+                });
+            });
+
+        if !open {
+            choice = Some(false);
+        }
+        choice
+    }
+
+    fn load_preview(&mut self) {
+        let revision = self.revision.trim().to_string();
+        // From `@` to the revision reads as what the restore will do
+        let source = DiffSource::Range {
+            from: "@".to_string(),
+            to: revision.clone(),
+        };
+        let patch =
+            jj::git_diff_paths(&source, std::slice::from_ref(&self.path)).and_then(|diff| {
+                patch::parse(&diff)
+                    .map_err(|_| tr("The file is already the same there").to_string())
+            });
+        self.preview = Some((revision, patch));
+    }
+
+    /// Returns whether an up-to-date preview with changes is shown
+    fn show_preview(&self, ui: &mut egui::Ui) -> bool {
+        let Some((revision, patch)) = &self.preview else {
+            ui.label(tr("Pick a revision and preview what restoring it changes."));
+            return false;
+        };
+        if revision != self.revision.trim() {
+            ui.label(tr("The revision changed, preview again."));
+            return false;
+        }
+        let patch = match patch {
+            Ok(patch) => patch,
+            Err(e) => {
+                ui.label(RichText::new(e).color(FileStatus::Deleted.color()));
+                return false;
+            }
+        };
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .max_height(360.0)
+            .show(ui, |ui| {
+                for hunk in patch.files.iter().flat_map(|f| &f.hunks) {
+                    ui.label(
+                        RichText::new(&hunk.header)
+                            .monospace()
+                            .color(theme::dim_text(ui.visuals())),
+                    );
+                    patch::show_hunk_lines(ui, hunk);
+                }
+            });
+        true
+    }
+}
@@ -0,0 +1,368 @@
+use eframe::egui::{self, Color32, RichText};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::changed_files::{ChangedFile, FileStatus};
+use crate::dialogs::{self, DialogKind};
+use crate::i18n::{tr, trf};
+use crate::jj::{self, DiffSource};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileReview {
+    Viewed,
+    NeedsWork,
+}
+
+impl FileReview {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            FileReview::Viewed => "✔",
+            FileReview::NeedsWork => "✎",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileReview::Viewed => tr("Viewed"),
+            FileReview::NeedsWork => tr("Needs work"),
+        }
+    }
+
+    pub fn color(&self) -> Color32 {
+        match self {
+            FileReview::Viewed => FileStatus::Added.color(),
+            FileReview::NeedsWork => Color32::from_rgb(220, 180, 80),
+        }
+    }
+}
+
+/// A line number on one side of the diff
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineRef {
+    pub line: usize,
+    /// The line only exists in the old version
+    pub old: bool,
+}
+
+impl std::fmt::Display for LineRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.old {
+            write!(f, "old L{}", self.line)
+        } else {
+            write!(f, "L{}", self.line)
+        }
+    }
+}
+
+pub struct Comment {
+    pub path: String,
+    /// None for a comment on the whole file
+    pub line: Option<LineRef>,
+    pub text: String,
+}
+
+/// Without input for this long the reviewer is assumed away and the
+/// timer stops
+const IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Review notes for the session: per-file states, comments and overall
+/// notes, exported together as Markdown
+#[derive(Default)]
+pub struct Review {
+    states: BTreeMap<String, FileReview>,
+    comments: Vec<Comment>,
+    notes: String,
+    /// Comment being written, shown in the review window
+    draft: Option<Comment>,
+    focus_draft: bool,
+    pub open: bool,
+    export_result: Option<Result<String, String>>,
+    /// Time spent per change (keyed by its summary title), then per file
+    time: BTreeMap<String, BTreeMap<String, Duration>>,
+    last_tick: Option<Instant>,
+    last_input: Option<Instant>,
+}
+
+impl Review {
+    pub fn state(&self, path: &str) -> Option<FileReview> {
+        self.states.get(path).copied()
+    }
+
+    pub fn set_state(&mut self, path: &str, state: Option<FileReview>) {
+        match state {
+            Some(state) => self.states.insert(path.to_string(), state),
+            None => self.states.remove(path),
+        };
+    }
+
+    pub fn states(&self) -> &BTreeMap<String, FileReview> {
+        &self.states
+    }
+
+    /// Replace every file's state, e.g. with the ones saved for a repo
+    pub fn set_states(&mut self, states: BTreeMap<String, FileReview>) {
+        self.states = states;
+    }
+
+    /// Count the time since the last frame towards `path`, or the
+    /// overview when None, while the window is focused and in use
+    pub fn tick(&mut self, ctx: &egui::Context, source: &DiffSource, path: Option<&str>) {
+        let now = Instant::now();
+        let (focused, had_input) = ctx.input(|i| {
+            (
+                i.viewport().focused.unwrap_or(true),
+                !i.events.is_empty() || i.pointer.is_moving(),
+            )
+        });
+        if had_input {
+            self.last_input = Some(now);
+        }
+        let active = focused
+            && self
+                .last_input
+                .is_some_and(|input| now - input < IDLE_TIMEOUT);
+        if let Some(last) = self.last_tick.replace(now)
+            && active
+        {
+            *self
+                .time
+                .entry(source_title(source))
+                .or_default()
+                .entry(path.unwrap_or_default().to_string())
+                .or_default() += now - last;
+        }
+        // Keeps the status bar clock moving
+        if active {
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
+    }
+
+    /// Time on the whole session, the change and the file
+    pub fn elapsed(&self, source: &DiffSource, path: Option<&str>) -> [Duration; 3] {
+        let change = self.time.get(&source_title(source));
+        [
+            self.time.values().flat_map(|files| files.values()).sum(),
+            change.map(|files| files.values().sum()).unwrap_or_default(),
+            change
+                .and_then(|files| files.get(path.unwrap_or_default()))
+                .copied()
+                .unwrap_or_default(),
+        ]
+    }
+
+    /// Open the window with an empty comment on `path`
+    pub fn start_comment(&mut self, path: &str, line: Option<LineRef>) {
+        self.draft = Some(Comment {
+            path: path.to_string(),
+            line,
+            text: String::new(),
+        });
+        self.focus_draft = true;
+        self.open = true;
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, source: &DiffSource, files: &[ChangedFile]) {
+        let mut open = self.open;
+        egui::Window::new(tr("Review summary"))
+            .open(&mut open)
+            .default_size([520.0, 480.0])
+            .show(ctx, |ui| {
+                self.show_draft(ui);
+
+                ui.label(tr("Overall notes"));
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.notes)
+                        .desired_rows(4)
+                        .desired_width(f32::INFINITY),
+                );
+                ui.add_space(8.0);
+
+                let reviewed = files.iter().filter(|f| self.state(&f.path).is_some());
+                ui.label(trf(
+                    "{} of {} files reviewed, {} comments",
+                    &[&reviewed.count(), &files.len(), &self.comments.len()],
+                ));
+                let [_, change, _] = self.elapsed(source, None);
+                ui.label(trf("Review time: {}", &[&format_duration(change)]));
+
+                let mut remove = None;
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for (i, comment) in self.comments.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("✕").clicked() {
+                                    remove = Some(i);
+                                }
+                                ui.label(RichText::new(location(comment)).monospace());
+                                ui.label(&comment.text);
+                            });
+                        }
+                    });
+                if let Some(i) = remove {
+                    self.comments.remove(i);
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button(tr("Copy Markdown")).clicked() {
+                        ui.ctx().copy_text(self.to_markdown(source, files));
+                        self.export_result = Some(Ok(tr("Copied to clipboard").to_string()));
+                    }
+                    if ui.button(tr("Save...")).clicked() {
+                        self.export_result = self.save(source, files);
+                    }
+                });
+                match &self.export_result {
+                    Some(Ok(message)) => {
+                        ui.label(message);
+                    }
+                    Some(Err(e)) => {
+                        ui.label(RichText::new(e).color(FileStatus::Deleted.color()));
+                    }
+                    None => {}
+                }
+            });
+        self.open = open;
+    }
+
+    fn show_draft(&mut self, ui: &mut egui::Ui) {
+        let Some(draft) = &mut self.draft else {
+            return;
+        };
+
+        let mut done = None;
+        ui.group(|ui| {
+            ui.label(RichText::new(location(draft)).monospace());
+            let response = ui.add(
+                egui::TextEdit::multiline(&mut draft.text)
+                    .hint_text(tr("Comment"))
+                    .desired_rows(2)
+                    .desired_width(f32::INFINITY),
+            );
+            if std::mem::take(&mut self.focus_draft) {
+                response.request_focus();
+            }
+            ui.horizontal(|ui| {
+                let has_text = !draft.text.trim().is_empty();
+                if ui
+                    .add_enabled(has_text, egui::Button::new(tr("Add comment")))
+                    .clicked()
+                {
+                    done = Some(true);
+                }
+                if ui.button(tr("Cancel")).clicked() {
+                    done = Some(false);
+                }
+            });
+        });
+        ui.add_space(8.0);
+
+        match done {
+            Some(true) => self.comments.extend(self.draft.take()),
+            Some(false) => self.draft = None,
+            None => {}
+        }
+    }
+
+    fn to_markdown(&self, source: &DiffSource, files: &[ChangedFile]) -> String {
+        let title = source_title(source);
+        let time = self.time.get(&title);
+        let mut out = format!("# Review: {}\n\n", title);
+        if let Some(time) = time {
+            let total: Duration = time.values().sum();
+            out.push_str(&format!("Review time: {}\n\n", format_duration(total)));
+        }
+
+        if !self.notes.trim().is_empty() {
+            out.push_str("## Notes\n\n");
+            out.push_str(self.notes.trim());
+            out.push_str("\n\n");
+        }
+
+        out.push_str("## Files\n\n");
+        for file in files {
+            let state = self.state(&file.path);
+            let check = if state == Some(FileReview::Viewed) {
+                "x"
+            } else {
+                " "
+            };
+            out.push_str(&format!("- [{}] `{}`", check, file.path));
+            if state == Some(FileReview::NeedsWork) {
+                out.push_str(" — needs work");
+            }
+            if let Some(spent) = time.and_then(|t| t.get(&file.path)) {
+                out.push_str(&format!(" ({})", format_duration(*spent)));
+            }
+            out.push('\n');
+        }
+
+        if !self.comments.is_empty() {
+            out.push_str("\n## Comments\n");
+            let mut by_file: BTreeMap<&str, Vec<&Comment>> = BTreeMap::new();
+            for comment in &self.comments {
+                by_file.entry(&comment.path).or_default().push(comment);
+            }
+            for (path, comments) in by_file {
+                out.push_str(&format!("\n### `{}`\n\n", path));
+                for comment in comments {
+                    let text = comment.text.trim().replace('\n', "\n  ");
+                    match comment.line {
+                        Some(line) => out.push_str(&format!("- {}: {}\n", line, text)),
+                        None => out.push_str(&format!("- {}\n", text)),
+                    }
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Suggests `<repo>-review.md`; the dialog starts beside the workspace,
+    /// where jj won't snapshot it into the change. None when cancelled.
+    fn save(&self, source: &DiffSource, files: &[ChangedFile]) -> Option<Result<String, String>> {
+        let name = jj::workspace_root()
+            .and_then(|root| root.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "le-differ".to_string());
+        let path = dialogs::save_file(
+            DialogKind::SaveReview,
+            tr("Save review summary"),
+            &format!("{}-review.md", name),
+            ("Markdown", &["md"]),
+        )?;
+        Some(
+            std::fs::write(&path, self.to_markdown(source, files))
+                .map(|()| trf("Saved {}", &[&path.display()]))
+                .map_err(|e| e.to_string()),
+        )
+    }
+}
+
+fn source_title(source: &DiffSource) -> String {
+    match source {
+        DiffSource::WorkingCopy => "working copy (@)".to_string(),
+        DiffSource::Range { from, to } => format!("{} → {}", from, to),
+        DiffSource::Unsnapshotted => "unsnapshotted edits (last snapshot → disk)".to_string(),
+    }
+}
+
+/// `1h 02m`, `4m 05s` or `12s`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, _) => format!("{}h {:02}m", h, m),
+    }
+}
+
+fn location(comment: &Comment) -> String {
+    match comment.line {
+        Some(line) => format!("{}:{}", comment.path, line),
+        None => comment.path.clone(),
+    }
+}
@@ -0,0 +1,158 @@
+use serde_json::Value;
+use similar::ChangeTag;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// One row of the semantic diff tree
+pub struct TreeLine {
+    pub text: String,
+    pub change_type: ChangeTag,
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+fn format_for(path: &str) -> Option<Format> {
+    match Path::new(path).extension().and_then(|e| e.to_str())? {
+        "json" => Some(Format::Json),
+        "yaml" | "yml" => Some(Format::Yaml),
+        "toml" => Some(Format::Toml),
+        _ => None,
+    }
+}
+
+pub fn supports(path: &str) -> bool {
+    format_for(path).is_some()
+}
+
+/// Parse both sides and diff them as key trees, so reordered keys and
+/// reindented documents produce no rows. Unchanged subtrees are omitted;
+/// parents of changed keys are kept as context.
+pub fn diff(path: &str, old: &str, new: &str) -> Result<Vec<TreeLine>, String> {
+    let format = format_for(path).ok_or("unsupported file type")?;
+    let old = parse(format, old).map_err(|e| format!("old side: {}", e))?;
+    let new = parse(format, new).map_err(|e| format!("new side: {}", e))?;
+
+    let mut out = Vec::new();
+    match (&old, &new) {
+        (Some(old), Some(new)) => walk(None, 0, old, new, &mut out),
+        (Some(old), None) => emit_value(None, 0, old, ChangeTag::Delete, &mut out),
+        (None, Some(new)) => emit_value(None, 0, new, ChangeTag::Insert, &mut out),
+        (None, None) => {}
+    }
+    Ok(out)
+}
+
+/// Empty content parses to None so added/deleted files diff against nothing
+fn parse(format: Format, content: &str) -> Result<Option<Value>, String> {
+    if content.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let value = match format {
+        Format::Json => serde_json::from_str(content).map_err(|e| e.to_string())?,
+        Format::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string())?,
+        Format::Toml => toml::from_str(content).map_err(|e| e.to_string())?,
+    };
+    Ok(Some(value))
+}
+
+fn walk(label: Option<&str>, depth: usize, old: &Value, new: &Value, out: &mut Vec<TreeLine>) {
+    if old == new {
+        return;
+    }
+
+    // Root containers have no label; their children start at depth 0
+    let child_depth = if label.is_some() { depth + 1 } else { depth };
+
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            push_label(label, depth, ChangeTag::Equal, out);
+            let keys: BTreeSet<&String> = old_map.keys().chain(new_map.keys()).collect();
+            for key in keys {
+                diff_entry(key, child_depth, old_map.get(key), new_map.get(key), out);
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            push_label(label, depth, ChangeTag::Equal, out);
+            for i in 0..old_items.len().max(new_items.len()) {
+                let key = format!("[{}]", i);
+                diff_entry(&key, child_depth, old_items.get(i), new_items.get(i), out);
+            }
+        }
+        _ => {
+            emit_value(label, depth, old, ChangeTag::Delete, out);
+            emit_value(label, depth, new, ChangeTag::Insert, out);
+        }
+    }
+}
+
+fn diff_entry(
+    key: &str,
+    depth: usize,
+    old: Option<&Value>,
+    new: Option<&Value>,
+    out: &mut Vec<TreeLine>,
+) {
+    match (old, new) {
+        (Some(old), Some(new)) => walk(Some(key), depth, old, new, out),
+        (Some(old), None) => emit_value(Some(key), depth, old, ChangeTag::Delete, out),
+        (None, Some(new)) => emit_value(Some(key), depth, new, ChangeTag::Insert, out),
+        (None, None) => {}
+    }
+}
+
+/// Container rows are just the key; their children follow indented
+fn push_label(label: Option<&str>, depth: usize, change_type: ChangeTag, out: &mut Vec<TreeLine>) {
+    if let Some(label) = label {
+        out.push(TreeLine {
+            text: format!("{}{}:", indent(depth), label),
+            change_type,
+        });
+    }
+}
+
+/// Emit a whole value (and any children) with a single change tag
+fn emit_value(
+    label: Option<&str>,
+    depth: usize,
+    value: &Value,
+    change_type: ChangeTag,
+    out: &mut Vec<TreeLine>,
+) {
+    let child_depth = if label.is_some() { depth + 1 } else { depth };
+    let prefix = label.map(|l| format!("{}: ", l)).unwrap_or_default();
+
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            push_label(label, depth, change_type, out);
+            for (key, child) in map {
+                emit_value(Some(key), child_depth, child, change_type, out);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            push_label(label, depth, change_type, out);
+            for (i, child) in items.iter().enumerate() {
+                emit_value(
+                    Some(&format!("[{}]", i)),
+                    child_depth,
+                    child,
+                    change_type,
+                    out,
+                );
+            }
+        }
+        scalar => out.push(TreeLine {
+            text: format!("{}{}{}", indent(depth), prefix, scalar),
+            change_type,
+        }),
+    }
+}
+
+fn indent(depth: usize) -> String {
+    "  ".repeat(depth)
+}
@@ -0,0 +1,141 @@
+use eframe::egui::{self, RichText};
+
+use crate::changed_files::FileStatus;
+use crate::i18n::tr;
+use crate::jj::{self, DiffSource, FileStat};
+use crate::theme;
+
+/// Splits the working-copy change by file: ticked files go into a new
+/// first change, the rest stay in `@`
+pub struct SplitDialog {
+    stats: Vec<FileStat>,
+    /// Parallel to `stats`; true puts the file in the first change
+    in_first: Vec<bool>,
+    description: String,
+}
+
+impl SplitDialog {
+    pub fn new() -> Self {
+        let stats = jj::diff_stats(&DiffSource::WorkingCopy);
+        Self {
+            in_first: vec![false; stats.len()],
+            description: jj::description("@"),
+            stats,
+        }
+    }
+
+    /// `jj split` arguments for the current buckets
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            "split".to_string(),
+            "-m".to_string(),
+            self.description.clone(),
+            "--".to_string(),
+        ];
+        args.extend(
+            self.stats
+                .iter()
+                .zip(&self.in_first)
+                .filter(|(_, first)| **first)
+                .map(|(stat, _)| jj::root_file(&stat.path)),
+        );
+        args
+    }
+
+    /// Returns Some(true) when confirmed, Some(false) when cancelled
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<bool> {
+        let mut open = true;
+        let mut choice = None;
+
+        egui::Window::new(tr("Split change"))
+            .open(&mut open)
+            .default_size([720.0, 480.0])
+            .show(ctx, |ui| {
+                ui.label(tr("Description of the first change"));
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.description)
+                        .hint_text(tr("(no description)"))
+                        .desired_width(f32::INFINITY),
+                );
+                ui.add_space(8.0);
+
+                ui.columns(2, |columns| {
+                    self.show_bucket(&mut columns[0], true);
+                    self.show_bucket(&mut columns[1], false);
+                });
+
+                ui.separator();
+                let first = self.in_first.iter().filter(|&&f| f).count();
+                let splittable = first > 0 && first < self.stats.len();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(splittable, egui::Button::new(tr("Split")))
+                        .on_disabled_hover_text(tr("Both changes need at least one file"))
+                        .clicked()
+                    {
+                        choice = Some(true);
+                    }
+                    if ui.button(tr("Cancel")).clicked() {
+                        choice = Some(false);
+                    }
+                });
+            });
+
+        if !open {
+            choice = Some(false);
+        }
+        choice
+    }
+
+    /// One side of the split, with its file list and line totals
+    fn show_bucket(&mut self, ui: &mut egui::Ui, first: bool) {
+        let (added, removed) = self
+            .stats
+            .iter()
+            .zip(&self.in_first)
+            .filter(|(_, f)| **f == first)
+            .fold((0, 0), |(a, r), (s, _)| (a + s.added, r + s.removed));
+
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new(if first {
+                    tr("First change")
+                } else {
+                    tr("Remains in @")
+                })
+                .strong(),
+            );
+            ui.label(RichText::new(format!("+{}", added)).color(FileStatus::Added.color()));
+            ui.label(RichText::new(format!("-{}", removed)).color(FileStatus::Deleted.color()));
+        });
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .id_salt(first)
+            .auto_shrink([false, false])
+            .max_height(320.0)
+            .show(ui, |ui| {
+                for (stat, in_first) in self.stats.iter().zip(self.in_first.iter_mut()) {
+                    if *in_first != first {
+                        continue;
+                    }
+                    ui.horizontal(|ui| {
+                        let arrow = if first { "→" } else { "←" };
+                        if ui
+                            .small_button(arrow)
+                            .on_hover_text(tr("Move to the other change"))
+                            .clicked()
+                        {
+                            *in_first = !first;
+                        }
+                        ui.label(
+                            RichText::new(format!("+{} -{}", stat.added, stat.removed))
+                                .monospace()
+                                .color(theme::dim_text(ui.visuals())),
+                        );
+                        ui.label(RichText::new(&stat.path).monospace());
+                    });
+                }
+            });
+    }
+}
@@ -0,0 +1,252 @@
+use eframe::egui::{self, RichText};
+use std::collections::BTreeSet;
+use std::sync::mpsc::{Receiver, channel};
+use std::thread;
+
+use crate::changed_files::FileStatus;
+use crate::i18n::{tr, trf};
+use crate::jj::{self, DiffSource};
+use crate::metadata_panel;
+use crate::theme;
+
+/// The commits on top of trunk up to `@`, leaving out an empty working
+/// copy that hasn't been described yet
+const STACK_REVSET: &str = r#"trunk()..@ ~ (@ & empty() & description(exact:""))"#;
+
+/// Fields are tab separated; the description goes last so tabs in it survive
+const STACK_TEMPLATE: &str =
+    r#"change_id ++ "\t" ++ commit_id ++ "\t" ++ description.first_line() ++ "\n""#;
+
+struct StackCommit {
+    change_id: String,
+    commit_id: String,
+    description: String,
+}
+
+/// Walks through the stack one commit at a time, oldest first, checking
+/// each off once its diff was read
+pub struct StackReview {
+    pub open: bool,
+    commits: Result<Vec<StackCommit>, String>,
+    loaded: bool,
+    /// `jj log` running in the background; the old stack shows meanwhile
+    loading: Option<Receiver<Result<Vec<StackCommit>, String>>>,
+    /// Commit IDs checked off. A rewritten commit gets a new ID and so
+    /// needs another look.
+    reviewed: BTreeSet<String>,
+    /// Go to the first unchecked commit once the stack is loaded
+    jump: bool,
+}
+
+impl Default for StackReview {
+    fn default() -> Self {
+        Self {
+            open: false,
+            commits: Ok(Vec::new()),
+            loaded: false,
+            loading: None,
+            reviewed: BTreeSet::new(),
+            jump: false,
+        }
+    }
+}
+
+impl StackReview {
+    /// Open the mode at the first commit not checked off yet
+    pub fn start(&mut self) {
+        self.open = true;
+        self.loaded = false;
+        self.jump = true;
+    }
+
+    /// Reload the stack the next time it's shown
+    pub fn invalidate(&mut self) {
+        self.loaded = false;
+    }
+
+    pub fn reviewed(&self) -> &BTreeSet<String> {
+        &self.reviewed
+    }
+
+    /// Replace the checked-off commits, e.g. with the ones saved for a repo
+    pub fn set_reviewed(&mut self, reviewed: BTreeSet<String>) {
+        self.reviewed = reviewed;
+    }
+
+    fn start_loading(&mut self, ctx: &egui::Context) {
+        let (sender, receiver) = channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let _ = sender.send(fetch_stack());
+            ctx.request_repaint();
+        });
+        self.loading = Some(receiver);
+        self.loaded = true;
+    }
+
+    fn poll_loading(&mut self) {
+        if let Some(receiver) = &self.loading
+            && let Ok(commits) = receiver.try_recv()
+        {
+            self.commits = commits;
+            self.loading = None;
+        }
+    }
+
+    /// Checklist of the stack with previous and next buttons. `source` is
+    /// what the sidebar shows, to tell the current commit. Returns the
+    /// commit to diff next.
+    pub fn show(&mut self, ui: &mut egui::Ui, source: &DiffSource) -> Option<DiffSource> {
+        if !self.loaded {
+            self.start_loading(ui.ctx());
+        }
+        self.poll_loading();
+        // Jumping waits for the fresh stack
+        if self.jump && self.loading.is_some() {
+            ui.spinner();
+            return None;
+        }
+        let commits = match &self.commits {
+            Ok(commits) => commits,
+            Err(e) => {
+                ui.label(RichText::new(e).color(FileStatus::Deleted.color()));
+                return None;
+            }
+        };
+        let diff = |i: usize| {
+            commits.get(i).map(|commit| DiffSource::Range {
+                from: format!("{}-", commit.commit_id),
+                to: commit.commit_id.clone(),
+            })
+        };
+        let mut next = None;
+        if std::mem::take(&mut self.jump) {
+            let first = commits
+                .iter()
+                .position(|c| !self.reviewed.contains(&c.commit_id))
+                .unwrap_or(0);
+            next = diff(first);
+        }
+
+        let current = source
+            .new_revision()
+            .and_then(|revision| commits.iter().position(|c| c.commit_id == revision));
+
+        ui.horizontal(|ui| {
+            ui.heading(tr("Stack review"));
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui
+                    .small_button("✕")
+                    .on_hover_text(tr("Close stack review"))
+                    .clicked()
+                {
+                    self.open = false;
+                }
+            });
+        });
+        if commits.is_empty() {
+            ui.label(tr("No commits between trunk and @"));
+            return next;
+        }
+
+        let done = commits
+            .iter()
+            .filter(|c| self.reviewed.contains(&c.commit_id))
+            .count();
+        ui.add(
+            egui::ProgressBar::new(done as f32 / commits.len() as f32)
+                .text(trf("{} of {} commits reviewed", &[&done, &commits.len()])),
+        );
+
+        ui.horizontal(|ui| {
+            let previous = current.and_then(|i| i.checked_sub(1));
+            if ui
+                .add_enabled(previous.is_some(), egui::Button::new(tr("◀ Previous")))
+                .clicked()
+            {
+                next = previous.and_then(diff);
+            }
+            let following = current.map_or(0, |i| i + 1);
+            if ui
+                .add_enabled(following < commits.len(), egui::Button::new(tr("Next ▶")))
+                .clicked()
+            {
+                next = diff(following);
+            }
+            if let Some(i) = current
+                && !self.reviewed.contains(&commits[i].commit_id)
+                && ui
+                    .button(tr("Reviewed, next"))
+                    .on_hover_text(tr("Check this commit off and go to the next one"))
+                    .clicked()
+            {
+                self.reviewed.insert(commits[i].commit_id.clone());
+                next = diff(i + 1);
+            }
+        });
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for (i, commit) in commits.iter().enumerate() {
+                    let mut checked = self.reviewed.contains(&commit.commit_id);
+                    ui.horizontal(|ui| {
+                        if ui.checkbox(&mut checked, "").changed() {
+                            if checked {
+                                self.reviewed.insert(commit.commit_id.clone());
+                            } else {
+                                self.reviewed.remove(&commit.commit_id);
+                            }
+                        }
+                        let description = match commit.description.as_str() {
+                            "" => tr("(no description)"),
+                            description => description,
+                        };
+                        let text = RichText::new(description).color(if checked {
+                            theme::dim_text(ui.visuals())
+                        } else {
+                            ui.visuals().text_color()
+                        });
+                        ui.label(
+                            RichText::new(jj::short_id(&commit.change_id, 8))
+                                .monospace()
+                                .color(metadata_panel::CHANGE_ID_COLOR),
+                        );
+                        if ui.selectable_label(current == Some(i), text).clicked() {
+                            next = diff(i);
+                        }
+                    });
+                }
+            });
+
+        next
+    }
+}
+
+fn fetch_stack() -> Result<Vec<StackCommit>, String> {
+    let output = jj::output_with_retry(jj::command().args([
+        "log",
+        "--no-graph",
+        "--reversed",
+        "-r",
+        STACK_REVSET,
+        "-T",
+        STACK_TEMPLATE,
+    ]));
+    match output {
+        Ok(o) if o.status.success() => Ok(String::from_utf8_lossy(&o.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                Some(StackCommit {
+                    change_id: fields.next()?.to_string(),
+                    commit_id: fields.next()?.to_string(),
+                    description: fields.next().unwrap_or("").to_string(),
+                })
+            })
+            .collect()),
+        Ok(o) => Err(String::from_utf8_lossy(&o.stderr).trim().to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
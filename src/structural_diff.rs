@@ -0,0 +1,217 @@
+use similar::{Algorithm, ChangeTag, DiffTag};
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tree_sitter::{Language, Parser, Tree};
+
+/// Token diffs are quadratic in the worst case; past this the result is
+/// still correct, just less minimal
+const DIFF_DEADLINE: Duration = Duration::from_secs(2);
+
+/// One row of a structural diff, pointing at 1-based lines of either file
+pub struct StructuralLine {
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    pub change_type: ChangeTag,
+}
+
+fn language_for(path: &str) -> Option<Language> {
+    match Path::new(path).extension().and_then(|e| e.to_str())? {
+        "rs" => Some(tree_sitter_rust::LANGUAGE.into()),
+        "py" => Some(tree_sitter_python::LANGUAGE.into()),
+        "js" | "mjs" | "cjs" | "jsx" => Some(tree_sitter_javascript::LANGUAGE.into()),
+        _ => None,
+    }
+}
+
+pub fn supports(path: &str) -> bool {
+    language_for(path).is_some()
+}
+
+/// The parsed file, for languages with a grammar when it parses cleanly
+pub fn syntax_tree(path: &str, content: &str) -> Option<Tree> {
+    parse(&language_for(path)?, content).ok()
+}
+
+/// A leaf of the syntax tree, e.g. an identifier, operator or comment
+struct Token<'a> {
+    text: &'a str,
+    /// 0-based; several lines for block comments and multi-line strings
+    lines: RangeInclusive<usize>,
+}
+
+/// Diff the syntax tokens of both sides rather than their lines, so
+/// reindenting, rewrapping or moving a brace onto its own line is not a
+/// change. The new file is shown whole, with old lines whose tokens were
+/// removed placed before the line that replaced them.
+pub fn diff(path: &str, old: &str, new: &str) -> Result<Vec<StructuralLine>, String> {
+    let language = language_for(path).ok_or("unsupported file type")?;
+    let old_tree = parse(&language, old).map_err(|e| format!("old side: {}", e))?;
+    let new_tree = parse(&language, new).map_err(|e| format!("new side: {}", e))?;
+    let old_tokens = tokens(&old_tree, old);
+    let new_tokens = tokens(&new_tree, new);
+
+    let old_texts: Vec<&str> = old_tokens.iter().map(|t| t.text).collect();
+    let new_texts: Vec<&str> = new_tokens.iter().map(|t| t.text).collect();
+    let ops = similar::capture_diff_slices_deadline(
+        Algorithm::Myers,
+        &old_texts,
+        &new_texts,
+        Some(Instant::now() + DIFF_DEADLINE),
+    );
+
+    let new_count = new.lines().count();
+    // The old line each new line's first matching token came from
+    let mut old_for_new: Vec<Option<usize>> = vec![None; new_count];
+    let mut new_changed = vec![false; new_count];
+    // Removed old lines, keyed by the new line they're shown before
+    let mut removed: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    let mut removed_seen = vec![false; old.lines().count()];
+
+    for op in ops {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        let anchor = new_tokens
+            .get(new_range.start)
+            .map_or(new_count, |t| *t.lines.start());
+        if tag == DiffTag::Equal {
+            for (o, n) in old_range.zip(new_range) {
+                if let Some(old_line) = old_for_new.get_mut(*new_tokens[n].lines.start()) {
+                    old_line.get_or_insert(*old_tokens[o].lines.start());
+                }
+            }
+            continue;
+        }
+        for o in old_range {
+            for line in old_tokens[o].lines.clone() {
+                if removed_seen.get(line) == Some(&false) {
+                    removed_seen[line] = true;
+                    removed.entry(anchor).or_default().push(line);
+                }
+            }
+        }
+        for n in new_range {
+            for line in new_tokens[n].lines.clone() {
+                if let Some(changed) = new_changed.get_mut(line) {
+                    *changed = true;
+                }
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut emit_removed = |anchor: usize, out: &mut Vec<StructuralLine>| {
+        for line in removed.remove(&anchor).unwrap_or_default() {
+            out.push(StructuralLine {
+                old_line: Some(line + 1),
+                new_line: None,
+                change_type: ChangeTag::Delete,
+            });
+        }
+    };
+    for line in 0..new_count {
+        emit_removed(line, &mut out);
+        let changed = new_changed[line];
+        out.push(StructuralLine {
+            old_line: if changed {
+                None
+            } else {
+                old_for_new[line].map(|l| l + 1)
+            },
+            new_line: Some(line + 1),
+            change_type: if changed {
+                ChangeTag::Insert
+            } else {
+                ChangeTag::Equal
+            },
+        });
+    }
+    emit_removed(new_count, &mut out);
+
+    Ok(out)
+}
+
+fn parse(language: &Language, content: &str) -> Result<Tree, String> {
+    let mut parser = Parser::new();
+    parser.set_language(language).map_err(|e| e.to_string())?;
+    let tree = parser.parse(content, None).ok_or("parser gave up")?;
+    if tree.root_node().has_error() {
+        return Err("syntax errors".to_string());
+    }
+    Ok(tree)
+}
+
+/// Leaves in document order. Whitespace isn't part of the tree, which is
+/// what makes formatting invisible to the diff.
+fn tokens<'a>(tree: &Tree, content: &'a str) -> Vec<Token<'a>> {
+    let mut tokens = Vec::new();
+    let mut cursor = tree.walk();
+    loop {
+        let node = cursor.node();
+        if node.child_count() == 0 {
+            let text = &content[node.byte_range()];
+            if !text.is_empty() {
+                tokens.push(Token {
+                    text,
+                    lines: node.start_position().row..=node.end_position().row,
+                });
+            }
+        }
+
+        if cursor.goto_first_child() || cursor.goto_next_sibling() {
+            continue;
+        }
+        loop {
+            if !cursor.goto_parent() {
+                return tokens;
+            }
+            if cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `=`, `+` or `-` per row
+    fn tags(path: &str, old: &str, new: &str) -> Result<String, String> {
+        let rows = diff(path, old, new)?;
+        Ok(rows
+            .iter()
+            .map(|row| match row.change_type {
+                ChangeTag::Equal => '=',
+                ChangeTag::Insert => '+',
+                ChangeTag::Delete => '-',
+            })
+            .collect())
+    }
+
+    #[test]
+    fn diffs_tokens_rather_than_lines() {
+        let cases = [
+            // Reindented
+            ("fn a() {\n    1\n}\n", "fn a() {\n        1\n}\n", "==="),
+            // Brace moved onto the signature's line
+            ("fn a()\n{\n}\n", "fn a() {\n}\n", "=="),
+            ("fn a() { 1 }\n", "fn a() { 2 }\n", "-+"),
+            ("x = 1\n", "x = 1\ny = 2\n", "=+"),
+        ];
+        for (old, new, expected) in cases {
+            let path = if old.starts_with("fn") {
+                "a.rs"
+            } else {
+                "a.py"
+            };
+            assert_eq!(tags(path, old, new).as_deref(), Ok(expected), "{:?}", new);
+        }
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_parse() {
+        assert!(tags("a.txt", "a\n", "b\n").is_err());
+        assert!(tags("a.rs", "fn a() {}\n", "fn a( {}\n").is_err());
+    }
+}
@@ -0,0 +1,204 @@
+use similar::{ChangeTag, TextDiff};
+use std::path::Path;
+
+/// Unchanged rows kept around each change
+const CONTEXT_ROWS: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RowChange {
+    Equal,
+    Added,
+    Removed,
+    /// A removed row replaced in place; cells hold the new values
+    Changed,
+}
+
+pub enum TableRow {
+    Record {
+        change: RowChange,
+        /// 1-based line in the new file, or the old one for removed rows
+        line: usize,
+        cells: Vec<String>,
+        /// Previous values of a changed row
+        old_cells: Option<Vec<String>>,
+    },
+    /// A run of unchanged rows left out
+    Skipped(usize),
+}
+
+pub struct TableDiff {
+    pub header: Vec<String>,
+    /// Set when the header row itself changed
+    pub old_header: Option<Vec<String>>,
+    pub rows: Vec<TableRow>,
+    /// Widest row on either side
+    pub columns: usize,
+}
+
+fn delimiter_for(path: &str) -> Option<char> {
+    match Path::new(path).extension().and_then(|e| e.to_str())? {
+        "csv" => Some(','),
+        "tsv" => Some('\t'),
+        _ => None,
+    }
+}
+
+pub fn supports(path: &str) -> bool {
+    delimiter_for(path).is_some()
+}
+
+/// Diff records line by line, treating the first line as the header.
+/// Runs of removed rows followed by added ones pair up as changed rows.
+pub fn diff(path: &str, old: &str, new: &str) -> Result<TableDiff, String> {
+    let delimiter = delimiter_for(path).ok_or("unsupported file type")?;
+    let (old_header, old_body) = split_header(old);
+    let (new_header, new_body) = split_header(new);
+
+    let header = parse_record(new_header.or(old_header).unwrap_or(""), delimiter);
+    let old_header = match (old_header, new_header) {
+        (Some(o), Some(n)) if o != n => Some(parse_record(o, delimiter)),
+        _ => None,
+    };
+
+    let diff = TextDiff::from_lines(old_body, new_body);
+    let mut records = Vec::new();
+    let (mut old_line, mut new_line) = (2usize, 2usize);
+    let mut removed: Vec<(usize, Vec<String>)> = Vec::new();
+
+    for change in diff.iter_all_changes() {
+        let cells = parse_record(change.value().trim_end_matches(['\n', '\r']), delimiter);
+        match change.tag() {
+            ChangeTag::Delete => {
+                removed.push((old_line, cells));
+                old_line += 1;
+            }
+            ChangeTag::Insert => {
+                let (change, old_cells) = if removed.is_empty() {
+                    (RowChange::Added, None)
+                } else {
+                    (RowChange::Changed, Some(removed.remove(0).1))
+                };
+                records.push(record(change, new_line, cells, old_cells));
+                new_line += 1;
+            }
+            ChangeTag::Equal => {
+                flush_removed(&mut removed, &mut records);
+                records.push(record(RowChange::Equal, new_line, cells, None));
+                old_line += 1;
+                new_line += 1;
+            }
+        }
+    }
+    flush_removed(&mut removed, &mut records);
+
+    let columns = records
+        .iter()
+        .filter_map(|r| match r {
+            TableRow::Record {
+                cells, old_cells, ..
+            } => Some(cells.len().max(old_cells.as_ref().map_or(0, Vec::len))),
+            TableRow::Skipped(_) => None,
+        })
+        .chain([header.len(), old_header.as_ref().map_or(0, Vec::len)])
+        .max()
+        .unwrap_or(0);
+
+    Ok(TableDiff {
+        header,
+        old_header,
+        rows: collapse_unchanged(records),
+        columns,
+    })
+}
+
+fn record(
+    change: RowChange,
+    line: usize,
+    cells: Vec<String>,
+    old_cells: Option<Vec<String>>,
+) -> TableRow {
+    TableRow::Record {
+        change,
+        line,
+        cells,
+        old_cells,
+    }
+}
+
+/// Removed rows that found no replacement
+fn flush_removed(removed: &mut Vec<(usize, Vec<String>)>, records: &mut Vec<TableRow>) {
+    for (line, cells) in removed.drain(..) {
+        records.push(record(RowChange::Removed, line, cells, None));
+    }
+}
+
+fn split_header(content: &str) -> (Option<&str>, &str) {
+    if content.is_empty() {
+        return (None, "");
+    }
+    match content.split_once('\n') {
+        Some((header, body)) => (Some(header.trim_end_matches('\r')), body),
+        None => (Some(content), ""),
+    }
+}
+
+/// Split one line into cells, honoring double quotes and `""` escapes.
+/// Quoted fields spanning lines aren't supported.
+fn parse_record(line: &str, delimiter: char) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            c if c == delimiter && !in_quotes => cells.push(std::mem::take(&mut cell)),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells
+}
+
+/// Replace long runs of unchanged rows with a count
+fn collapse_unchanged(records: Vec<TableRow>) -> Vec<TableRow> {
+    let is_equal = |r: &TableRow| {
+        matches!(
+            r,
+            TableRow::Record {
+                change: RowChange::Equal,
+                ..
+            }
+        )
+    };
+    let keep: Vec<bool> = (0..records.len())
+        .map(|i| {
+            let start = i.saturating_sub(CONTEXT_ROWS);
+            let end = (i + CONTEXT_ROWS + 1).min(records.len());
+            records[start..end].iter().any(|r| !is_equal(r))
+        })
+        .collect();
+
+    let mut out = Vec::new();
+    let mut skipped = 0;
+    for (row, keep) in records.into_iter().zip(keep) {
+        if keep {
+            if skipped > 0 {
+                out.push(TableRow::Skipped(skipped));
+                skipped = 0;
+            }
+            out.push(row);
+        } else {
+            skipped += 1;
+        }
+    }
+    if skipped > 0 {
+        out.push(TableRow::Skipped(skipped));
+    }
+    out
+}
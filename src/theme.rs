@@ -1,68 +1,108 @@
-use eframe::egui;
-
-pub fn set_rusty_theme(ctx: &egui::Context) {
-    let mut style = (*ctx.style()).clone();
-
-    style.text_styles = [
-        (
-            egui::TextStyle::Heading,
-            egui::FontId::new(24.0, egui::FontFamily::Proportional),
-        ),
-        (
-            egui::TextStyle::Name("Title".into()),
-            egui::FontId::new(20.0, egui::FontFamily::Proportional),
-        ),
-        (
-            egui::TextStyle::Body,
-            egui::FontId::new(18.0, egui::FontFamily::Proportional),
-        ),
-        (
-            egui::TextStyle::Button,
-            egui::FontId::new(17.0, egui::FontFamily::Proportional),
-        ),
-        (
-            egui::TextStyle::Small,
-            egui::FontId::new(15.0, egui::FontFamily::Proportional),
-        ),
-        (
-            egui::TextStyle::Monospace,
-            egui::FontId::new(14.0, egui::FontFamily::Monospace),
-        ),
-    ]
-    .into();
-
-    style.spacing.item_spacing = egui::vec2(10.0, 8.0);
-    style.spacing.button_padding = egui::vec2(14.0, 8.0);
-    style.spacing.window_margin = egui::Margin::same(12);
-
-    ctx.set_style(style);
+use eframe::egui::{self, Color32, Theme, ThemePreference};
 
+use crate::config::ThemeMode;
+
+/// Look settings the menu changes and saves to `config.toml`
+pub struct Appearance {
+    /// Multiplier on top of the OS scale factor
+    pub ui_scale: f32,
+    pub theme: ThemeMode,
+}
+
+/// Set fonts, spacing and both color schemes; egui switches between the
+/// two itself when `mode` follows the OS
+pub fn set_rusty_theme(ctx: &egui::Context, mode: ThemeMode) {
+    ctx.all_styles_mut(|style| {
+        style.text_styles = [
+            (
+                egui::TextStyle::Heading,
+                egui::FontId::new(24.0, egui::FontFamily::Proportional),
+            ),
+            (
+                egui::TextStyle::Name("Title".into()),
+                egui::FontId::new(20.0, egui::FontFamily::Proportional),
+            ),
+            (
+                egui::TextStyle::Body,
+                egui::FontId::new(18.0, egui::FontFamily::Proportional),
+            ),
+            (
+                egui::TextStyle::Button,
+                egui::FontId::new(17.0, egui::FontFamily::Proportional),
+            ),
+            (
+                egui::TextStyle::Small,
+                egui::FontId::new(15.0, egui::FontFamily::Proportional),
+            ),
+            (
+                egui::TextStyle::Monospace,
+                egui::FontId::new(14.0, egui::FontFamily::Monospace),
+            ),
+        ]
+        .into();
+
+        style.spacing.item_spacing = egui::vec2(10.0, 8.0);
+        style.spacing.button_padding = egui::vec2(14.0, 8.0);
+        style.spacing.window_margin = egui::Margin::same(12);
+    });
+
+    ctx.set_visuals_of(Theme::Dark, dark_visuals());
+    ctx.set_visuals_of(Theme::Light, light_visuals());
+    ctx.set_theme(match mode {
+        ThemeMode::System => ThemePreference::System,
+        ThemeMode::Dark => ThemePreference::Dark,
+        ThemeMode::Light => ThemePreference::Light,
+    });
+}
+
+fn dark_visuals() -> egui::Visuals {
     let mut visuals = egui::Visuals::dark();
 
     // Base Layers
-    visuals.panel_fill = egui::Color32::from_rgb(26, 28, 32); // deepest background
-    visuals.window_fill = egui::Color32::from_rgb(30, 32, 36); // content surface
-    visuals.faint_bg_color = egui::Color32::from_rgb(38, 40, 45);
+    visuals.panel_fill = Color32::from_rgb(26, 28, 32); // deepest background
+    visuals.window_fill = Color32::from_rgb(30, 32, 36); // content surface
+    visuals.faint_bg_color = Color32::from_rgb(38, 40, 45);
+
+    // Text
+    visuals.override_text_color = Some(Color32::from_rgb(220, 220, 215));
+
+    // Widgets
+    visuals.widgets.inactive.bg_fill = Color32::from_rgb(42, 45, 50);
+    visuals.widgets.inactive.bg_stroke = egui::Stroke::new(1.0, Color32::from_rgb(60, 65, 70));
+
+    rust_accent(visuals)
+}
+
+fn light_visuals() -> egui::Visuals {
+    let mut visuals = egui::Visuals::light();
+
+    // Base Layers
+    visuals.panel_fill = Color32::from_rgb(244, 242, 238); // deepest background
+    visuals.window_fill = Color32::from_rgb(250, 249, 246); // content surface
+    visuals.faint_bg_color = Color32::from_rgb(234, 231, 226);
 
     // Text
-    visuals.override_text_color = Some(egui::Color32::from_rgb(220, 220, 215));
+    visuals.override_text_color = Some(Color32::from_rgb(40, 40, 44));
 
-    // Rust Accent
-    let rust = egui::Color32::from_rgb(190, 80, 20);
-    let rust_hover = egui::Color32::from_rgb(210, 95, 30);
-    let rust_active = egui::Color32::from_rgb(160, 60, 10);
+    // Widgets
+    visuals.widgets.inactive.bg_fill = Color32::from_rgb(226, 223, 218);
+    visuals.widgets.inactive.bg_stroke = egui::Stroke::new(1.0, Color32::from_rgb(200, 196, 190));
+
+    rust_accent(visuals)
+}
+
+/// The accent and rounding both schemes share
+fn rust_accent(mut visuals: egui::Visuals) -> egui::Visuals {
+    let rust = Color32::from_rgb(190, 80, 20);
+    let rust_hover = Color32::from_rgb(210, 95, 30);
+    let rust_active = Color32::from_rgb(160, 60, 10);
 
     visuals.selection.bg_fill = rust;
     visuals.selection.stroke = egui::Stroke::new(1.0, rust_hover);
 
-    // Widgets
-    visuals.widgets.inactive.bg_fill = egui::Color32::from_rgb(42, 45, 50);
     visuals.widgets.hovered.bg_fill = rust_hover;
     visuals.widgets.active.bg_fill = rust_active;
 
-    visuals.widgets.inactive.bg_stroke =
-        egui::Stroke::new(1.0, egui::Color32::from_rgb(60, 65, 70));
-
     visuals.widgets.hovered.bg_stroke = egui::Stroke::new(1.0, rust);
 
     visuals.widgets.active.bg_stroke = egui::Stroke::new(1.0, rust_hover);
@@ -76,5 +116,42 @@ pub fn set_rusty_theme(ctx: &egui::Context) {
     visuals.widgets.hovered.corner_radius = rounding;
     visuals.widgets.active.corner_radius = rounding;
 
-    ctx.set_visuals(visuals);
+    visuals
+}
+
+/// Background of a sidebar or log row
+pub fn row_fill(visuals: &egui::Visuals, selected: bool, hovered: bool) -> Color32 {
+    match (visuals.dark_mode, selected, hovered) {
+        (_, false, false) => Color32::TRANSPARENT,
+        (true, true, _) => Color32::from_rgb(55, 60, 70),
+        (true, false, true) => Color32::from_rgb(45, 48, 54),
+        (false, true, _) => Color32::from_rgb(220, 215, 208),
+        (false, false, true) => Color32::from_rgb(232, 228, 222),
+    }
+}
+
+/// Line numbers, context prefixes and other secondary text
+pub fn dim_text(visuals: &egui::Visuals) -> Color32 {
+    if visuals.dark_mode {
+        Color32::from_rgb(100, 100, 110)
+    } else {
+        Color32::from_rgb(140, 138, 134)
+    }
+}
+
+pub fn title_bar_fill(visuals: &egui::Visuals) -> Color32 {
+    if visuals.dark_mode {
+        Color32::from_rgb(34, 36, 40)
+    } else {
+        Color32::from_rgb(236, 233, 228)
+    }
+}
+
+/// Sidebar and inset panels, set apart from the content surface
+pub fn sidebar_fill(visuals: &egui::Visuals) -> Color32 {
+    if visuals.dark_mode {
+        Color32::from_rgb(36, 38, 43)
+    } else {
+        Color32::from_rgb(241, 238, 233)
+    }
 }
@@ -1,28 +1,338 @@
 use eframe::egui;
 use tracing::debug;
 
-pub fn show(ui: &mut egui::Ui, title: &str) {
+use crate::changed_files;
+use crate::config::ThemeMode;
+use crate::danger_zone::DangerAction;
+use crate::i18n::{tr, trf};
+use crate::theme::{self, Appearance};
+
+/// Longer descriptions are cut short with an ellipsis
+const DESCRIPTION_CHARS: usize = 60;
+
+pub struct Title<'a> {
+    pub name: &'a str,
+    /// First line of the working-copy change's description
+    pub description: &'a str,
+    /// Name of the repository's root directory
+    pub repo: &'a str,
+    /// Directory the sidebar is filtered to
+    pub directory: Option<&'a str>,
+    /// Path of the selected file, whose directories extend the breadcrumbs
+    pub path: Option<&'a str>,
+}
+
+pub enum TitleBarAction {
+    Split,
+    PastePatch,
+    Fetch,
+    Push,
+    ToggleSplit,
+    ToggleMetadata,
+    ToggleSidebar,
+    SwitchWorkspace(String),
+    Danger(DangerAction),
+    /// The UI scale slider was released; `appearance` already holds the value
+    SaveUiScale,
+    /// A theme was picked; `appearance` already holds it
+    SaveTheme,
+    ReviewSummary,
+    /// Step through the commits between trunk and `@`
+    StackReview,
+    /// Compare the files on disk with the last snapshot
+    CompareSnapshot,
+    /// The description next to the title was clicked
+    EditDescription,
+    /// A breadcrumb was clicked; None is the repository itself
+    FilterDirectory(Option<String>),
+    Help,
+    OpenRepository,
+    SavePatch,
+    Close,
+}
+
+/// `busy` disables actions that would start another jj operation and
+/// `read_only` hides them. The workspace selector only appears when the
+/// repo has more than one.
+pub fn show(
+    ui: &mut egui::Ui,
+    title: &Title,
+    busy: bool,
+    workspaces: &[String],
+    current_workspace: Option<&str>,
+    appearance: &mut Appearance,
+    read_only: bool,
+) -> Option<TitleBarAction> {
+    let mut action = None;
+
     egui::Frame::new()
-        .fill(egui::Color32::from_rgb(34, 36, 40))
+        .fill(theme::title_bar_fill(ui.visuals()))
         .inner_margin(egui::Margin::symmetric(20, 14))
         .show(ui, |ui| {
             ui.horizontal(|ui| {
-                ui.label(egui::RichText::new(title).text_style(egui::TextStyle::Heading));
+                ui.label(egui::RichText::new(title.name).text_style(egui::TextStyle::Heading));
+                if let Some(clicked) = breadcrumbs(ui, title) {
+                    action = Some(clicked);
+                }
+                if ui
+                    .add(
+                        egui::Label::new(
+                            egui::RichText::new(description_label(title.description)).weak(),
+                        )
+                        .sense(egui::Sense::click()),
+                    )
+                    .on_hover_cursor(egui::CursorIcon::PointingHand)
+                    .on_hover_text(tr("Edit the description of the working-copy change"))
+                    .clicked()
+                {
+                    action = Some(TitleBarAction::EditDescription);
+                }
 
                 ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
                     ui.menu_button("☰", |ui| {
                         ui.set_min_width(180.0);
-                        ui.label("Menu item 1");
-                        ui.label("Menu item 2");
+                        if !read_only {
+                            ui.add_enabled_ui(!busy, |ui| {
+                                if ui
+                                    .button(tr("Split change..."))
+                                    .on_hover_text(tr(
+                                        "Move some files of @ into a new change before it",
+                                    ))
+                                    .clicked()
+                                {
+                                    action = Some(TitleBarAction::Split);
+                                }
+                                if ui
+                                    .button(tr("Paste patch"))
+                                    .on_hover_text(tr(
+                                        "Preview and apply a unified diff from the clipboard",
+                                    ))
+                                    .clicked()
+                                {
+                                    action = Some(TitleBarAction::PastePatch);
+                                }
+                                ui.menu_button(tr("Danger zone"), |ui| {
+                                    if ui.button(tr("Abandon current change")).clicked() {
+                                        action =
+                                            Some(TitleBarAction::Danger(DangerAction::Abandon));
+                                    }
+                                    if ui.button(tr("Undo last operation")).clicked() {
+                                        action = Some(TitleBarAction::Danger(DangerAction::Undo));
+                                    }
+                                });
+                            });
+                            ui.separator();
+                        }
+
+                        if ui.button(tr("Open repository...")).clicked() {
+                            action = Some(TitleBarAction::OpenRepository);
+                        }
+                        if ui
+                            .button(tr("Save patch..."))
+                            .on_hover_text(tr("Write the whole diff as a git patch"))
+                            .clicked()
+                        {
+                            action = Some(TitleBarAction::SavePatch);
+                        }
+                        if ui
+                            .button(tr("Unsnapshotted edits"))
+                            .on_hover_text(tr(
+                                "Compare the files on disk with what jj last snapshotted, without taking a new snapshot",
+                            ))
+                            .clicked()
+                        {
+                            action = Some(TitleBarAction::CompareSnapshot);
+                        }
+                        if ui
+                            .button(tr("Review summary..."))
+                            .on_hover_text(tr("File states, comments and notes as Markdown"))
+                            .clicked()
+                        {
+                            action = Some(TitleBarAction::ReviewSummary);
+                        }
+                        if ui
+                            .button(tr("Stack review"))
+                            .on_hover_text(tr(
+                                "Check off each commit between trunk and @, oldest first",
+                            ))
+                            .clicked()
+                        {
+                            action = Some(TitleBarAction::StackReview);
+                        }
+                        if ui
+                            .add(egui::Button::new(tr("Help")).shortcut_text("?"))
+                            .clicked()
+                        {
+                            action = Some(TitleBarAction::Help);
+                        }
+                        ui.separator();
+
+                        let response = ui
+                            .add(
+                                egui::Slider::new(&mut appearance.ui_scale, 0.75..=2.0)
+                                    .text(tr("UI scale"))
+                                    .custom_formatter(|v, _| format!("{:.0}%", v * 100.0))
+                                    .custom_parser(|s| {
+                                        s.trim_end_matches('%')
+                                            .trim()
+                                            .parse::<f64>()
+                                            .ok()
+                                            .map(|p| p / 100.0)
+                                    }),
+                            )
+                            .on_hover_text(tr("Relative to the display's own scale factor"));
+                        if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                            action = Some(TitleBarAction::SaveUiScale);
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label(tr("Theme"));
+                            for mode in ThemeMode::ALL {
+                                let label = match mode {
+                                    ThemeMode::System => tr("System"),
+                                    ThemeMode::Dark => tr("Dark"),
+                                    ThemeMode::Light => tr("Light"),
+                                };
+                                if ui.radio_value(&mut appearance.theme, mode, label).changed() {
+                                    action = Some(TitleBarAction::SaveTheme);
+                                }
+                            }
+                        });
+                    });
+
+                    if workspaces.len() > 1 {
+                        egui::ComboBox::from_id_salt("workspace_selector")
+                            .selected_text(current_workspace.unwrap_or("workspace"))
+                            .show_ui(ui, |ui| {
+                                for name in workspaces {
+                                    let selected = current_workspace == Some(name.as_str());
+                                    if ui.selectable_label(selected, name).clicked() && !selected {
+                                        action =
+                                            Some(TitleBarAction::SwitchWorkspace(name.clone()));
+                                    }
+                                }
+                            });
+                    }
+
+                    ui.add_enabled_ui(!busy, |ui| {
+                        if read_only {
+                            return;
+                        }
+                        if ui
+                            .button(tr("Fetch"))
+                            .on_hover_text("jj git fetch")
+                            .clicked()
+                        {
+                            action = Some(TitleBarAction::Fetch);
+                        }
+                        if ui
+                            .button(tr("Push"))
+                            .on_hover_text(tr("jj git push (tracked bookmarks)"))
+                            .clicked()
+                        {
+                            action = Some(TitleBarAction::Push);
+                        }
                     });
                 });
 
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::LEFT), |ui| {
                     if ui.button("X").clicked() {
-                        debug!("Close app button clicked... std::process::exit(0)");
-                        std::process::exit(0);
+                        debug!("Close app button clicked...");
+                        action = Some(TitleBarAction::Close);
+                    }
+                    if ui
+                        .button(tr("Split"))
+                        .on_hover_text(tr(
+                            "View two files at once; click a pane to bind the sidebar",
+                        ))
+                        .clicked()
+                    {
+                        action = Some(TitleBarAction::ToggleSplit);
+                    }
+                    if ui
+                        .button(tr("Info"))
+                        .on_hover_text(tr("Show details of the revision being diffed"))
+                        .clicked()
+                    {
+                        action = Some(TitleBarAction::ToggleMetadata);
+                    }
+                    if ui
+                        .button(tr("Files"))
+                        .on_hover_text(tr("Show or hide the changed files"))
+                        .clicked()
+                    {
+                        action = Some(TitleBarAction::ToggleSidebar);
                     }
                 });
             });
         });
+
+    action
+}
+
+/// `repo › src › ui` for the sidebar's directory filter, continued down to
+/// the selected file's directory when it lies below it. Segments inside the
+/// filter are strong; clicking any of them filters to it.
+fn breadcrumbs(ui: &mut egui::Ui, title: &Title) -> Option<TitleBarAction> {
+    if title.repo.is_empty() {
+        return None;
+    }
+    let file_directory = title
+        .path
+        .and_then(|path| path.rsplit_once('/'))
+        .map(|(directory, _)| directory)
+        .filter(|directory| {
+            title.directory.is_none_or(|filter| {
+                directory == &filter || changed_files::in_directory(directory, filter)
+            })
+        });
+    let trail = file_directory.or(title.directory).unwrap_or("");
+    let filtered = title.directory.map_or(0, str::len);
+
+    let mut action = None;
+    let mut crumb = |ui: &mut egui::Ui, text: &str, strong: bool, target: Option<String>| {
+        let text = egui::RichText::new(text).monospace();
+        let text = if strong { text.strong() } else { text.weak() };
+        let hover = match &target {
+            Some(directory) => trf("Only list files in {}/", &[directory]),
+            None => tr("List files in every directory").to_string(),
+        };
+        if ui
+            .add(egui::Label::new(text).sense(egui::Sense::click()))
+            .on_hover_cursor(egui::CursorIcon::PointingHand)
+            .on_hover_text(hover)
+            .clicked()
+        {
+            action = Some(TitleBarAction::FilterDirectory(target));
+        }
+    };
+
+    crumb(ui, title.repo, true, None);
+    if !trail.is_empty() {
+        for (end, part) in segments(trail) {
+            ui.weak("›");
+            let strong = end <= filtered;
+            crumb(ui, part, strong, Some(trail[..end].to_string()));
+        }
+    }
+    action
+}
+
+/// Each component of `directory` with the byte offset where it ends
+fn segments(directory: &str) -> impl Iterator<Item = (usize, &str)> {
+    directory.split('/').scan(0, |end, part| {
+        let start = if *end == 0 { 0 } else { *end + 1 };
+        *end = start + part.len();
+        Some((*end, part))
+    })
+}
+
+fn description_label(description: &str) -> String {
+    if description.is_empty() {
+        return tr("(no description)").to_string();
+    }
+    if description.chars().count() <= DESCRIPTION_CHARS {
+        return description.to_string();
+    }
+    let cut: String = description.chars().take(DESCRIPTION_CHARS - 1).collect();
+    format!("{}…", cut.trim_end())
 }
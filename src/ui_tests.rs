@@ -0,0 +1,329 @@
+//! Snapshot tests of the sidebar and diff viewer against jj repos built in
+//! temp dirs. Run `UPDATE_SNAPSHOTS=1 cargo test` to accept new snapshots
+//! after a deliberate UI change.
+
+use eframe::egui;
+use egui_kittest::Harness;
+use egui_kittest::kittest::Queryable;
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::changed_files::{self, ChangedFile, ChangedFilesCache, FileStatus};
+use crate::config::{Config, ThemeMode};
+use crate::diff_viewer::{DiffViewMode, DiffViewer};
+use crate::jj;
+use crate::review::Review;
+use crate::theme;
+
+/// The workspace root is global, so fixtures take turns
+static WORKSPACE: Mutex<()> = Mutex::new(());
+
+const LIB_BEFORE: &str = "\
+pub fn greet(name: &str) -> String {
+    format!(\"Hello, {}!\", name)
+}
+
+pub fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+";
+
+const LIB_AFTER: &str = "\
+pub fn greet(name: &str) -> String {
+    format!(\"Hello there, {}!\", name)
+}
+
+pub fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+pub fn sub(a: i32, b: i32) -> i32 {
+    a - b
+}
+";
+
+/// A jj repo with one commit of `committed` files and `working` applied on
+/// top in the working copy, where None deletes the file. It's the selected
+/// workspace until dropped.
+struct Fixture {
+    dir: tempfile::TempDir,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Fixture {
+    /// None when jj isn't installed, so the test can be skipped
+    fn new(committed: &[(&str, &str)], working: &[(&str, Option<&str>)]) -> Option<Self> {
+        if Command::new("jj").arg("--version").output().is_err() {
+            eprintln!("jj isn't on PATH, skipping");
+            return None;
+        }
+        let lock = WORKSPACE.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().expect("create temp dir");
+        jj_in(dir.path(), &["git", "init"]);
+        for (path, content) in committed {
+            write(dir.path(), path, Some(content));
+        }
+        jj_in(dir.path(), &["commit", "-m", "Fixture"]);
+        for (path, content) in working {
+            write(dir.path(), path, *content);
+        }
+
+        jj::set_workspace_root(Some(dir.path().to_path_buf()));
+        Some(Self { dir, _lock: lock })
+    }
+
+    fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        jj::set_workspace_root(None);
+    }
+}
+
+fn jj_in(dir: &Path, args: &[&str]) {
+    let output = Command::new("jj")
+        .current_dir(dir)
+        .args([
+            "--config",
+            "user.name=Test",
+            "--config",
+            "user.email=test@example.com",
+        ])
+        .args(args)
+        .output()
+        .expect("run jj");
+    assert!(
+        output.status.success(),
+        "jj {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn write(dir: &Path, path: &str, content: Option<&str>) {
+    let full = dir.join(path);
+    match content {
+        Some(content) => {
+            std::fs::create_dir_all(full.parent().unwrap()).unwrap();
+            std::fs::write(full, content).unwrap();
+        }
+        None => std::fs::remove_file(full).unwrap(),
+    }
+}
+
+/// Thirty numbered lines with `changed` ones reworded
+pub(crate) fn numbered(changed: &[usize]) -> String {
+    (1..=30)
+        .map(|i| match changed.contains(&i) {
+            true => format!("// changed {}\n", i),
+            false => format!("// line {}\n", i),
+        })
+        .collect()
+}
+
+/// One modified, one added and one deleted file
+fn mixed_changes() -> Option<Fixture> {
+    Fixture::new(
+        &[
+            ("src/lib.rs", LIB_BEFORE),
+            ("README.md", "# Fixture\n"),
+            ("notes.txt", "Remove me\n"),
+        ],
+        &[
+            ("src/lib.rs", Some(LIB_AFTER)),
+            ("src/new.rs", Some("pub const NEW: bool = true;\n")),
+            ("notes.txt", None),
+        ],
+    )
+}
+
+/// `app` drawn in a central panel framed like the main window's
+fn harness<'a, State>(
+    size: [f32; 2],
+    state: State,
+    mut app: impl FnMut(&mut egui::Ui, &mut State) + 'a,
+) -> Harness<'a, State> {
+    let harness = Harness::builder().with_size(size).build_state(
+        move |ctx, state| {
+            egui::CentralPanel::default()
+                .frame(
+                    egui::Frame::new()
+                        .fill(ctx.style().visuals.window_fill)
+                        .inner_margin(egui::Margin::symmetric(24, 20)),
+                )
+                .show(ctx, |ui| app(ui, state));
+        },
+        state,
+    );
+    theme::set_rusty_theme(&harness.ctx, ThemeMode::Dark);
+    harness
+}
+
+struct SidebarState {
+    cache: ChangedFilesCache,
+    selected: Option<usize>,
+    review: Review,
+}
+
+#[test]
+fn sidebar_lists_working_copy_changes() {
+    let Some(_fixture) = mixed_changes() else {
+        return;
+    };
+    let mut cache = ChangedFilesCache::default();
+    let mut files: Vec<(String, FileStatus)> = cache
+        .get_files()
+        .iter()
+        .map(|f| (f.path.clone(), f.status.clone()))
+        .collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        files,
+        [
+            ("notes.txt".to_string(), FileStatus::Deleted),
+            ("src/lib.rs".to_string(), FileStatus::Modified),
+            ("src/new.rs".to_string(), FileStatus::Added),
+        ]
+    );
+
+    let state = SidebarState {
+        cache,
+        selected: None,
+        review: Review::default(),
+    };
+    let mut harness = harness([320.0, 360.0], state, |ui, state| {
+        changed_files::show(
+            ui,
+            &mut state.cache,
+            &mut state.selected,
+            &mut state.review,
+            false,
+            false,
+        );
+    });
+    harness.run();
+    harness.snapshot("sidebar");
+}
+
+struct DiffState {
+    viewer: DiffViewer,
+    file: ChangedFile,
+    view_mode: DiffViewMode,
+}
+
+/// Steps until the viewer's worker is done; `run` alone stops at the
+/// first frame that only asks for a delayed repaint
+fn wait_for_diff(harness: &mut Harness<'_, DiffState>) {
+    for _ in 0..200 {
+        harness.step();
+        if harness.state().viewer.is_loaded() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert!(harness.state().viewer.is_loaded(), "diff never loaded");
+    harness.run();
+}
+
+fn diff_harness(file: ChangedFile, view_mode: DiffViewMode) -> Harness<'static, DiffState> {
+    let state = DiffState {
+        viewer: DiffViewer::new(Arc::new(Config::default())),
+        file,
+        view_mode,
+    };
+    let mut harness = harness([1800.0, 480.0], state, |ui, state| {
+        state
+            .viewer
+            .show(ui, Some(&state.file), &mut state.view_mode, false);
+    });
+    wait_for_diff(&mut harness);
+    harness
+}
+
+fn modified_lib() -> ChangedFile {
+    ChangedFile {
+        path: "src/lib.rs".to_string(),
+        old_path: None,
+        status: FileStatus::Modified,
+    }
+}
+
+#[test]
+fn diff_side_by_side() {
+    let Some(_fixture) = mixed_changes() else {
+        return;
+    };
+    let mut harness = diff_harness(modified_lib(), DiffViewMode::SideBySide);
+    harness.snapshot("diff_side_by_side");
+}
+
+#[test]
+fn diff_inline() {
+    let Some(_fixture) = mixed_changes() else {
+        return;
+    };
+    let mut harness = diff_harness(modified_lib(), DiffViewMode::Inline);
+    harness.snapshot("diff_inline");
+}
+
+#[test]
+fn diff_full_context() {
+    let Some(_fixture) = Fixture::new(
+        &[("src/long.rs", &numbered(&[]))],
+        &[("src/long.rs", Some(&numbered(&[3, 28])))],
+    ) else {
+        return;
+    };
+    let file = ChangedFile {
+        path: "src/long.rs".to_string(),
+        old_path: None,
+        status: FileStatus::Modified,
+    };
+    let mut harness = diff_harness(file, DiffViewMode::Inline);
+    harness
+        .get_all_by_label("Full context")
+        .nth(1)
+        .unwrap()
+        .click();
+    // One frame takes the click, the next starts the recomputed diff
+    harness.step();
+    harness.step();
+    wait_for_diff(&mut harness);
+    assert!(harness.query_by_label("Less context").is_some());
+    harness.snapshot("diff_full_context");
+}
+
+#[test]
+fn diff_added_file() {
+    let Some(fixture) = mixed_changes() else {
+        return;
+    };
+    assert!(fixture.path().join("src/new.rs").exists());
+    let added = ChangedFile {
+        path: "src/new.rs".to_string(),
+        old_path: None,
+        status: FileStatus::Added,
+    };
+    let mut harness = diff_harness(added, DiffViewMode::SideBySide);
+    harness.snapshot("diff_added");
+}
+
+#[test]
+fn diff_deleted_file() {
+    let Some(fixture) = mixed_changes() else {
+        return;
+    };
+    assert!(!fixture.path().join("notes.txt").exists());
+    let deleted = ChangedFile {
+        path: "notes.txt".to_string(),
+        old_path: None,
+        status: FileStatus::Deleted,
+    };
+    let mut harness = diff_harness(deleted, DiffViewMode::Inline);
+    harness.snapshot("diff_deleted");
+}
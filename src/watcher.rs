@@ -0,0 +1,183 @@
+use eframe::egui;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, channel};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::git;
+
+/// Saves usually come in bursts (write, rename, format-on-save), so wait
+/// for things to settle before refetching
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watches a workspace for file writes, for `refresh = "watch"`
+pub struct RepoWatcher {
+    root: PathBuf,
+    /// None when watching failed; dropping it stops watching
+    _watcher: Option<RecommendedWatcher>,
+    receiver: Receiver<()>,
+    /// Last change seen and not yet reported
+    pending_since: Option<Instant>,
+}
+
+impl RepoWatcher {
+    /// A watcher that fails to start is logged and reports nothing, so it
+    /// isn't retried every frame
+    pub fn new(root: &Path, ctx: &egui::Context) -> Self {
+        let (sender, receiver) = channel();
+        let ctx = ctx.clone();
+        let mut ignored = IgnoreCache::new(root);
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            // jj writes its own store on every command, including our refresh
+            let in_store = |path: &PathBuf| {
+                path.components()
+                    .any(|c| c.as_os_str() == ".jj" || c.as_os_str() == ".git")
+            };
+            if event.kind.is_access()
+                || event
+                    .paths
+                    .iter()
+                    .all(|path| in_store(path) || ignored.is_ignored(path))
+            {
+                return;
+            }
+            let _ = sender.send(());
+            ctx.request_repaint_after(DEBOUNCE);
+        })
+        .and_then(|mut watcher| {
+            watcher.watch(root, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        });
+        if let Err(e) = &watcher {
+            warn!("Can't watch {}: {}", root.display(), e);
+        }
+
+        Self {
+            root: root.to_path_buf(),
+            _watcher: watcher.ok(),
+            receiver,
+            pending_since: None,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// True once when writes have been quiet for `DEBOUNCE`
+    pub fn poll(&mut self, ctx: &egui::Context) -> bool {
+        if self.receiver.try_iter().count() > 0 {
+            self.pending_since = Some(Instant::now());
+        }
+        match self.pending_since {
+            Some(since) if since.elapsed() >= DEBOUNCE => {
+                self.pending_since = None;
+                true
+            }
+            Some(since) => {
+                ctx.request_repaint_after(DEBOUNCE - since.elapsed());
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+/// Remembers which paths git ignores, so build output like `target/`
+/// doesn't trigger refreshes. Directories are checked before what's in
+/// them, so a busy ignored directory costs one `git check-ignore`.
+struct IgnoreCache {
+    root: PathBuf,
+    /// Only colocated repos have a git working tree to ask
+    enabled: bool,
+    ignored: HashMap<PathBuf, bool>,
+}
+
+impl IgnoreCache {
+    fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            enabled: root.join(".git").exists(),
+            ignored: HashMap::new(),
+        }
+    }
+
+    /// Whether `path` or a directory above it is ignored and untracked
+    fn is_ignored(&mut self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        if !self.enabled {
+            return false;
+        }
+        // New rules may change any answer
+        if relative
+            .file_name()
+            .is_some_and(|name| name == ".gitignore")
+        {
+            self.ignored.clear();
+            return false;
+        }
+        let ancestors: Vec<&Path> = relative
+            .ancestors()
+            .filter(|p| !p.as_os_str().is_empty())
+            .collect();
+        if ancestors
+            .iter()
+            .any(|p| self.ignored.get(*p) == Some(&true))
+        {
+            return true;
+        }
+
+        let unknown: Vec<String> = ancestors
+            .iter()
+            .filter(|p| !self.ignored.contains_key(**p))
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect();
+        if unknown.is_empty() {
+            return false;
+        }
+        let hits = git::untracked_ignored(&self.root, &unknown);
+        for name in unknown {
+            let ignored = hits.contains(&name);
+            self.ignored.insert(PathBuf::from(name), ignored);
+        }
+        ancestors
+            .iter()
+            .any(|p| self.ignored.get(*p) == Some(&true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn skips_ignored_untracked_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        if Command::new("git")
+            .arg("init")
+            .current_dir(root)
+            .output()
+            .is_err()
+        {
+            return;
+        }
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        std::fs::create_dir_all(root.join("target/debug")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+
+        let mut cache = IgnoreCache::new(root);
+        assert!(cache.is_ignored(&root.join("target/debug/app")));
+        assert!(cache.is_ignored(&root.join("build.log")));
+        assert!(!cache.is_ignored(&root.join("src/main.rs")));
+        assert!(!cache.is_ignored(&root.join(".gitignore")));
+    }
+}